@@ -0,0 +1,160 @@
+//! Per-token cost of turning upstream SSE events into client chunks.
+//!
+//! `legacy` is the previous path: parse into owned `OpenAIResponse`, rebuild a
+//! `ChatCompletionChunk` with freshly formatted fields, then serialize. `templated`
+//! borrows from the event and renders into a per-request `ChunkTemplate`.
+//! Allocations per token are printed before the timings.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use loro::{
+    chunk::{ChunkTemplate, CHUNK_OBJECT},
+    models::{ChatCompletionChunk, ChoiceDelta, MessageDelta, OpenAIResponse},
+    service::LoroService,
+    sse::SseDecoder,
+    usage::UsageMeter,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const REQUEST_ID: &str = "5f0c6f3e-8d1a-4d59-9a43-3f1f1f8b2c7d";
+const MODEL: &str = "loro-voice-assistant";
+const TOKENS: [&str; 6] = ["今天", "是晴天", "，", "适合", "出门", "。"];
+
+/// A content delta as DeepSeek-style OpenAI-compatible upstreams send it
+fn event_data(token: &str) -> String {
+    format!(
+        r#"{{"id":"chatcmpl-0a1b2c","object":"chat.completion.chunk","created":1700000000,"model":"deepseek-chat","system_fingerprint":"fp_1","choices":[{{"index":0,"delta":{{"content":"{token}"}},"logprobs":null,"finish_reason":null}}]}}"#
+    )
+}
+
+fn legacy_chunk(json_data: &str, request_id: &str, model_name: &str) -> Option<String> {
+    let upstream: OpenAIResponse = serde_json::from_str(json_data).ok()?;
+    let choice = upstream.choices.first()?;
+    let delta = choice.delta.as_ref()?;
+    let content = delta.content.as_ref().filter(|c| !c.is_empty())?;
+    let chunk = ChatCompletionChunk {
+        id: format!("chatcmpl-{request_id}"),
+        object: CHUNK_OBJECT.to_string(),
+        created: chrono::Utc::now().timestamp(),
+        model: model_name.to_string(),
+        choices: vec![ChoiceDelta {
+            index: 0,
+            delta: MessageDelta {
+                role: delta.role.clone(),
+                content: Some(content.clone()),
+                tool_calls: None,
+            },
+            finish_reason: choice.finish_reason.clone(),
+        }],
+        usage: None,
+    };
+    let json_str = serde_json::to_string(&chunk).ok()?;
+    (json_str.len() <= 1024 * 1024).then_some(json_str)
+}
+
+fn templated_chunk(
+    json_data: &str,
+    template: &ChunkTemplate,
+    meter: &mut UsageMeter,
+) -> Option<String> {
+    LoroService::process_sse_data(json_data, template, meter).unwrap()
+}
+
+/// Whole reply as the upstream streams it, `[DONE]` included
+fn sse_body(tokens: usize) -> Vec<u8> {
+    let mut body = String::new();
+    for token in TOKENS.iter().cycle().take(tokens) {
+        body += &format!("data: {}\n\n", event_data(token));
+    }
+    body += "data: [DONE]\n\n";
+    body.into_bytes()
+}
+
+fn allocations_per_token(mut convert: impl FnMut(&str) -> Option<String>) -> f64 {
+    let events: Vec<String> = TOKENS.iter().map(|token| event_data(token)).collect();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for data in &events {
+        black_box(convert(data));
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / events.len() as f64
+}
+
+fn report_allocations() {
+    let template = ChunkTemplate::new(REQUEST_ID, MODEL);
+    let mut meter = UsageMeter::default();
+    let legacy = allocations_per_token(|data| legacy_chunk(data, REQUEST_ID, MODEL));
+    let templated = allocations_per_token(|data| templated_chunk(data, &template, &mut meter));
+    println!("allocations per token: legacy {legacy:.1}, templated {templated:.1}");
+}
+
+fn bench_chunk(c: &mut Criterion) {
+    report_allocations();
+    let data = event_data("适合出门");
+    let template = ChunkTemplate::new(REQUEST_ID, MODEL);
+    let mut meter = UsageMeter::default();
+
+    let mut group = c.benchmark_group("chunk_per_token");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("legacy", |b| {
+        b.iter(|| legacy_chunk(black_box(&data), REQUEST_ID, MODEL))
+    });
+    group.bench_function("templated", |b| {
+        b.iter(|| templated_chunk(black_box(&data), &template, &mut meter))
+    });
+    group.finish();
+}
+
+fn bench_stream(c: &mut Criterion) {
+    const TOKENS_PER_REPLY: usize = 200;
+    let body = sse_body(TOKENS_PER_REPLY);
+
+    let mut group = c.benchmark_group("decode_reply");
+    group.throughput(Throughput::Elements(TOKENS_PER_REPLY as u64));
+    // Network reads rarely line up with events; 1 KiB reads split most of them
+    group.bench_function("templated_1k_reads", |b| {
+        b.iter(|| {
+            let template = ChunkTemplate::new(REQUEST_ID, MODEL);
+            let mut meter = UsageMeter::default();
+            let mut decoder = SseDecoder::new();
+            let mut chunks = 0;
+            for read in body.chunks(1024) {
+                decoder
+                    .feed_with(read, |event| {
+                        chunks += usize::from(
+                            templated_chunk(event.data, &template, &mut meter).is_some(),
+                        );
+                    })
+                    .unwrap();
+            }
+            assert_eq!(chunks, TOKENS_PER_REPLY);
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_chunk, bench_stream);
+criterion_main!(benches);
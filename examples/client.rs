@@ -33,7 +33,7 @@ async fn test_health_check(
 ) -> Result<(), Box<dyn std::error::Error>> {
     println!("🔍 Testing health check...");
 
-    match client.get(&format!("{}/health", base_url)).send().await {
+    match client.get(format!("{}/health", base_url)).send().await {
         Ok(response) => {
             if response.status().is_success() {
                 let body: serde_json::Value = response.json().await?;
@@ -82,7 +82,7 @@ async fn test_metrics_reset(
     println!("🔍 Testing metrics reset...");
 
     let response = client
-        .post(&format!("{}/metrics/reset", base_url))
+        .post(format!("{}/metrics/reset", base_url))
         .send()
         .await?;
 
@@ -102,12 +102,13 @@ async fn test_voice_scenarios(
 ) -> Result<(), Box<dyn std::error::Error>> {
     println!("🎤 Testing voice assistant scenarios...");
 
-    let test_scenarios = vec![
+    let test_scenarios = [
         TestScenario {
             name: "Greeting",
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "你好！".to_string(),
+                content: "你好！".into(),
+                ..Default::default()
             }],
             description: "Basic greeting interaction",
         },
@@ -115,7 +116,8 @@ async fn test_voice_scenarios(
             name: "Simple Question",
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "今天天气怎么样？".to_string(),
+                content: "今天天气怎么样？".into(),
+                ..Default::default()
             }],
             description: "Weather inquiry",
         },
@@ -123,7 +125,8 @@ async fn test_voice_scenarios(
             name: "Request Help",
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "请帮我设个明天上午9点的闹钟".to_string(),
+                content: "请帮我设个明天上午9点的闹钟".into(),
+                ..Default::default()
             }],
             description: "Task request",
         },
@@ -185,7 +188,7 @@ async fn test_voice_scenarios(
     }
 
     // Get final metrics
-    test_metrics(&client, base_url).await?;
+    test_metrics(client, base_url).await?;
 
     Ok(())
 }
@@ -211,12 +214,13 @@ async fn test_voice_response(
         stream: true,
         stop: None,
         disable_quick_response: disable_quick,
+        ..Default::default()
     };
 
     let start_time = Instant::now();
 
     let response = client
-        .post(&format!("{}/v1/chat/completions", base_url))
+        .post(format!("{}/v1/chat/completions", base_url))
         .header("Content-Type", "application/json")
         .json(&request)
         .send()
@@ -254,7 +258,7 @@ async fn test_voice_response(
 async fn test_metrics(client: &Client, base_url: &str) -> Result<(), Box<dyn std::error::Error>> {
     println!("\n📈 Server Metrics:");
 
-    let response = client.get(&format!("{}/metrics", base_url)).send().await?;
+    let response = client.get(format!("{}/metrics", base_url)).send().await?;
 
     if response.status().is_success() {
         let metrics: serde_json::Value = response.json().await?;
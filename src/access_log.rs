@@ -0,0 +1,162 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use bytes::Bytes;
+use http_body::{Frame, SizeHint};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use crate::service::REQUEST_ID_HEADER;
+
+/// Log target of the access lines, so they can be filtered separately
+pub const ACCESS_TARGET: &str = "access";
+
+/// What the service learned about a request, filled in while the reply streams
+#[derive(Debug, Clone, Default)]
+pub struct AccessFields {
+    pub mode: Option<&'static str>,
+    pub small_model: Option<String>,
+    pub large_model: Option<String>,
+    pub quick_chunk_ms: Option<u64>,
+    pub first_large_token_ms: Option<u64>,
+    pub prompt_tokens: Option<u32>,
+    pub completion_tokens: Option<u32>,
+    pub fallback: Option<bool>,
+}
+
+/// Shared between the access log middleware and the service. Timings are measured
+/// from when the middleware first saw the request.
+#[derive(Debug, Clone)]
+pub struct AccessRecord {
+    start: Instant,
+    fields: Arc<Mutex<AccessFields>>,
+}
+
+impl Default for AccessRecord {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            fields: Arc::default(),
+        }
+    }
+}
+
+impl AccessRecord {
+    pub fn update(&self, update: impl FnOnce(&mut AccessFields)) {
+        if let Ok(mut fields) = self.fields.lock() {
+            update(&mut fields);
+        }
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    pub fn fields(&self) -> AccessFields {
+        self.fields
+            .lock()
+            .map(|fields| fields.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Emits one `access` line per request once its response body is finished (or dropped
+/// by a disconnecting client), so streamed replies are logged with their total time.
+/// Must run inside `propagate_request_id` to see the request ID.
+pub async fn access_log(mut request: Request, next: Next) -> Response {
+    let record = AccessRecord::default();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    request.extensions_mut().insert(record.clone());
+
+    let response = next.run(request).await;
+    let line = AccessLine {
+        record,
+        method,
+        path,
+        status: response.status(),
+        request_id,
+    };
+    let (parts, body) = response.into_parts();
+    Response::from_parts(
+        parts,
+        Body::new(AccessLogBody {
+            inner: body,
+            line: Some(line),
+        }),
+    )
+}
+
+struct AccessLine {
+    record: AccessRecord,
+    method: Method,
+    path: String,
+    status: StatusCode,
+    request_id: String,
+}
+
+impl Drop for AccessLine {
+    fn drop(&mut self) {
+        let fields = self.record.fields();
+        tracing::info!(
+            target: ACCESS_TARGET,
+            method = %self.method,
+            path = %self.path,
+            status = self.status.as_u16(),
+            request_id = %self.request_id,
+            mode = fields.mode,
+            small_model = fields.small_model.as_deref(),
+            large_model = fields.large_model.as_deref(),
+            quick_chunk_ms = fields.quick_chunk_ms,
+            first_large_token_ms = fields.first_large_token_ms,
+            total_ms = self.record.elapsed_ms(),
+            prompt_tokens = fields.prompt_tokens,
+            completion_tokens = fields.completion_tokens,
+            fallback = fields.fallback,
+            "access"
+        );
+    }
+}
+
+/// Response body that logs the access line when the last frame has been sent
+struct AccessLogBody {
+    inner: Body,
+    line: Option<AccessLine>,
+}
+
+impl http_body::Body for AccessLogBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+        let frame = Pin::new(&mut this.inner).poll_frame(cx);
+        if let Poll::Ready(None | Some(Err(_))) = frame {
+            this.line.take();
+        }
+        frame
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
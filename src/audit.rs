@@ -0,0 +1,423 @@
+use crate::config::AuditConfig;
+use crate::models::Message;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::warn;
+
+/// Records waiting for the writer thread; new records are dropped when it falls behind
+const QUEUE_CAPACITY: usize = 1024;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+/// How often the writer thread runs `AuditSink::maintain`, whether or not records arrive
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// One line of the conversation log
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub request_id: String,
+    /// Masked key label, as in the usage metrics
+    pub api_key: String,
+    pub model: String,
+    pub mode: &'static str,
+    pub messages: Vec<AuditMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quick_response: Option<String>,
+    pub reply: String,
+    /// False when the stream failed or the client went away before `[DONE]`
+    pub completed: bool,
+}
+
+/// Text of a request message; images are only counted
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditMessage {
+    pub role: String,
+    pub content: String,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub images: usize,
+}
+
+fn is_zero(count: &usize) -> bool {
+    *count == 0
+}
+
+impl From<&Message> for AuditMessage {
+    fn from(message: &Message) -> Self {
+        Self {
+            role: message.role.clone(),
+            content: message.content.text().into_owned(),
+            images: message.content.image_urls().count(),
+        }
+    }
+}
+
+impl AuditRecord {
+    fn redact(&mut self) {
+        for message in &mut self.messages {
+            message.content = redact(&message.content);
+        }
+        if let Some(quick_response) = &mut self.quick_response {
+            *quick_response = redact(quick_response);
+        }
+        self.reply = redact(&self.reply);
+    }
+}
+
+/// Destination of the serialized records, one JSON object per line
+pub trait AuditSink: Send + 'static {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()>;
+
+    /// Periodic housekeeping from the writer thread, also while the log is quiet
+    fn maintain(&mut self) {}
+}
+
+/// Appends to `AuditConfig::path`, rotating by size and deleting old rotated files
+pub struct FileSink {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_file_bytes: u64,
+    retention: Duration,
+}
+
+impl FileSink {
+    pub fn open(config: &AuditConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).with_context(|| {
+                format!("Failed to create audit log directory {}", dir.display())
+            })?;
+        }
+        let file = Self::append(&path)?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        let sink = Self {
+            path,
+            file,
+            size,
+            max_file_bytes: config.max_file_bytes,
+            retention: Duration::from_secs(config.retention_days.saturating_mul(SECS_PER_DAY)),
+        };
+        sink.remove_expired();
+        Ok(sink)
+    }
+
+    fn append(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open audit log {}", path.display()))
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        let suffix = Utc::now().format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let rotated_path = |n: usize| {
+            let mut rotated = self.path.clone().into_os_string();
+            match n {
+                0 => rotated.push(format!(".{}", suffix)),
+                n => rotated.push(format!(".{}-{}", suffix, n)),
+            }
+            PathBuf::from(rotated)
+        };
+        // Several rotations can happen within the same millisecond
+        let rotated = (0..)
+            .map(rotated_path)
+            .find(|path| !path.exists())
+            .expect("unbounded range");
+        fs::rename(&self.path, &rotated).context("Failed to rotate audit log")?;
+        self.file = Self::append(&self.path)?;
+        self.size = 0;
+        self.remove_expired();
+        Ok(())
+    }
+
+    /// Rotated files are `{file name}.{timestamp}` next to the live file
+    fn remove_expired(&self) {
+        let (Some(dir), Some(name)) = (self.path.parent(), self.path.file_name()) else {
+            return;
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let prefix = format!("{}.", name.to_string_lossy());
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            if !entry.file_name().to_string_lossy().starts_with(&prefix) {
+                continue;
+            }
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| now.duration_since(modified).unwrap_or_default() >= self.retention)
+                .unwrap_or(false);
+            if expired {
+                if let Err(e) = fs::remove_file(entry.path()) {
+                    warn!(
+                        "Failed to delete expired audit log {}: {}",
+                        entry.path().display(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl AuditSink for FileSink {
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let len = line.len() as u64 + 1;
+        if self.size > 0 && self.size + len > self.max_file_bytes {
+            self.rotate().map_err(std::io::Error::other)?;
+        }
+        self.file.write_all(format!("{}\n", line).as_bytes())?;
+        self.size += len;
+        Ok(())
+    }
+
+    /// Enforces retention on a log that rarely rotates
+    fn maintain(&mut self) {
+        self.remove_expired();
+    }
+}
+
+/// Samples chat requests and writes their redacted conversations from a background
+/// thread, so file I/O never runs on the request path.
+pub struct AuditLog {
+    sample_rate: f64,
+    queue: SyncSender<String>,
+}
+
+impl AuditLog {
+    pub fn open(config: &AuditConfig) -> Result<Self> {
+        Ok(Self::with_sink(config.sample_rate, FileSink::open(config)?))
+    }
+
+    pub fn with_sink(sample_rate: f64, sink: impl AuditSink) -> Self {
+        Self::with_maintenance_interval(sample_rate, sink, MAINTENANCE_INTERVAL)
+    }
+
+    /// Like [`with_sink`](Self::with_sink), running `AuditSink::maintain` every `interval`
+    pub fn with_maintenance_interval(
+        sample_rate: f64,
+        mut sink: impl AuditSink,
+        interval: Duration,
+    ) -> Self {
+        let (queue, records) = sync_channel::<String>(QUEUE_CAPACITY);
+        // Ends once the log (and with it the sender) is dropped
+        std::thread::spawn(move || {
+            let mut last_maintained = Instant::now();
+            loop {
+                match records.recv_timeout(interval) {
+                    Ok(line) => {
+                        if let Err(e) = sink.write_line(&line) {
+                            warn!("Failed to write audit record: {}", e);
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                if last_maintained.elapsed() >= interval {
+                    sink.maintain();
+                    last_maintained = Instant::now();
+                }
+            }
+        });
+        Self { sample_rate, queue }
+    }
+
+    /// Decides per request whether its conversation is recorded
+    pub fn sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || rand::random::<f64>() < self.sample_rate
+    }
+
+    /// Redacts and queues the record
+    pub fn record(&self, mut record: AuditRecord) {
+        record.redact();
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize audit record: {}", e);
+                return;
+            }
+        };
+        if let Err(TrySendError::Full(_)) = self.queue.try_send(line) {
+            warn!(
+                "Audit log queue is full, dropping record {}",
+                record.request_id
+            );
+        }
+    }
+}
+
+/// Collects the reply from the outgoing chunk payloads and submits the record when
+/// dropped, i.e. once the stream has ended or the client has disconnected.
+pub struct AuditCapture {
+    log: Arc<AuditLog>,
+    record: Option<AuditRecord>,
+    /// The next content chunk is the quick acknowledgement
+    awaiting_quick: bool,
+}
+
+impl AuditCapture {
+    pub fn new(log: Arc<AuditLog>, record: AuditRecord, quick: bool) -> Self {
+        Self {
+            log,
+            record: Some(record),
+            awaiting_quick: quick,
+        }
+    }
+
+    pub fn observe(&mut self, payload: &str) {
+        let Some(record) = &mut self.record else {
+            return;
+        };
+        if payload == "[DONE]" {
+            record.completed = true;
+            return;
+        }
+        let Ok(chunk) = serde_json::from_str::<Value>(payload) else {
+            return;
+        };
+        let Some(content) = chunk["choices"][0]["delta"]["content"].as_str() else {
+            return;
+        };
+        if std::mem::take(&mut self.awaiting_quick) {
+            record.quick_response = Some(content.to_string());
+        } else {
+            record.reply.push_str(content);
+        }
+    }
+}
+
+impl Drop for AuditCapture {
+    fn drop(&mut self) {
+        if let Some(record) = self.record.take() {
+            self.log.record(record);
+        }
+    }
+}
+
+const EMAIL_MASK: &str = "[EMAIL]";
+const PHONE_MASK: &str = "[PHONE]";
+const ID_MASK: &str = "[ID]";
+
+/// Masks email addresses, phone numbers (mainland mobile, `+` international and
+/// `0`-prefixed landline) and national ID numbers (15 or 18 characters)
+pub fn redact(text: &str) -> String {
+    redact_numbers(&redact_emails(text))
+}
+
+fn is_local_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '%' | '+' | '-')
+}
+
+fn is_domain_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '-')
+}
+
+fn redact_emails(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (at, _) in chars.iter().enumerate().filter(|(_, c)| **c == '@') {
+        if at < copied {
+            continue;
+        }
+        let mut start = at;
+        while start > copied && is_local_char(chars[start - 1]) {
+            start -= 1;
+        }
+        let mut end = at + 1;
+        while end < chars.len() && is_domain_char(chars[end]) {
+            end += 1;
+        }
+        // A trailing dot ends the sentence, not the domain
+        while end > at + 1 && chars[end - 1] == '.' {
+            end -= 1;
+        }
+        let domain: String = chars[at + 1..end].iter().collect();
+        let valid_domain = domain.contains('.') && domain.split('.').all(|label| !label.is_empty());
+        if start == at || !valid_domain {
+            continue;
+        }
+        out.extend(&chars[copied..start]);
+        out.push_str(EMAIL_MASK);
+        copied = end;
+    }
+    out.extend(&chars[copied..]);
+    out
+}
+
+fn redact_numbers(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        // Not inside a word or after a decimal point
+        let at_boundary = match i.checked_sub(1).map(|prev| chars[prev]) {
+            None => true,
+            Some('.') => !(i >= 2 && chars[i - 2].is_ascii_digit()),
+            Some(prev) => !prev.is_ascii_alphanumeric(),
+        };
+        let starts_number = chars[i].is_ascii_digit()
+            || (chars[i] == '+' && chars.get(i + 1).is_some_and(char::is_ascii_digit));
+        if !(at_boundary && starts_number) {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        // Digits with single space or dash separators, as in "138 0013 8000"
+        let plus = chars[i] == '+';
+        let mut end = if plus { i + 1 } else { i };
+        let mut digits = String::new();
+        while end < chars.len() {
+            if chars[end].is_ascii_digit() {
+                digits.push(chars[end]);
+                end += 1;
+            } else if matches!(chars[end], ' ' | '-')
+                && chars.get(end + 1).is_some_and(char::is_ascii_digit)
+            {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        let check_x = digits.len() == 17 && matches!(chars.get(end), Some('X' | 'x'));
+        if check_x {
+            end += 1;
+        }
+        // Decimals and numbers glued to letters are left alone
+        let decimal =
+            chars.get(end) == Some(&'.') && chars.get(end + 1).is_some_and(char::is_ascii_digit);
+        let glued = chars.get(end).is_some_and(char::is_ascii_alphanumeric);
+
+        let mask = match digits.len() {
+            _ if decimal || glued => None,
+            17 if check_x => Some(ID_MASK),
+            15 | 18 if !plus => Some(ID_MASK),
+            8..=15 if plus => Some(PHONE_MASK),
+            11 if digits.starts_with('1') => Some(PHONE_MASK),
+            10..=12 if digits.starts_with('0') => Some(PHONE_MASK),
+            _ => None,
+        };
+        match mask {
+            Some(mask) => out.push_str(mask),
+            None => out.extend(&chars[i..end]),
+        }
+        i = end;
+    }
+    out
+}
@@ -0,0 +1,135 @@
+use crate::timing::{timed_chat, ChatTiming};
+use reqwest::Client;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// Prompts used when no corpus file is given
+pub const DEFAULT_PROMPTS: &[&str] = &[
+    "你好！",
+    "今天天气怎么样？",
+    "请帮我设个明天上午9点的闹钟",
+    "给我讲一个关于太空旅行的简短故事",
+    "What is the capital of Australia?",
+];
+
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    /// Requests in flight at most
+    pub concurrency: usize,
+    /// Requests started per second; `None` starts a new one whenever a slot frees up
+    pub rate: Option<f64>,
+    /// No new requests start after this; requests in flight are awaited
+    pub duration: Duration,
+    pub prompts: Vec<String>,
+}
+
+/// One line per prompt; blank lines and `#` comments are skipped
+pub fn parse_corpus(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Runs the load for `options.duration`, cycling through the prompts, with the quick
+/// response enabled or disabled. Timings are returned in start order.
+pub async fn run(
+    client: &Client,
+    options: &BenchOptions,
+    disable_quick_response: bool,
+) -> Vec<ChatTiming> {
+    let slots = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let deadline = Instant::now() + options.duration;
+    let mut ticker = options.rate.map(|rate| {
+        let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+        // A rate the server can't keep up with is capped by the concurrency instead
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+
+    let mut tasks = Vec::new();
+    for prompt in options.prompts.iter().cycle() {
+        if let Some(ticker) = &mut ticker {
+            if tokio::time::timeout_at(deadline, ticker.tick())
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+        let Ok(Ok(permit)) =
+            tokio::time::timeout_at(deadline, Arc::clone(&slots).acquire_owned()).await
+        else {
+            break;
+        };
+        if Instant::now() >= deadline {
+            break;
+        }
+        let client = client.clone();
+        let options = options.clone();
+        let body = json!({
+            "model": options.model,
+            "messages": [{"role": "user", "content": prompt}],
+            "stream": true,
+            "disable_quick_response": disable_quick_response
+        });
+        tasks.push(tokio::spawn(async move {
+            let timing = timed_chat(
+                &client,
+                &options.base_url,
+                options.api_key.as_deref(),
+                &body,
+                !disable_quick_response,
+            )
+            .await;
+            drop(permit);
+            timing
+        }));
+    }
+
+    let mut timings = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        timings.push(task.await.unwrap_or_else(|e| ChatTiming {
+            error: Some(e.to_string()),
+            ..Default::default()
+        }));
+    }
+    timings
+}
+
+/// Per-request timings of labelled runs as CSV, latencies in ms
+pub fn to_csv(runs: &[(String, Vec<ChatTiming>)]) -> String {
+    let ms = |secs: Option<f64>| {
+        secs.map(|secs| format!("{:.3}", secs * 1000.0))
+            .unwrap_or_default()
+    };
+    let mut out =
+        String::from("run,status,ttfb_ms,quick_chunk_ms,first_large_chunk_ms,total_ms,error\n");
+    for (label, timings) in runs {
+        for timing in timings {
+            let error = timing
+                .error
+                .as_deref()
+                .map(|error| format!("\"{}\"", error.replace('"', "\"\"")))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                label,
+                timing.status,
+                ms(timing.ttfb),
+                ms(timing.quick_chunk),
+                ms(timing.first_large_chunk),
+                ms(Some(timing.total)),
+                error
+            ));
+        }
+    }
+    out
+}
@@ -0,0 +1,177 @@
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use std::time::{Duration, Instant};
+
+use loro::{
+    bench::{parse_corpus, run, to_csv, BenchOptions, DEFAULT_PROMPTS},
+    timing::{format_table, TimingSummary},
+};
+
+const USAGE: &str = "\
+Load-tests a running loro server and compares quick and direct responses
+
+Usage: loro-bench [OPTIONS]
+
+Options:
+  --url <URL>            Server to benchmark [default: http://127.0.0.1:8000]
+  --api-key <KEY>        Bearer token sent with every request
+  --model <MODEL>        Model name in the requests [default: loro-voice-assistant]
+  --concurrency <N>      Requests in flight at most [default: 4]
+  --rate <PER_SEC>       Requests started per second [default: as fast as slots free up]
+  --duration <SECS>      How long each run starts new requests [default: 30]
+  --prompts <PATH>       Prompt corpus, one prompt per line [default: built-in prompts]
+  --mode <MODE>          quick, direct or compare [default: compare]
+  --json <PATH>          Write the per-request timings and summaries as JSON
+  --csv <PATH>           Write the per-request timings as CSV
+  -h, --help             Print this help";
+
+struct Args {
+    options: BenchOptions,
+    /// `disable_quick_response` of each run
+    runs: Vec<bool>,
+    json: Option<String>,
+    csv: Option<String>,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        options: BenchOptions {
+            base_url: "http://127.0.0.1:8000".to_string(),
+            api_key: None,
+            model: "loro-voice-assistant".to_string(),
+            concurrency: 4,
+            rate: None,
+            duration: Duration::from_secs(30),
+            prompts: DEFAULT_PROMPTS.iter().map(|p| p.to_string()).collect(),
+        },
+        runs: vec![false, true],
+        json: None,
+        csv: None,
+    };
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || {
+            argv.next()
+                .with_context(|| format!("{} requires a value", arg))
+        };
+        let options = &mut args.options;
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "--url" => options.base_url = value()?,
+            "--api-key" => options.api_key = Some(value()?),
+            "--model" => options.model = value()?,
+            "--concurrency" => {
+                options.concurrency = value()?.parse().context("--concurrency must be a count")?;
+                if options.concurrency == 0 {
+                    bail!("--concurrency must be at least 1");
+                }
+            }
+            "--rate" => {
+                let rate: f64 = value()?.parse().context("--rate must be a number")?;
+                if !(rate > 0.0 && rate.is_finite()) {
+                    bail!("--rate must be greater than 0");
+                }
+                options.rate = Some(rate);
+            }
+            "--duration" => {
+                let secs: f64 = value()?.parse().context("--duration must be a number")?;
+                if !(secs > 0.0 && secs.is_finite()) {
+                    bail!("--duration must be greater than 0");
+                }
+                options.duration = Duration::from_secs_f64(secs);
+            }
+            "--prompts" => {
+                let path = value()?;
+                let corpus = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path))?;
+                options.prompts = parse_corpus(&corpus);
+                if options.prompts.is_empty() {
+                    bail!("No prompts in {}", path);
+                }
+            }
+            "--mode" => {
+                args.runs = match value()?.as_str() {
+                    "quick" => vec![false],
+                    "direct" => vec![true],
+                    "compare" => vec![false, true],
+                    other => bail!("Unknown mode '{}'", other),
+                };
+            }
+            "--json" => args.json = Some(value()?),
+            "--csv" => args.csv = Some(value()?),
+            other => bail!("Unknown argument '{}'\n\n{}", other, USAGE),
+        }
+    }
+    Ok(args)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = parse_args()?;
+    let options = &args.options;
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .no_proxy()
+        .build()?;
+
+    let mut runs = Vec::new();
+    for &disable_quick_response in &args.runs {
+        let label = if disable_quick_response {
+            "direct"
+        } else {
+            "quick"
+        };
+        eprintln!(
+            "Running {} mode for {:.0}s with concurrency {}{} against {}",
+            label,
+            options.duration.as_secs_f64(),
+            options.concurrency,
+            options
+                .rate
+                .map(|rate| format!(" at {} req/s", rate))
+                .unwrap_or_default(),
+            options.base_url
+        );
+        let start = Instant::now();
+        let timings = run(&client, options, disable_quick_response).await;
+        eprintln!(
+            "{} requests in {:.1}s ({:.1} req/s)",
+            timings.len(),
+            start.elapsed().as_secs_f64(),
+            timings.len() as f64 / start.elapsed().as_secs_f64()
+        );
+        runs.push((label.to_string(), timings));
+    }
+
+    let summaries: Vec<(String, TimingSummary)> = runs
+        .iter()
+        .map(|(label, timings)| (label.clone(), TimingSummary::from_timings(timings)))
+        .collect();
+    print!("{}", format_table(&summaries));
+
+    if let Some(path) = &args.json {
+        let report = serde_json::json!({
+            "concurrency": options.concurrency,
+            "rate": options.rate,
+            "duration_secs": options.duration.as_secs_f64(),
+            "runs": runs
+                .iter()
+                .zip(&summaries)
+                .map(|((label, timings), (_, summary))| serde_json::json!({
+                    "mode": label,
+                    "summary": summary,
+                    "timings": timings,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write {}", path))?;
+    }
+    if let Some(path) = &args.csv {
+        std::fs::write(path, to_csv(&runs)).with_context(|| format!("Failed to write {}", path))?;
+    }
+    Ok(())
+}
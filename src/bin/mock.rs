@@ -0,0 +1,54 @@
+use anyhow::{bail, Context, Result};
+
+use loro::{
+    mock_upstream::{MockScript, MockUpstream},
+    shutdown::shutdown_signal,
+};
+
+const USAGE: &str = "\
+Serves scripted OpenAI and Ollama chat completions for offline testing
+
+Usage: loro-mock [OPTIONS]
+
+Options:
+  --addr <ADDR>        Address to listen on [default: 127.0.0.1:11434]
+  --script <PATH>      JSON script with `quick` and `stream` replies [default: built-in]
+  -h, --help           Print this help";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt().with_target(false).init();
+
+    let mut addr = "127.0.0.1:11434".to_string();
+    let mut script = MockScript::default();
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || {
+            argv.next()
+                .with_context(|| format!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            "--addr" => addr = value()?,
+            "--script" => {
+                let path = value()?;
+                let text = std::fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path))?;
+                script = serde_json::from_str(&text)
+                    .with_context(|| format!("Invalid mock script {}", path))?;
+            }
+            other => bail!("Unknown argument '{}'\n\n{}", other, USAGE),
+        }
+    }
+
+    let mock = MockUpstream::bind(&addr, script)
+        .await
+        .with_context(|| format!("Failed to listen on {}", addr))?;
+    println!("OpenAI base URL: {}", mock.openai_base_url());
+    println!("Ollama base URL: {}", mock.ollama_base_url());
+    shutdown_signal().await;
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use anyhow::{bail, Context, Result};
+use reqwest::Client;
+use std::time::Duration;
+
+use loro::{
+    replay::{load_requests, replay, ReplayMode, ReplayOptions},
+    timing::{format_table, TimingSummary},
+};
+
+const USAGE: &str = "\
+Replays requests from a loro conversation audit log against a running server
+
+Usage: loro-replay [OPTIONS] <AUDIT_LOG>
+
+Options:
+  --url <URL>          Server to replay against [default: http://127.0.0.1:8000]
+  --api-key <KEY>      Bearer token sent with every request
+  --speed <FACTOR>     Divides the recorded gaps between requests [default: 1]
+  --mode <MODE>        original, quick, direct or compare [default: original]
+  --limit <N>          Replay only the first N requests
+  --json <PATH>        Also write the per-request timings and summaries as JSON
+  -h, --help           Print this help";
+
+struct Args {
+    log_path: String,
+    url: String,
+    api_key: Option<String>,
+    speed: f64,
+    modes: Vec<ReplayMode>,
+    limit: Option<usize>,
+    json: Option<String>,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        log_path: String::new(),
+        url: "http://127.0.0.1:8000".to_string(),
+        api_key: None,
+        speed: 1.0,
+        modes: vec![ReplayMode::Original],
+        limit: None,
+        json: None,
+    };
+    let mut log_path = None;
+    let mut argv = std::env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || {
+            argv.next()
+                .with_context(|| format!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "--url" => args.url = value()?,
+            "--api-key" => args.api_key = Some(value()?),
+            "--speed" => {
+                args.speed = value()?.parse().context("--speed must be a number")?;
+                if !(args.speed > 0.0 && args.speed.is_finite()) {
+                    bail!("--speed must be greater than 0");
+                }
+            }
+            "--mode" => {
+                let mode = value()?;
+                args.modes = match mode.as_str() {
+                    "compare" => vec![ReplayMode::Quick, ReplayMode::Direct],
+                    other => vec![ReplayMode::parse(other)
+                        .with_context(|| format!("Unknown mode '{}'", other))?],
+                };
+            }
+            "--limit" => args.limit = Some(value()?.parse().context("--limit must be a count")?),
+            "--json" => args.json = Some(value()?),
+            other if other.starts_with('-') => bail!("Unknown option '{}'\n\n{}", other, USAGE),
+            other if log_path.is_none() => log_path = Some(other.to_string()),
+            other => bail!("Unexpected argument '{}'", other),
+        }
+    }
+    args.log_path = log_path.with_context(|| format!("Missing audit log path\n\n{}", USAGE))?;
+    Ok(args)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = parse_args()?;
+    let log = std::fs::read_to_string(&args.log_path)
+        .with_context(|| format!("Failed to read {}", args.log_path))?;
+    let mut requests = load_requests(&log)?;
+    if let Some(limit) = args.limit {
+        requests.truncate(limit);
+    }
+    if requests.is_empty() {
+        bail!("No requests in {}", args.log_path);
+    }
+
+    let client = Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .no_proxy()
+        .build()?;
+    let mut summaries = Vec::new();
+    let mut runs = Vec::new();
+    for mode in &args.modes {
+        eprintln!(
+            "Replaying {} requests in {} mode at {}x against {}",
+            requests.len(),
+            mode.as_str(),
+            args.speed,
+            args.url
+        );
+        let options = ReplayOptions {
+            base_url: args.url.clone(),
+            api_key: args.api_key.clone(),
+            speed: args.speed,
+            mode: *mode,
+        };
+        let timings = replay(&client, &requests, &options).await;
+        for (request, timing) in requests.iter().zip(&timings) {
+            if let Some(error) = &timing.error {
+                eprintln!("{} request failed: {}", request.timestamp, error);
+            }
+        }
+        summaries.push((
+            mode.as_str().to_string(),
+            TimingSummary::from_timings(&timings),
+        ));
+        runs.push(serde_json::json!({"mode": mode.as_str(), "timings": timings}));
+    }
+
+    print!("{}", format_table(&summaries));
+    if let Some(path) = &args.json {
+        let report = serde_json::json!({
+            "speed": args.speed,
+            "runs": runs,
+            "summaries": summaries
+                .iter()
+                .map(|(mode, summary)| serde_json::json!({"mode": mode, "summary": summary}))
+                .collect::<Vec<_>>(),
+        });
+        std::fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write {}", path))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::config::{ChaosConfig, FaultConfig};
+use bytes::Bytes;
+use futures::stream::{BoxStream, StreamExt};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use reqwest::{RequestBuilder, Response};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+/// Upstream body chunks; transport errors and injected disconnects are both I/O errors
+pub type ByteStream = BoxStream<'static, std::io::Result<Bytes>>;
+
+/// Fault injectors for the small and large model upstreams, built from `CHAOS`
+#[derive(Clone, Default)]
+pub struct Chaos {
+    pub small_model: Option<Arc<FaultInjector>>,
+    pub large_model: Option<Arc<FaultInjector>>,
+}
+
+impl Chaos {
+    pub fn new(config: Option<&ChaosConfig>) -> Self {
+        let injector = |role: &'static str, faults: &Option<FaultConfig>| {
+            faults
+                .clone()
+                .map(|faults| Arc::new(FaultInjector::new(role, faults)))
+        };
+        match config {
+            Some(config) => Self {
+                small_model: injector("small", &config.small_model),
+                large_model: injector("large", &config.large_model),
+            },
+            None => Self::default(),
+        }
+    }
+}
+
+/// Injects the faults of one upstream into its requests and response streams
+pub struct FaultInjector {
+    role: &'static str,
+    faults: FaultConfig,
+}
+
+impl FaultInjector {
+    pub fn new(role: &'static str, faults: FaultConfig) -> Self {
+        Self { role, faults }
+    }
+
+    fn roll(probability: f64) -> bool {
+        probability > 0.0 && rand::thread_rng().gen_bool(probability.min(1.0))
+    }
+
+    /// Sends the request, possibly late, or answers with an injected error status
+    /// without reaching the upstream
+    pub async fn send(&self, builder: RequestBuilder) -> reqwest::Result<Response> {
+        if Self::roll(self.faults.latency_probability) {
+            warn!(
+                "Chaos: delaying {} model request by {}ms",
+                self.role, self.faults.latency_ms
+            );
+            tokio::time::sleep(Duration::from_millis(self.faults.latency_ms)).await;
+        }
+        if Self::roll(self.faults.error_probability) {
+            if let Some(&status) = self.faults.error_statuses.choose(&mut rand::thread_rng()) {
+                warn!(
+                    "Chaos: answering {} model request with {}",
+                    self.role, status
+                );
+                return Ok(error_response(status));
+            }
+        }
+        builder.send().await
+    }
+
+    /// Drops the connection or truncates lines of the body at the configured rates
+    pub fn wrap_stream(self: Arc<Self>, body: ByteStream) -> ByteStream {
+        body.scan(false, move |dropped, chunk| {
+            if *dropped {
+                return futures::future::ready(None);
+            }
+            let chunk = match chunk {
+                Ok(_) if Self::roll(self.faults.disconnect_probability) => {
+                    warn!("Chaos: dropping {} model stream", self.role);
+                    *dropped = true;
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::ConnectionReset,
+                        "connection dropped by chaos layer",
+                    ))
+                }
+                Ok(bytes) if Self::roll(self.faults.truncate_probability) => {
+                    warn!("Chaos: truncating a {} model stream line", self.role);
+                    Ok(truncate_line(
+                        &bytes,
+                        rand::thread_rng().gen_range(0..bytes.len().max(1)),
+                    ))
+                }
+                other => other,
+            };
+            futures::future::ready(Some(chunk))
+        })
+        .boxed()
+    }
+}
+
+/// Removes the bytes from `at` up to the end of that line, keeping its line break
+pub fn truncate_line(bytes: &[u8], at: usize) -> Bytes {
+    let at = at.min(bytes.len());
+    let line_end = bytes[at..]
+        .iter()
+        .position(|&b| b == b'\n' || b == b'\r')
+        .map_or(bytes.len(), |offset| at + offset);
+    let mut truncated = Vec::with_capacity(bytes.len() - (line_end - at));
+    truncated.extend_from_slice(&bytes[..at]);
+    truncated.extend_from_slice(&bytes[line_end..]);
+    Bytes::from(truncated)
+}
+
+fn error_response(status: u16) -> Response {
+    let body = json!({
+        "error": {"message": "Injected by chaos layer", "type": "chaos_error"}
+    });
+    let response = http::Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(body.to_string())
+        .expect("chaos error response is valid");
+    Response::from(response)
+}
+
+/// Sends the request, through the upstream's fault injector when one is configured
+pub async fn send(
+    builder: RequestBuilder,
+    injector: Option<Arc<FaultInjector>>,
+) -> reqwest::Result<Response> {
+    match injector {
+        Some(injector) => injector.send(builder).await,
+        None => builder.send().await,
+    }
+}
+
+/// The response body as a [`ByteStream`], with the upstream's faults applied
+pub fn body_stream(response: Response, injector: Option<&Arc<FaultInjector>>) -> ByteStream {
+    let body: ByteStream = response
+        .bytes_stream()
+        .map(|chunk| chunk.map_err(std::io::Error::other))
+        .boxed();
+    match injector {
+        Some(injector) => Arc::clone(injector).wrap_stream(body),
+        None => body,
+    }
+}
+
+/// The whole response body, with the upstream's faults applied
+pub async fn body_bytes(
+    response: Response,
+    injector: Option<&Arc<FaultInjector>>,
+) -> std::io::Result<Vec<u8>> {
+    let mut body = body_stream(response, injector);
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.next().await {
+        bytes.extend_from_slice(&chunk?);
+    }
+    Ok(bytes)
+}
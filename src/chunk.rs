@@ -0,0 +1,80 @@
+use crate::models::{StreamDelta, Usage};
+
+/// `object` of every streamed chunk
+pub const CHUNK_OBJECT: &str = "chat.completion.chunk";
+
+/// Largest chunk payload forwarded to clients; a larger chunk is a stream error
+pub const MAX_CHUNK_BYTES: usize = 1024 * 1024;
+
+/// The parts of a `chat.completion.chunk` shared by every chunk of one reply, rendered
+/// once per request so that each token only serializes its delta.
+///
+/// Output is byte-for-byte what serializing a [`ChatCompletionChunk`] with the same
+/// fields produces.
+///
+/// [`ChatCompletionChunk`]: crate::models::ChatCompletionChunk
+#[derive(Debug, Clone)]
+pub struct ChunkTemplate {
+    /// `{"id":…,"object":…,"created":…,"model":…,"choices":[{"index":0,"delta":`
+    prefix: String,
+    /// Length of the prefix up to `"choices"`, shared with the usage chunk
+    head_len: usize,
+}
+
+impl ChunkTemplate {
+    pub fn new(request_id: &str, model: &str) -> Self {
+        Self::with_created(request_id, model, chrono::Utc::now().timestamp())
+    }
+
+    pub fn with_created(request_id: &str, model: &str, created: i64) -> Self {
+        let id = serde_json::Value::from(format!("chatcmpl-{request_id}"));
+        let model = serde_json::Value::from(model);
+        let mut prefix = format!(
+            r#"{{"id":{id},"object":"{CHUNK_OBJECT}","created":{created},"model":{model},"#
+        );
+        let head_len = prefix.len();
+        prefix.push_str(CHOICE_PREFIX);
+        Self { prefix, head_len }
+    }
+
+    /// Bytes a chunk adds around its delta, besides the finish reason
+    pub fn overhead(&self) -> usize {
+        self.prefix.len() + SUFFIX.len()
+    }
+
+    /// Renders one chunk into a single allocation. `size_hint` is the expected length
+    /// of the serialized delta, such as the length of the upstream event it came from.
+    pub fn render(
+        &self,
+        delta: &StreamDelta<'_>,
+        finish_reason: Option<&str>,
+        size_hint: usize,
+    ) -> serde_json::Result<String> {
+        let finish_len = finish_reason.map_or(0, |reason| FINISH_REASON.len() + reason.len() + 2);
+        let mut out = Vec::with_capacity(self.overhead() + size_hint + finish_len);
+        out.extend_from_slice(self.prefix.as_bytes());
+        serde_json::to_writer(&mut out, delta)?;
+        if let Some(reason) = finish_reason {
+            out.extend_from_slice(FINISH_REASON.as_bytes());
+            serde_json::to_writer(&mut out, reason)?;
+        }
+        out.extend_from_slice(SUFFIX.as_bytes());
+        Ok(String::from_utf8(out).expect("serde_json writes valid UTF-8"))
+    }
+
+    /// Renders the final chunk of a reply, which has no choices and carries `usage`
+    pub fn render_usage(&self, usage: &Usage) -> serde_json::Result<String> {
+        let mut out = Vec::with_capacity(self.head_len + USAGE_PREFIX.len() + 96);
+        out.extend_from_slice(&self.prefix.as_bytes()[..self.head_len]);
+        out.extend_from_slice(USAGE_PREFIX.as_bytes());
+        serde_json::to_writer(&mut out, usage)?;
+        out.push(b'}');
+        Ok(String::from_utf8(out).expect("serde_json writes valid UTF-8"))
+    }
+}
+
+const CHOICE_PREFIX: &str = r#""choices":[{"index":0,"delta":"#;
+const USAGE_PREFIX: &str = r#""choices":[],"usage":"#;
+
+const FINISH_REASON: &str = r#","finish_reason":"#;
+const SUFFIX: &str = "}]}";
@@ -1,8 +1,11 @@
+use crate::routing::ResponseMode;
 use anyhow::{Context, Result};
 use secrecy::{Secret, ExposeSecret};
 use serde::{Deserialize, Serialize};
 use std::env;
 
+const DEFAULT_ERROR_APOLOGY: &str = "抱歉，出现了问题。";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub host: String,
@@ -14,6 +17,332 @@ pub struct Config {
     pub small_model_timeout_secs: u64,
     pub max_retries: u32,
     pub stats_max_entries: usize,
+    pub shutdown_drain_timeout_secs: u64,
+    /// How long `/health` reports draining before the listener closes
+    pub shutdown_pre_drain_delay_secs: u64,
+    pub readiness_probe_interval_secs: u64,
+    pub readiness_probe_timeout_secs: u64,
+    pub model_routes: Vec<RouteConfig>,
+    pub transcription: Option<TranscriptionConfig>,
+    pub max_audio_upload_bytes: usize,
+    pub speech: Option<SpeechConfig>,
+    /// Said to the user when a reply fails mid-stream; `None` disables it
+    pub error_apology: Option<String>,
+    /// Include raw upstream error details in client-facing errors (debugging only)
+    pub expose_upstream_errors: bool,
+    /// Per-model token prices used to compute request costs
+    pub pricing: Vec<ModelPrice>,
+    /// Client keys accounted separately in usage totals and budgets; requests with
+    /// any other key share the `other` bucket
+    #[serde(skip)]
+    pub client_api_keys: Vec<Secret<String>>,
+    /// Daily spending cap applied to each client API key
+    pub budget: Option<BudgetConfig>,
+    /// OTLP trace export; logs stay local when unset
+    pub telemetry: Option<TelemetryConfig>,
+    /// Console log output: `text` or `json` (one object per line)
+    pub log_format: String,
+    /// One structured `access` line per HTTP request, independent of `log_level`
+    pub access_log: bool,
+    /// Sampled conversation log for quality review; off when unset
+    pub audit: Option<AuditConfig>,
+    /// Faults injected into upstream calls for resilience testing; never set in production
+    pub chaos: Option<ChaosConfig>,
+}
+
+/// OTLP/HTTP trace export to a collector (or Jaeger's OTLP port, 4318).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// Collector base URL; spans are posted to `{endpoint}/v1/traces`
+    pub endpoint: String,
+    pub service_name: String,
+    pub protocol: OtlpProtocol,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OtlpProtocol {
+    #[serde(rename = "http/protobuf")]
+    HttpProtobuf,
+    #[serde(rename = "http/json")]
+    HttpJson,
+}
+
+/// Conversation log written as JSONL, with personal data masked before writing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    pub path: String,
+    /// Fraction of chat requests recorded, from 0.0 to 1.0
+    pub sample_rate: f64,
+    /// The file is rotated (renamed with a timestamp suffix) once it reaches this size
+    pub max_file_bytes: u64,
+    /// Rotated files older than this are deleted
+    pub retention_days: u64,
+}
+
+/// Faults injected per upstream role; a role without an entry is left alone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosConfig {
+    #[serde(default)]
+    pub small_model: Option<FaultConfig>,
+    #[serde(default)]
+    pub large_model: Option<FaultConfig>,
+}
+
+/// Probabilities are rolled independently: per request for latency and errors,
+/// per received network chunk for disconnects and truncation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FaultConfig {
+    pub latency_probability: f64,
+    /// Added before the request is sent
+    pub latency_ms: u64,
+    /// Answer with one of `error_statuses` instead of calling the upstream
+    pub error_probability: f64,
+    pub error_statuses: Vec<u16>,
+    /// Drop the connection mid-stream
+    pub disconnect_probability: f64,
+    /// Cut a line of the stream short
+    pub truncate_probability: f64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            latency_probability: 0.0,
+            latency_ms: 0,
+            error_probability: 0.0,
+            error_statuses: vec![500, 502, 503, 429],
+            disconnect_probability: 0.0,
+            truncate_probability: 0.0,
+        }
+    }
+}
+
+/// Price of an upstream model in USD per 1K tokens, matched by `model_name`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelPrice {
+    pub model: String,
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+}
+
+/// What happens to requests from a key that has spent its daily budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetAction {
+    /// Fail with 429 `budget_exceeded`
+    Reject,
+    /// Answer directly with the route's small model instead of the large one
+    Degrade,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    /// USD per key and UTC day
+    pub daily_limit_usd: f64,
+    pub action: BudgetAction,
+}
+
+/// OpenAI-compatible `/audio/speech` backend, e.g. a local Piper or Kokoro server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeechConfig {
+    pub backend: ModelConfig,
+    pub voice: String,
+    /// One of `models::AUDIO_FORMATS`
+    pub format: String,
+    /// Sentences synthesized in parallel; output order is preserved
+    pub max_concurrency: usize,
+    /// Pre-render the fallback quick responses at startup
+    pub precache: bool,
+}
+
+/// OpenAI-compatible `/audio/transcriptions` backend, e.g. a local whisper.cpp server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionConfig {
+    pub backend: ModelConfig,
+    /// Language hint (ISO-639-1) used when the upload does not specify one
+    pub language: Option<String>,
+}
+
+/// Maps a public model name (or `*` wildcard pattern) to its backends, prompts and defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteConfig {
+    pub name: String,
+    pub mode: Option<ResponseMode>,
+    pub large_model: ModelConfig,
+    pub small_model: ModelConfig,
+    pub large_system_prompt: Option<String>,
+    pub quick_system_prompt: Option<String>,
+    pub max_tokens: Option<u32>,
+}
+
+// Route entry as written in MODEL_ROUTES / MODEL_ROUTES_FILE; unset backend fields
+// inherit from the default SMALL_MODEL_* / LARGE_MODEL_* settings
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteEntry {
+    name: String,
+    #[serde(default)]
+    mode: Option<ResponseMode>,
+    #[serde(default)]
+    large_model: Option<ModelOverride>,
+    #[serde(default)]
+    small_model: Option<ModelOverride>,
+    #[serde(default)]
+    large_system_prompt: Option<String>,
+    #[serde(default)]
+    quick_system_prompt: Option<String>,
+    #[serde(default)]
+    max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelOverride {
+    #[serde(default)]
+    base_url: Option<String>,
+    #[serde(default)]
+    model_name: Option<String>,
+    // Name of the environment variable holding the key, so keys stay out of route files
+    #[serde(default)]
+    api_key_env: Option<String>,
+}
+
+impl ModelOverride {
+    fn resolve(self, default: &ModelConfig) -> Result<ModelConfig> {
+        let api_key = match self.api_key_env {
+            Some(var) => Secret::new(
+                env::var(&var).with_context(|| format!("{var} environment variable is required by MODEL_ROUTES"))?,
+            ),
+            None => default.api_key.clone(),
+        };
+        Ok(ModelConfig {
+            api_key,
+            base_url: self.base_url.unwrap_or_else(|| default.base_url.clone()),
+            model_name: self.model_name.unwrap_or_else(|| default.model_name.clone()),
+        })
+    }
+}
+
+// JSON setting given inline in `var` or as a file path in `{var}_FILE` (which wins)
+fn read_json_setting(var: &str) -> Result<Option<String>> {
+    let file_var = format!("{var}_FILE");
+    if let Ok(path) = env::var(&file_var) {
+        std::fs::read_to_string(&path)
+            .map(Some)
+            .with_context(|| format!("Failed to read {file_var} {path}"))
+    } else {
+        Ok(env::var(var).ok())
+    }
+}
+
+fn load_model_routes(small_model: &ModelConfig, large_model: &ModelConfig) -> Result<Vec<RouteConfig>> {
+    let Some(raw) = read_json_setting("MODEL_ROUTES")? else {
+        return Ok(Vec::new());
+    };
+
+    let entries: Vec<RouteEntry> =
+        serde_json::from_str(&raw).context("MODEL_ROUTES must be a JSON array of routes")?;
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            Ok(RouteConfig {
+                name: entry.name,
+                mode: entry.mode,
+                large_model: entry.large_model.unwrap_or_default().resolve(large_model)?,
+                small_model: entry.small_model.unwrap_or_default().resolve(small_model)?,
+                large_system_prompt: entry.large_system_prompt,
+                quick_system_prompt: entry.quick_system_prompt,
+                max_tokens: entry.max_tokens,
+            })
+        })
+        .collect()
+}
+
+fn load_pricing() -> Result<Vec<ModelPrice>> {
+    match read_json_setting("MODEL_PRICING")? {
+        Some(raw) => serde_json::from_str(&raw)
+            .context("MODEL_PRICING must be a JSON array of {model, input_per_1k, output_per_1k}"),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn load_budget() -> Result<Option<BudgetConfig>> {
+    let Ok(limit) = env::var("DAILY_BUDGET_USD") else {
+        return Ok(None);
+    };
+    let action = match env::var("BUDGET_EXCEEDED_ACTION")
+        .unwrap_or_else(|_| "reject".to_string())
+        .to_lowercase()
+        .as_str()
+    {
+        "reject" => BudgetAction::Reject,
+        "degrade" => BudgetAction::Degrade,
+        _ => return Err(anyhow::anyhow!("BUDGET_EXCEEDED_ACTION must be reject or degrade")),
+    };
+    Ok(Some(BudgetConfig {
+        daily_limit_usd: limit
+            .parse()
+            .context("DAILY_BUDGET_USD must be a valid number")?,
+        action,
+    }))
+}
+
+fn load_audit() -> Result<Option<AuditConfig>> {
+    let Ok(path) = env::var("AUDIT_LOG_PATH") else {
+        return Ok(None);
+    };
+    Ok(Some(AuditConfig {
+        path,
+        sample_rate: env::var("AUDIT_SAMPLE_RATE")
+            .unwrap_or_else(|_| "1.0".to_string())
+            .parse()
+            .context("AUDIT_SAMPLE_RATE must be a valid number")?,
+        max_file_bytes: env::var("AUDIT_MAX_FILE_BYTES")
+            .unwrap_or_else(|_| "104857600".to_string())
+            .parse()
+            .context("AUDIT_MAX_FILE_BYTES must be a valid number")?,
+        retention_days: env::var("AUDIT_RETENTION_DAYS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse()
+            .context("AUDIT_RETENTION_DAYS must be a valid number")?,
+    }))
+}
+
+fn load_chaos() -> Result<Option<ChaosConfig>> {
+    read_json_setting("CHAOS")?
+        .map(|raw| {
+            serde_json::from_str(&raw)
+                .context("CHAOS must be a JSON object with small_model and/or large_model faults")
+        })
+        .transpose()
+}
+
+// Uses the standard OpenTelemetry variable names so existing deployments carry over
+fn load_telemetry() -> Result<Option<TelemetryConfig>> {
+    let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_ENDPOINT") else {
+        return Ok(None);
+    };
+    let protocol = match env::var("OTEL_EXPORTER_OTLP_PROTOCOL")
+        .unwrap_or_else(|_| "http/protobuf".to_string())
+        .as_str()
+    {
+        "http/protobuf" => OtlpProtocol::HttpProtobuf,
+        "http/json" => OtlpProtocol::HttpJson,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "OTEL_EXPORTER_OTLP_PROTOCOL must be http/protobuf or http/json"
+            ))
+        }
+    };
+    Ok(Some(TelemetryConfig {
+        endpoint,
+        service_name: env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| "loro".to_string()),
+        protocol,
+    }))
 }
 
 #[derive(Clone)]
@@ -23,6 +352,13 @@ pub struct ModelConfig {
     pub model_name: String,
 }
 
+impl ModelConfig {
+    /// Ollama is detected by its default port; everything else is treated as OpenAI-compatible.
+    pub fn is_ollama(&self) -> bool {
+        self.base_url.contains("11434")
+    }
+}
+
 // Custom Debug implementation to hide API keys
 impl std::fmt::Debug for ModelConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -91,6 +427,46 @@ impl Config {
                 .unwrap_or_else(|_| "deepseek-ai/DeepSeek-V2.5".to_string()),
         };
 
+        let model_routes = load_model_routes(&small_model, &large_model)?;
+
+        // Speech-to-text is only enabled when a transcription backend is configured
+        let transcription = env::var("TRANSCRIPTION_BASE_URL")
+            .ok()
+            .map(|base_url| TranscriptionConfig {
+                backend: ModelConfig {
+                    api_key: Secret::new(
+                        env::var("TRANSCRIPTION_API_KEY").unwrap_or_else(|_| "none".to_string()),
+                    ),
+                    base_url,
+                    model_name: env::var("TRANSCRIPTION_MODEL")
+                        .unwrap_or_else(|_| "whisper-1".to_string()),
+                },
+                language: env::var("TRANSCRIPTION_LANGUAGE").ok(),
+            });
+
+        let speech = match env::var("TTS_BASE_URL") {
+            Ok(base_url) => Some(SpeechConfig {
+                backend: ModelConfig {
+                    api_key: Secret::new(
+                        env::var("TTS_API_KEY").unwrap_or_else(|_| "none".to_string()),
+                    ),
+                    base_url,
+                    model_name: env::var("TTS_MODEL").unwrap_or_else(|_| "tts-1".to_string()),
+                },
+                voice: env::var("TTS_VOICE").unwrap_or_else(|_| "alloy".to_string()),
+                format: env::var("TTS_FORMAT").unwrap_or_else(|_| "mp3".to_string()),
+                max_concurrency: env::var("TTS_MAX_CONCURRENCY")
+                    .unwrap_or_else(|_| "2".to_string())
+                    .parse()
+                    .context("TTS_MAX_CONCURRENCY must be a valid number")?,
+                precache: env::var("TTS_PRECACHE")
+                    .unwrap_or_else(|_| "true".to_string())
+                    .parse()
+                    .context("TTS_PRECACHE must be true or false")?,
+            }),
+            Err(_) => None,
+        };
+
         let config = Config {
             host: env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
             port: env::var("PORT")
@@ -116,6 +492,54 @@ impl Config {
                 .unwrap_or_else(|_| "10000".to_string())
                 .parse()
                 .context("STATS_MAX_ENTRIES must be a valid number")?,
+            shutdown_drain_timeout_secs: env::var("SHUTDOWN_DRAIN_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .context("SHUTDOWN_DRAIN_TIMEOUT_SECS must be a valid number")?,
+            shutdown_pre_drain_delay_secs: env::var("SHUTDOWN_PRE_DRAIN_DELAY_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("SHUTDOWN_PRE_DRAIN_DELAY_SECS must be a valid number")?,
+            readiness_probe_interval_secs: env::var("READINESS_PROBE_INTERVAL_SECS")
+                .unwrap_or_else(|_| "0".to_string())
+                .parse()
+                .context("READINESS_PROBE_INTERVAL_SECS must be a valid number")?,
+            readiness_probe_timeout_secs: env::var("READINESS_PROBE_TIMEOUT_SECS")
+                .unwrap_or_else(|_| "2".to_string())
+                .parse()
+                .context("READINESS_PROBE_TIMEOUT_SECS must be a valid number")?,
+            model_routes,
+            transcription,
+            max_audio_upload_bytes: env::var("MAX_AUDIO_UPLOAD_BYTES")
+                .unwrap_or_else(|_| "26214400".to_string())
+                .parse()
+                .context("MAX_AUDIO_UPLOAD_BYTES must be a valid number")?,
+            speech,
+            error_apology: match env::var("ERROR_APOLOGY") {
+                Ok(apology) => Some(apology).filter(|a| !a.trim().is_empty()),
+                Err(_) => Some(DEFAULT_ERROR_APOLOGY.to_string()),
+            },
+            expose_upstream_errors: env::var("EXPOSE_UPSTREAM_ERRORS")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .context("EXPOSE_UPSTREAM_ERRORS must be true or false")?,
+            pricing: load_pricing()?,
+            client_api_keys: env::var("CLIENT_API_KEYS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(|key| Secret::new(key.to_string()))
+                .collect(),
+            budget: load_budget()?,
+            telemetry: load_telemetry()?,
+            log_format: env::var("LOG_FORMAT").unwrap_or_else(|_| "text".to_string()),
+            access_log: env::var("ACCESS_LOG")
+                .unwrap_or_else(|_| "true".to_string())
+                .parse()
+                .context("ACCESS_LOG must be true or false")?,
+            audit: load_audit()?,
+            chaos: load_chaos()?,
         };
 
         // Validate configuration
@@ -132,6 +556,9 @@ impl Config {
                 "LOG_LEVEL must be one of trace|debug|info|warn|error"
             ));
         }
+        if !["text", "json"].contains(&self.log_format.to_lowercase().as_str()) {
+            return Err(anyhow::anyhow!("LOG_FORMAT must be text or json"));
+        }
 
         // Validate API keys are not empty (allow "none" for local services like Ollama)
         if self.small_model.api_key.expose_secret().trim().is_empty() {
@@ -185,6 +612,193 @@ impl Config {
                 "STATS_MAX_ENTRIES must be between 100 and 100000"
             ));
         }
+        if self.shutdown_drain_timeout_secs > 300 {
+            return Err(anyhow::anyhow!(
+                "SHUTDOWN_DRAIN_TIMEOUT_SECS must be <= 300"
+            ));
+        }
+        if self.shutdown_pre_drain_delay_secs > 60 {
+            return Err(anyhow::anyhow!(
+                "SHUTDOWN_PRE_DRAIN_DELAY_SECS must be <= 60"
+            ));
+        }
+        if self.readiness_probe_interval_secs > 3600 {
+            return Err(anyhow::anyhow!(
+                "READINESS_PROBE_INTERVAL_SECS must be <= 3600"
+            ));
+        }
+        if self.readiness_probe_timeout_secs < 1 || self.readiness_probe_timeout_secs > 30 {
+            return Err(anyhow::anyhow!(
+                "READINESS_PROBE_TIMEOUT_SECS must be between 1 and 30 seconds"
+            ));
+        }
+
+        if let Some(transcription) = &self.transcription {
+            if !transcription.backend.base_url.starts_with("http") {
+                return Err(anyhow::anyhow!(
+                    "TRANSCRIPTION_BASE_URL must be a valid HTTP(S) URL"
+                ));
+            }
+            if transcription.backend.model_name.trim().is_empty() {
+                return Err(anyhow::anyhow!("TRANSCRIPTION_MODEL cannot be empty"));
+            }
+        }
+        if self.max_audio_upload_bytes < 1024 || self.max_audio_upload_bytes > 100 * 1024 * 1024 {
+            return Err(anyhow::anyhow!(
+                "MAX_AUDIO_UPLOAD_BYTES must be between 1024 and 104857600"
+            ));
+        }
+
+        if let Some(speech) = &self.speech {
+            if !speech.backend.base_url.starts_with("http") {
+                return Err(anyhow::anyhow!("TTS_BASE_URL must be a valid HTTP(S) URL"));
+            }
+            if speech.backend.model_name.trim().is_empty() {
+                return Err(anyhow::anyhow!("TTS_MODEL cannot be empty"));
+            }
+            if speech.voice.trim().is_empty() {
+                return Err(anyhow::anyhow!("TTS_VOICE cannot be empty"));
+            }
+            if !crate::models::AUDIO_FORMATS.contains(&speech.format.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "TTS_FORMAT must be one of {}",
+                    crate::models::AUDIO_FORMATS.join("|")
+                ));
+            }
+            if speech.max_concurrency < 1 || speech.max_concurrency > 8 {
+                return Err(anyhow::anyhow!("TTS_MAX_CONCURRENCY must be between 1 and 8"));
+            }
+        }
+
+        // Validate pricing and budget
+        let mut priced_models = std::collections::HashSet::new();
+        for price in &self.pricing {
+            if price.model.trim().is_empty() {
+                return Err(anyhow::anyhow!("MODEL_PRICING model cannot be empty"));
+            }
+            if !priced_models.insert(price.model.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "MODEL_PRICING contains duplicate model '{}'",
+                    price.model
+                ));
+            }
+            for value in [price.input_per_1k, price.output_per_1k] {
+                if !value.is_finite() || value < 0.0 {
+                    return Err(anyhow::anyhow!(
+                        "MODEL_PRICING prices for '{}' must be non-negative numbers",
+                        price.model
+                    ));
+                }
+            }
+        }
+        if let Some(budget) = &self.budget {
+            if !budget.daily_limit_usd.is_finite() || budget.daily_limit_usd <= 0.0 {
+                return Err(anyhow::anyhow!("DAILY_BUDGET_USD must be greater than 0"));
+            }
+            if self.pricing.is_empty() {
+                return Err(anyhow::anyhow!("DAILY_BUDGET_USD requires MODEL_PRICING"));
+            }
+        }
+
+        if let Some(telemetry) = &self.telemetry {
+            if !telemetry.endpoint.starts_with("http") {
+                return Err(anyhow::anyhow!(
+                    "OTEL_EXPORTER_OTLP_ENDPOINT must be a valid HTTP(S) URL"
+                ));
+            }
+            if telemetry.service_name.trim().is_empty() {
+                return Err(anyhow::anyhow!("OTEL_SERVICE_NAME cannot be empty"));
+            }
+        }
+
+        if let Some(audit) = &self.audit {
+            if audit.path.trim().is_empty() {
+                return Err(anyhow::anyhow!("AUDIT_LOG_PATH cannot be empty"));
+            }
+            if !(0.0..=1.0).contains(&audit.sample_rate) {
+                return Err(anyhow::anyhow!("AUDIT_SAMPLE_RATE must be between 0 and 1"));
+            }
+            if audit.max_file_bytes == 0 {
+                return Err(anyhow::anyhow!("AUDIT_MAX_FILE_BYTES must be greater than 0"));
+            }
+        }
+
+        if let Some(chaos) = &self.chaos {
+            let roles = [("small_model", &chaos.small_model), ("large_model", &chaos.large_model)];
+            for (role, faults) in roles {
+                let Some(faults) = faults else { continue };
+                let probabilities = [
+                    ("latency_probability", faults.latency_probability),
+                    ("error_probability", faults.error_probability),
+                    ("disconnect_probability", faults.disconnect_probability),
+                    ("truncate_probability", faults.truncate_probability),
+                ];
+                for (name, probability) in probabilities {
+                    if !(0.0..=1.0).contains(&probability) {
+                        return Err(anyhow::anyhow!(
+                            "CHAOS {} {} must be between 0 and 1",
+                            role,
+                            name
+                        ));
+                    }
+                }
+                if faults.error_probability > 0.0 && faults.error_statuses.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "CHAOS {} error_statuses cannot be empty",
+                        role
+                    ));
+                }
+                if let Some(status) = faults
+                    .error_statuses
+                    .iter()
+                    .find(|&&status| status != 429 && !(500..=599).contains(&status))
+                {
+                    return Err(anyhow::anyhow!(
+                        "CHAOS {} error status {} must be 429 or 5xx",
+                        role,
+                        status
+                    ));
+                }
+            }
+        }
+
+        // Validate model routes
+        let mut route_names = std::collections::HashSet::new();
+        for route in &self.model_routes {
+            if route.name.trim().is_empty() {
+                return Err(anyhow::anyhow!("MODEL_ROUTES route name cannot be empty"));
+            }
+            if !route_names.insert(route.name.as_str()) {
+                return Err(anyhow::anyhow!(
+                    "MODEL_ROUTES contains duplicate route '{}'",
+                    route.name
+                ));
+            }
+            for (kind, model) in [("large_model", &route.large_model), ("small_model", &route.small_model)] {
+                if !model.base_url.starts_with("http") {
+                    return Err(anyhow::anyhow!(
+                        "MODEL_ROUTES route '{}' {} base_url must be a valid HTTP(S) URL",
+                        route.name,
+                        kind
+                    ));
+                }
+                if model.model_name.trim().is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "MODEL_ROUTES route '{}' {} model_name cannot be empty",
+                        route.name,
+                        kind
+                    ));
+                }
+            }
+            if let Some(max_tokens) = route.max_tokens {
+                if max_tokens == 0 || max_tokens > 8192 {
+                    return Err(anyhow::anyhow!(
+                        "MODEL_ROUTES route '{}' max_tokens must be between 1 and 8192",
+                        route.name
+                    ));
+                }
+            }
+        }
 
         Ok(())
     }
@@ -216,6 +830,24 @@ mod tests {
             small_model_timeout_secs: 5,
             max_retries: 3,
             stats_max_entries: 1000,
+            shutdown_drain_timeout_secs: 30,
+            shutdown_pre_drain_delay_secs: 0,
+            readiness_probe_interval_secs: 0,
+            readiness_probe_timeout_secs: 2,
+            model_routes: Vec::new(),
+            transcription: None,
+            max_audio_upload_bytes: 26214400,
+            error_apology: None,
+            expose_upstream_errors: false,
+            pricing: Vec::new(),
+            client_api_keys: Vec::new(),
+            budget: None,
+            telemetry: None,
+            log_format: "text".to_string(),
+            access_log: true,
+            audit: None,
+            chaos: None,
+            speech: None,
         };
 
         // Valid config should pass
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -33,6 +34,9 @@ pub enum LoroError {
     #[error("Stream processing error: {0}")]
     StreamProcessing(String),
 
+    #[error("Daily budget of ${limit_usd} exceeded")]
+    BudgetExceeded { limit_usd: f64 },
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
@@ -49,4 +53,163 @@ impl LoroError {
     pub fn is_validation_error(&self) -> bool {
         matches!(self, LoroError::Validation(_))
     }
+
+    /// HTTP status reported to clients. Upstream failures become gateway errors:
+    /// rate limits stay 429, unavailable or unreachable upstreams 503, timeouts 504.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            LoroError::Validation(_) => 400,
+            LoroError::BudgetExceeded { .. } => 429,
+            LoroError::Timeout { .. } => 504,
+            LoroError::ApiError { status, .. } => match status {
+                429 => 429,
+                503 => 503,
+                408 | 504 => 504,
+                _ => 502,
+            },
+            LoroError::HttpClient(e) if e.is_timeout() => 504,
+            LoroError::HttpClient(e) if e.is_connect() => 503,
+            LoroError::HttpClient(_)
+            | LoroError::JsonParse(_)
+            | LoroError::SmallModelFailed(_)
+            | LoroError::LargeModelFailed(_)
+            | LoroError::StreamProcessing(_) => 502,
+            LoroError::Config(_) | LoroError::Internal(_) => 500,
+        }
+    }
+
+    /// OpenAI-style error `type`
+    pub fn error_type(&self) -> &'static str {
+        match self.status_code() {
+            400 => "invalid_request_error",
+            429 => "rate_limit_error",
+            504 => "timeout_error",
+            500 => "internal_error",
+            _ => "api_error",
+        }
+    }
+
+    /// Stable machine-readable error `code`
+    pub fn code(&self) -> &'static str {
+        match self {
+            LoroError::Config(_) | LoroError::Internal(_) => "internal_error",
+            LoroError::Validation(_) => "validation_failed",
+            LoroError::JsonParse(_) => "invalid_upstream_response",
+            LoroError::SmallModelFailed(_) => "small_model_failed",
+            LoroError::LargeModelFailed(_) => "large_model_failed",
+            LoroError::StreamProcessing(_) => "stream_interrupted",
+            LoroError::BudgetExceeded { .. } => "budget_exceeded",
+            _ => match self.status_code() {
+                429 => "rate_limited",
+                503 => "upstream_unavailable",
+                504 => "upstream_timeout",
+                _ => "upstream_error",
+            },
+        }
+    }
+
+    /// Raw upstream error, only shown to clients when `EXPOSE_UPSTREAM_ERRORS` is set
+    pub fn upstream_details(&self) -> Option<UpstreamDetails> {
+        let (provider, status, message) = match self {
+            LoroError::ApiError {
+                provider,
+                status,
+                message,
+            } => (Some(provider.clone()), Some(*status), message.clone()),
+            LoroError::HttpClient(e) => (None, e.status().map(|s| s.as_u16()), e.to_string()),
+            LoroError::JsonParse(e) => (None, None, e.to_string()),
+            LoroError::SmallModelFailed(msg) => (Some("small".to_string()), None, msg.clone()),
+            LoroError::LargeModelFailed(msg) => (Some("large".to_string()), None, msg.clone()),
+            LoroError::StreamProcessing(msg) => (None, None, msg.clone()),
+            _ => return None,
+        };
+        Some(UpstreamDetails {
+            provider,
+            status,
+            message,
+        })
+    }
+
+    /// Message safe to show to clients; internal and upstream details stay in the logs.
+    pub fn public_message(&self) -> String {
+        match self {
+            LoroError::Timeout { timeout_secs } => {
+                format!("Request timeout after {}s", timeout_secs)
+            }
+            LoroError::ApiError { provider, .. } if self.status_code() == 429 => {
+                format!("Upstream {} is rate limiting requests, retry later", provider)
+            }
+            LoroError::ApiError {
+                provider, status, ..
+            } => format!("Upstream {} returned status {}", provider, status),
+            LoroError::HttpClient(e) if e.is_timeout() => "Upstream request timed out".to_string(),
+            LoroError::HttpClient(e) if e.is_connect() => "Upstream is unavailable".to_string(),
+            LoroError::JsonParse(_) => "Invalid response from upstream".to_string(),
+            LoroError::HttpClient(_) => "Upstream request failed".to_string(),
+            LoroError::Validation(msg) => msg.clone(),
+            LoroError::SmallModelFailed(_) => "Small model request failed".to_string(),
+            LoroError::LargeModelFailed(_) => "Large model request failed".to_string(),
+            LoroError::StreamProcessing(_) => "The response stream was interrupted".to_string(),
+            LoroError::BudgetExceeded { limit_usd } => {
+                format!("Daily budget of ${:.2} for this API key is exhausted", limit_usd)
+            }
+            LoroError::Config(_) | LoroError::Internal(_) => "Internal server error".to_string(),
+        }
+    }
+}
+
+/// OpenAI-compatible error object, used in error responses and as stream error events.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub code: String,
+    /// User-facing text to say instead of the failed reply (`ERROR_APOLOGY`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub apology: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream: Option<UpstreamDetails>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpstreamDetails {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl ErrorBody {
+    pub fn new(message: impl Into<String>, error_type: &str, code: &str) -> Self {
+        Self {
+            message: message.into(),
+            error_type: error_type.to_string(),
+            code: code.to_string(),
+            apology: None,
+            upstream: None,
+        }
+    }
+
+    /// Typed body for `LoroError`s; anything else is reported as an internal error.
+    pub fn from_error(e: &anyhow::Error) -> Self {
+        match e.downcast_ref::<LoroError>() {
+            Some(error) => Self::new(error.public_message(), error.error_type(), error.code()),
+            None => Self::new("Internal server error", "internal_error", "internal_error"),
+        }
+    }
+
+    /// Attaches the raw upstream error, for debugging deployments.
+    pub fn with_upstream_details(mut self, e: &anyhow::Error) -> Self {
+        self.upstream = e
+            .downcast_ref::<LoroError>()
+            .and_then(LoroError::upstream_details);
+        self
+    }
+
+    pub fn with_apology(mut self, apology: Option<String>) -> Self {
+        self.apology = apology;
+        self
+    }
 }
@@ -1,17 +1,64 @@
+pub mod access_log;
+pub mod audit;
+pub mod bench;
+pub mod chaos;
+pub mod chunk;
 pub mod config;
 pub mod errors;
+pub mod mock_upstream;
 pub mod models;
+pub mod ndjson;
+pub mod readiness;
+pub mod realtime;
+pub mod replay;
+pub mod routing;
 pub mod service;
+pub mod shutdown;
+pub mod speech;
+pub mod sse;
 pub mod stats;
+pub mod telemetry;
+pub mod timing;
+pub mod transcription;
+pub mod usage;
 
 // Re-export main functions for testing
 use axum::{
-    extract::State,
-    http::StatusCode,
+    body::Bytes,
+    extract::{ws::WebSocketUpgrade, Extension, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{Json, Response},
 };
+use access_log::AccessRecord;
+use errors::{ErrorBody, LoroError};
+use std::collections::HashMap;
 use std::sync::Arc;
-use service::LoroService;
+use service::{LoroService, RequestContext, REQUEST_ID_HEADER};
+
+/// Gives every request an `X-Request-Id` (the client's, or a generated one) before the
+/// handlers see it, and returns the same ID on the response.
+pub async fn propagate_request_id(
+    mut request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let request_id = RequestContext::from_headers(request.headers()).request_id;
+    let Ok(value) = HeaderValue::from_str(&request_id) else {
+        return next.run(request).await;
+    };
+    request.headers_mut().insert(REQUEST_ID_HEADER, value.clone());
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    response
+}
+
+/// Request context that reports to the access log when `access_log::access_log` runs
+fn request_context(headers: &HeaderMap, access: Option<Extension<AccessRecord>>) -> RequestContext {
+    let Extension(access) = access.unwrap_or_default();
+    RequestContext {
+        access,
+        ..RequestContext::from_headers(headers)
+    }
+}
 
 pub async fn root() -> Json<serde_json::Value> {
     Json(serde_json::json!({
@@ -21,87 +68,256 @@ pub async fn root() -> Json<serde_json::Value> {
     }))
 }
 
-pub async fn health() -> Json<serde_json::Value> {
-    Json(serde_json::json!({
-        "status": "healthy"
-    }))
+pub async fn health(State(service): State<Arc<LoroService>>) -> (StatusCode, Json<serde_json::Value>) {
+    let drain = service.drain_state();
+    if drain.is_announced() {
+        // Report 503 so load balancers stop routing new traffic here
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({
+                "status": "draining",
+                "active_streams": drain.active_streams()
+            })),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "status": "healthy"
+        })),
+    )
+}
+
+pub async fn ready(State(service): State<Arc<LoroService>>) -> (StatusCode, Json<serde_json::Value>) {
+    let report = service.check_readiness().await;
+    let draining = service.drain_state().is_announced();
+    let is_ready = report.is_ready() && !draining;
+    let status = if is_ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(serde_json::json!({
+            "status": if is_ready { "ready" } else { "not_ready" },
+            "draining": draining,
+            "upstreams": {
+                "small_model": report.small_model,
+                "large_model": report.large_model
+            }
+        })),
+    )
+}
+
+type ErrorResponse = (StatusCode, Json<serde_json::Value>);
+
+fn error_body(status: StatusCode, message: impl Into<String>, error_type: &str, code: &str) -> ErrorResponse {
+    let body = ErrorBody::new(message, error_type, code);
+    (status, Json(serde_json::json!({ "error": body })))
+}
+
+fn draining_error() -> ErrorResponse {
+    error_body(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Server is shutting down",
+        "service_unavailable",
+        "draining",
+    )
+}
+
+fn validation_error(message: impl Into<String>) -> ErrorResponse {
+    error_body(
+        StatusCode::BAD_REQUEST,
+        message,
+        "invalid_request_error",
+        "validation_failed",
+    )
+}
+
+/// Maps service errors to OpenAI-style error responses.
+fn service_error(service: &LoroService, e: &anyhow::Error) -> ErrorResponse {
+    let status = e
+        .downcast_ref::<LoroError>()
+        .and_then(|error| StatusCode::from_u16(error.status_code()).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    if status.is_server_error() {
+        tracing::warn!("Request failed with {}: {:#}", status, e);
+    }
+    (status, Json(serde_json::json!({ "error": service.error_body(e) })))
 }
 
 pub async fn chat_completions(
     State(service): State<Arc<LoroService>>,
+    headers: HeaderMap,
+    access: Option<Extension<AccessRecord>>,
     Json(request): Json<models::ChatCompletionRequest>,
-) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Response, ErrorResponse> {
     use tracing::warn;
-    use errors::LoroError;
+
+    if service.is_draining() {
+        return Err(draining_error());
+    }
 
     // Validate request
-    if let Err(validation_error) = request.validate() {
-        warn!("Request validation failed: {}", validation_error);
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({
-                "error": {
-                    "message": validation_error,
-                    "type": "invalid_request_error",
-                    "code": "validation_failed"
-                }
-            })),
+    if let Err(validation_error_message) = request.validate() {
+        warn!("Request validation failed: {}", validation_error_message);
+        return Err(validation_error(validation_error_message));
+    }
+    check_speech(&service, &request)?;
+
+    let ctx = request_context(&headers, access);
+    service.chat_completion(request, &ctx).await.map_err(|e| {
+        warn!("Chat completion error: {}", e);
+        service_error(&service, &e)
+    })
+}
+
+fn check_speech(
+    service: &LoroService,
+    request: &models::ChatCompletionRequest,
+) -> Result<(), ErrorResponse> {
+    if request.wants_audio() && !service.speech_enabled() {
+        return Err(error_body(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Audio output is not configured (set TTS_BASE_URL)",
+            "service_unavailable",
+            "speech_not_configured",
         ));
     }
+    Ok(())
+}
 
-    match service.chat_completion(request).await {
-        Ok(response) => Ok(response),
-        Err(e) => {
-            warn!("Chat completion error: {}", e);
-            let error_response = match e.downcast_ref::<LoroError>() {
-                Some(LoroError::Timeout { timeout_secs }) => (
-                    StatusCode::REQUEST_TIMEOUT,
-                    Json(serde_json::json!({
-                        "error": {
-                            "message": format!("Request timeout after {}s", timeout_secs),
-                            "type": "timeout_error",
-                            "code": "request_timeout"
-                        }
-                    })),
-                ),
-                Some(LoroError::ApiError {
-                    provider,
-                    status: _,
-                    message,
-                }) => (
-                    StatusCode::BAD_GATEWAY,
-                    Json(serde_json::json!({
-                        "error": {
-                            "message": format!("API error from {}: {}", provider, message),
-                            "type": "api_error",
-                            "code": "upstream_error"
-                        }
-                    })),
-                ),
-                Some(LoroError::Validation(msg)) => (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({
-                        "error": {
-                            "message": msg,
-                            "type": "invalid_request_error",
-                            "code": "validation_failed"
-                        }
-                    })),
-                ),
-                _ => (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": {
-                            "message": "Internal server error",
-                            "type": "internal_error",
-                            "code": "internal_error"
-                        }
-                    })),
-                ),
-            };
-            Err(error_response)
-        }
+fn audio_upload(
+    service: &LoroService,
+    query: HashMap<String, String>,
+    headers: &HeaderMap,
+    body: Bytes,
+) -> Result<transcription::AudioUpload, ErrorResponse> {
+    if !service.transcription_enabled() {
+        return Err(error_body(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Speech-to-text is not configured (set TRANSCRIPTION_BASE_URL)",
+            "service_unavailable",
+            "transcription_not_configured",
+        ));
     }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+    let mut upload =
+        transcription::AudioUpload::from_body(content_type, body).map_err(validation_error)?;
+    // Query parameters carry the settings for raw audio bodies; form fields win
+    for (key, value) in query {
+        upload.fields.entry(key).or_insert(value);
+    }
+    Ok(upload)
+}
+
+/// OpenAI-compatible speech-to-text: forwards the audio to the transcription backend.
+pub async fn audio_transcriptions(
+    State(service): State<Arc<LoroService>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ErrorResponse> {
+    use axum::response::IntoResponse;
+    use tracing::warn;
+
+    if service.is_draining() {
+        return Err(draining_error());
+    }
+
+    let upload = audio_upload(&service, query, &headers, body)?;
+    let ctx = RequestContext::from_headers(&headers);
+    let transcription = service.transcribe(&upload, &ctx).await.map_err(|e| {
+        warn!("Transcription error: {}", e);
+        service_error(&service, &e)
+    })?;
+
+    match upload.field("response_format") {
+        Some("text") => Ok(transcription.text.into_response()),
+        Some("json") | None => Ok(Json(transcription).into_response()),
+        Some(other) => Err(validation_error(format!(
+            "Unsupported response_format '{}', expected json or text",
+            other
+        ))),
+    }
+}
+
+/// Audio in, streamed reply out: transcribes the upload, then runs the transcript
+/// through the chat pipeline. The SSE stream starts with a `transcript` event.
+pub async fn voice_turn(
+    State(service): State<Arc<LoroService>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    access: Option<Extension<AccessRecord>>,
+    body: Bytes,
+) -> Result<Response, ErrorResponse> {
+    use axum::response::{sse::Event, IntoResponse, Sse};
+    use futures::{stream, StreamExt};
+    use tracing::warn;
+
+    if service.is_draining() {
+        return Err(draining_error());
+    }
+
+    let upload = audio_upload(&service, query, &headers, body)?;
+    let ctx = request_context(&headers, access);
+    let transcription = service.transcribe(&upload, &ctx).await.map_err(|e| {
+        warn!("Transcription error: {}", e);
+        service_error(&service, &e)
+    })?;
+    if transcription.text.is_empty() {
+        return Err(error_body(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "No speech detected in the audio",
+            "invalid_request_error",
+            "no_speech",
+        ));
+    }
+
+    let request = upload
+        .chat_request(&transcription.text)
+        .map_err(validation_error)?;
+    request.validate().map_err(validation_error)?;
+    check_speech(&service, &request)?;
+
+    let (replies, _) = service.reply_stream(request, &ctx).await.map_err(|e| {
+        warn!("Voice turn error: {}", e);
+        service_error(&service, &e)
+    })?;
+    let transcript_event = Event::default()
+        .event("transcript")
+        .json_data(&transcription)
+        .map_err(|e| service_error(&service, &e.into()))?;
+    let events = stream::once(async move { Ok(transcript_event) })
+        .chain(service::sse_events(replies));
+
+    Ok(Sse::new(events).into_response())
+}
+
+/// Upgrades to a full-duplex voice session; see `realtime` for the event protocol.
+pub async fn realtime(
+    State(service): State<Arc<LoroService>>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ErrorResponse> {
+    use axum::response::IntoResponse;
+
+    if service.is_draining() {
+        return Err(draining_error());
+    }
+
+    let ctx = RequestContext::from_headers(&headers);
+    Ok(ws
+        .on_upgrade(move |socket| realtime::handle_socket(socket, service, ctx))
+        .into_response())
+}
+
+pub async fn list_models(State(service): State<Arc<LoroService>>) -> Json<models::ModelList> {
+    Json(service.list_models())
 }
 
 pub async fn get_metrics(State(service): State<Arc<LoroService>>) -> Json<serde_json::Value> {
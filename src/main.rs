@@ -1,13 +1,25 @@
 use axum::{
+    extract::DefaultBodyLimit,
+    http::Request,
+    middleware,
     routing::{get, post},
     Router,
 };
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::info;
+use tracing::{info, info_span, warn};
 
-use loro::{config::Config, service::LoroService};
+use loro::{
+    access_log,
+    config::Config,
+    service::{LoroService, REQUEST_ID_HEADER},
+    shutdown::shutdown_signal,
+    telemetry,
+};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -19,11 +31,35 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize tracing using Config.log_level (unified entrypoint)
     let level = config.log_level.to_lowercase();
-    let filter = tracing_subscriber::EnvFilter::new(format!("{},tower_http={}", level, level));
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
+    // Access lines have their own switch, so they survive a quieter LOG_LEVEL
+    let access_level = if config.access_log { "info" } else { "off" };
+    let filter = tracing_subscriber::EnvFilter::new(format!(
+        "{},tower_http={},{}={}",
+        level,
+        level,
+        access_log::ACCESS_TARGET,
+        access_level
+    ));
+    let json = config.log_format.eq_ignore_ascii_case("json");
+    // Spans are also exported over OTLP when a collector is configured
+    let tracer_provider = config
+        .telemetry
+        .as_ref()
+        .map(telemetry::tracer_provider)
+        .transpose()?;
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(json.then(|| tracing_subscriber::fmt::layer().json().flatten_event(true)))
+        .with((!json).then(|| tracing_subscriber::fmt::layer().with_target(false)))
+        .with(tracer_provider.as_ref().map(telemetry::layer))
         .init();
+    if let Some(telemetry) = &config.telemetry {
+        info!(
+            "Exporting traces to {} as {}",
+            telemetry::traces_endpoint(&telemetry.endpoint),
+            telemetry.service_name
+        );
+    }
 
     // Performance hint: Consider setting thread affinity in production
     // e.g., use taskset on Linux or thread affinity APIs
@@ -34,15 +70,66 @@ async fn main() -> anyhow::Result<()> {
 
     // Initialize service
     let loro_service = Arc::new(LoroService::new(config.clone()).await?);
+    let drain = loro_service.drain_state();
+    if let Some(transcription) = &config.transcription {
+        info!(
+            "Speech-to-text enabled via {} ({})",
+            transcription.backend.base_url, transcription.backend.model_name
+        );
+    }
+    if let Some(speech) = &config.speech {
+        info!(
+            "Text-to-speech enabled via {} ({}, voice {})",
+            speech.backend.base_url, speech.backend.model_name, speech.voice
+        );
+    }
+    if let Some(audit) = &config.audit {
+        info!(
+            "Recording {:.0}% of conversations to {}",
+            audit.sample_rate * 100.0,
+            audit.path
+        );
+    }
+    loro_service.spawn_speech_warmup();
+    if loro_service.spawn_readiness_probe().is_some() {
+        info!(
+            "Background readiness probing every {}s",
+            config.readiness_probe_interval_secs
+        );
+    }
 
     // Build router
+    let audio_limit = DefaultBodyLimit::max(config.max_audio_upload_bytes);
     let app = Router::new()
         .route("/", get(loro::root))
         .route("/health", get(loro::health))
+        .route("/ready", get(loro::ready))
         .route("/v1/chat/completions", post(loro::chat_completions))
+        .route("/v1/models", get(loro::list_models))
+        .route("/v1/realtime", get(loro::realtime))
+        .route(
+            "/v1/audio/transcriptions",
+            post(loro::audio_transcriptions).layer(audio_limit),
+        )
+        .route("/v1/voice/turn", post(loro::voice_turn).layer(audio_limit))
         .route("/metrics", get(loro::get_metrics))
         .route("/metrics/reset", post(loro::reset_metrics))
-        .layer(TraceLayer::new_for_http())
+        .layer(TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
+            let request_id = request
+                .headers()
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            info_span!(
+                "http",
+                method = %request.method(),
+                uri = %request.uri(),
+                request_id = %request_id
+            )
+        }))
+        .layer(middleware::from_fn(access_log::access_log))
+        // Outside the trace layer so the span already sees the request ID
+        .layer(middleware::from_fn(loro::propagate_request_id))
         .layer(CorsLayer::permissive())
         .with_state(loro_service);
 
@@ -50,7 +137,52 @@ async fn main() -> anyhow::Result<()> {
     let listener = TcpListener::bind(format!("{}:{}", config.host, config.port)).await?;
     info!("🚀 Loro server listening on {}", listener.local_addr()?);
 
-    axum::serve(listener, app).await?;
+    // On SIGTERM/SIGINT fail health checks for the pre-drain delay, then stop
+    // accepting connections and let in-flight streams finish
+    let shutdown = {
+        let drain = Arc::clone(&drain);
+        let delay = Duration::from_secs(config.shutdown_pre_drain_delay_secs);
+        async move {
+            shutdown_signal().await;
+            drain.drain_after(delay).await;
+        }
+    };
+    let mut server = tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await
+    });
+
+    tokio::select! {
+        result = &mut server => {
+            result??;
+            telemetry::shutdown(tracer_provider).await;
+            return Ok(());
+        }
+        _ = drain.draining() => {}
+    }
+
+    let drain_timeout = Duration::from_secs(config.shutdown_drain_timeout_secs);
+    // Upgraded WebSocket connections outlive the HTTP server, so also wait for their turns
+    let drained = async {
+        server.await??;
+        drain.idle().await;
+        anyhow::Ok(())
+    };
+    match tokio::time::timeout(drain_timeout, drained).await {
+        Ok(result) => {
+            result?;
+            info!("All streams drained, shutdown complete");
+        }
+        Err(_) => {
+            warn!(
+                "Drain timeout after {}s, dropping {} active stream(s)",
+                config.shutdown_drain_timeout_secs,
+                drain.active_streams()
+            );
+        }
+    }
 
+    telemetry::shutdown(tracer_provider).await;
     Ok(())
 }
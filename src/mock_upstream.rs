@@ -0,0 +1,603 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::stream::{self, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// How the mock upstream answers, for non-streaming (quick response) and streaming
+/// (large model) calls separately. Loaded from JSON by the `loro-mock` binary.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MockScript {
+    pub quick: ReplyScript,
+    pub stream: ReplyScript,
+    /// Streaming replies chosen by the last user message, checked in order before `stream`
+    pub prompts: Vec<PromptScript>,
+}
+
+impl Default for MockScript {
+    fn default() -> Self {
+        Self {
+            quick: ReplyScript {
+                tokens: vec!["好的，".to_string()],
+                ..Default::default()
+            },
+            stream: ReplyScript::default(),
+            prompts: Vec::new(),
+        }
+    }
+}
+
+impl MockScript {
+    fn stream_for(&self, body: &Value) -> &ReplyScript {
+        let prompt = body["messages"]
+            .as_array()
+            .and_then(|messages| messages.iter().rev().find(|m| m["role"] == "user"))
+            .and_then(|message| message["content"].as_str())
+            .unwrap_or_default();
+        self.prompts
+            .iter()
+            .find(|script| prompt.contains(&script.contains))
+            .map_or(&self.stream, |script| &script.stream)
+    }
+}
+
+/// Streaming reply for prompts containing `contains`
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptScript {
+    pub contains: String,
+    pub stream: ReplyScript,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReplyScript {
+    /// Content pieces; a non-streaming reply is their concatenation
+    pub tokens: Vec<String>,
+    /// Delay before the response headers
+    pub latency_ms: u64,
+    /// Delay before each streamed token
+    pub token_interval_ms: u64,
+    pub finish_reason: String,
+    pub error: Option<InjectedError>,
+    /// Sends a line that is not valid JSON after this many tokens
+    pub malformed_after: Option<usize>,
+    /// Drops the connection after this many tokens
+    pub disconnect_after: Option<usize>,
+    /// Stops sending after this many tokens and holds the connection open
+    pub stall_after: Option<usize>,
+    /// Writes every streamed event in two body frames, cut inside a multi-byte character
+    pub split_utf8: bool,
+    /// Streamed after the tokens: as two argument fragments each over SSE, or one
+    /// message per call over Ollama
+    pub tool_calls: Vec<MockToolCall>,
+    /// Token counts on the reply (streams only send them when asked with `include_usage`)
+    pub report_usage: bool,
+}
+
+impl Default for ReplyScript {
+    fn default() -> Self {
+        Self {
+            tokens: ["今天", "是晴天", "，", "适合", "出门", "。"]
+                .map(String::from)
+                .to_vec(),
+            latency_ms: 0,
+            token_interval_ms: 0,
+            finish_reason: "stop".to_string(),
+            error: None,
+            malformed_after: None,
+            disconnect_after: None,
+            stall_after: None,
+            split_utf8: false,
+            tool_calls: Vec::new(),
+            report_usage: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MockToolCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Value,
+}
+
+/// An error status returned instead of a reply
+#[derive(Debug, Clone, Deserialize)]
+pub struct InjectedError {
+    pub status: u16,
+    #[serde(default = "default_error_message")]
+    pub message: String,
+    /// Only the first `times` calls fail; every call fails when unset
+    pub times: Option<u32>,
+}
+
+fn default_error_message() -> String {
+    "injected failure".to_string()
+}
+
+/// A request received by the mock
+#[derive(Debug, Clone)]
+pub struct MockCall {
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: Value,
+}
+
+impl MockCall {
+    pub fn is_stream(&self) -> bool {
+        self.body["stream"].as_bool().unwrap_or(false)
+    }
+}
+
+struct MockState {
+    script: MockScript,
+    calls: Mutex<Vec<MockCall>>,
+    quick_errors: AtomicU32,
+    stream_errors: AtomicU32,
+    active_streams: Arc<AtomicUsize>,
+    peak_streams: Arc<AtomicUsize>,
+}
+
+/// Wire format of the reply
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    OpenAI,
+    Ollama,
+}
+
+/// An OpenAI- and Ollama-compatible chat server driven by a [`MockScript`]. Serves
+/// `/v1/chat/completions` and `/api/chat`, the latter also under `/11434` so that a
+/// base URL of [`MockUpstream::ollama_base_url`] is detected as Ollama. The model
+/// listings (`/v1/models`, `/api/tags`) are served empty for readiness probes.
+pub struct MockUpstream {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+}
+
+impl MockUpstream {
+    /// Serves on an ephemeral local port until the runtime shuts down
+    pub async fn start(script: MockScript) -> std::io::Result<Self> {
+        Self::bind("127.0.0.1:0", script).await
+    }
+
+    pub async fn bind(addr: &str, script: MockScript) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(MockState {
+            script,
+            calls: Mutex::new(Vec::new()),
+            quick_errors: AtomicU32::new(0),
+            stream_errors: AtomicU32::new(0),
+            active_streams: Arc::new(AtomicUsize::new(0)),
+            peak_streams: Arc::new(AtomicUsize::new(0)),
+        });
+        let app = Router::new()
+            .route("/v1/chat/completions", post(chat))
+            .route("/api/chat", post(chat))
+            .route("/11434/api/chat", post(chat))
+            // Model listings answer readiness probes
+            .route(
+                "/v1/models",
+                get(|| async { Json(json!({"object": "list", "data": []})) }),
+            )
+            .route("/api/tags", get(|| async { Json(json!({"models": []})) }))
+            .route(
+                "/11434/api/tags",
+                get(|| async { Json(json!({"models": []})) }),
+            )
+            .with_state(Arc::clone(&state));
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                tracing::error!("Mock upstream stopped: {}", e);
+            }
+        });
+        Ok(Self { addr, state })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn openai_base_url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    pub fn ollama_base_url(&self) -> String {
+        format!("http://{}/11434", self.addr)
+    }
+
+    /// Requests received so far, in arrival order
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state
+            .calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Most streaming replies that were in flight at the same time
+    pub fn peak_streams(&self) -> usize {
+        self.state.peak_streams.load(Ordering::SeqCst)
+    }
+}
+
+/// Counts a streaming reply as in flight until its body is dropped
+struct StreamSlot(Arc<AtomicUsize>);
+
+impl StreamSlot {
+    fn new(state: &MockState) -> Self {
+        let active = state.active_streams.fetch_add(1, Ordering::SeqCst) + 1;
+        state.peak_streams.fetch_max(active, Ordering::SeqCst);
+        Self(Arc::clone(&state.active_streams))
+    }
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+async fn chat(
+    State(state): State<Arc<MockState>>,
+    uri: Uri,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let protocol = if uri.path().ends_with("/api/chat") {
+        Protocol::Ollama
+    } else {
+        Protocol::OpenAI
+    };
+    let streaming = body["stream"]
+        .as_bool()
+        .unwrap_or(protocol == Protocol::Ollama);
+    let model = body["model"].as_str().unwrap_or("mock").to_string();
+    let include_usage = body["stream_options"]["include_usage"]
+        .as_bool()
+        .unwrap_or(false);
+    let (script, errors) = if streaming {
+        (state.script.stream_for(&body), &state.stream_errors)
+    } else {
+        (&state.script.quick, &state.quick_errors)
+    };
+    let slot = streaming.then(|| StreamSlot::new(&state));
+    state
+        .calls
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(MockCall {
+            path: uri.path().to_string(),
+            headers,
+            body,
+        });
+    tokio::time::sleep(Duration::from_millis(script.latency_ms)).await;
+
+    if let Some(error) = &script.error {
+        let failed = errors.fetch_add(1, Ordering::SeqCst);
+        if error.times.is_none_or(|times| failed < times) {
+            return error_response(protocol, error);
+        }
+    }
+    if !streaming {
+        return Json(reply(protocol, &model, script)).into_response();
+    }
+    let events = stream_events(protocol, &model, script, include_usage).map(move |frame| {
+        let _slot = &slot;
+        frame
+    });
+    let content_type = match protocol {
+        Protocol::OpenAI => "text/event-stream",
+        Protocol::Ollama => "application/x-ndjson",
+    };
+    ([("content-type", content_type)], Body::from_stream(events)).into_response()
+}
+
+fn error_response(protocol: Protocol, error: &InjectedError) -> Response {
+    let status = StatusCode::from_u16(error.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = match protocol {
+        Protocol::OpenAI => json!({"error": {"message": error.message, "type": "mock_error"}}),
+        Protocol::Ollama => json!({"error": error.message}),
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Character count, standing in for a tokenizer
+fn count_tokens(text: &str) -> usize {
+    text.chars().count()
+}
+
+fn reply(protocol: Protocol, model: &str, script: &ReplyScript) -> Value {
+    let content = script.tokens.concat();
+    let completion_tokens = count_tokens(&content);
+    let mut reply = match protocol {
+        Protocol::OpenAI => json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "created": chrono::Utc::now().timestamp(),
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": content},
+                "finish_reason": script.finish_reason
+            }],
+            "usage": {
+                "prompt_tokens": 10,
+                "completion_tokens": completion_tokens,
+                "total_tokens": 10 + completion_tokens
+            }
+        }),
+        Protocol::Ollama => json!({
+            "model": model,
+            "created_at": chrono::Utc::now().to_rfc3339(),
+            "message": {"role": "assistant", "content": content},
+            "done": true,
+            "done_reason": script.finish_reason,
+            "prompt_eval_count": 10,
+            "eval_count": completion_tokens
+        }),
+    };
+    if !script.report_usage {
+        for field in ["usage", "prompt_eval_count", "eval_count"] {
+            reply.as_object_mut().map(|reply| reply.remove(field));
+        }
+    }
+    reply
+}
+
+/// One streamed event as written on the wire, including its line terminators
+fn token_event(protocol: Protocol, model: &str, token: &str) -> String {
+    match protocol {
+        Protocol::OpenAI => format!(
+            "data: {}\n\n",
+            json!({
+                "id": "chatcmpl-mock",
+                "object": "chat.completion.chunk",
+                "created": chrono::Utc::now().timestamp(),
+                "model": model,
+                "choices": [{"index": 0, "delta": {"content": token}, "finish_reason": null}]
+            })
+        ),
+        Protocol::Ollama => format!(
+            "{}\n",
+            json!({
+                "model": model,
+                "created_at": chrono::Utc::now().to_rfc3339(),
+                "message": {"role": "assistant", "content": token},
+                "done": false
+            })
+        ),
+    }
+}
+
+/// Tool calls in the shape each protocol streams them
+fn tool_call_events(protocol: Protocol, model: &str, calls: &[MockToolCall]) -> Vec<String> {
+    let mut events = Vec::new();
+    for (index, call) in calls.iter().enumerate() {
+        match protocol {
+            Protocol::OpenAI => {
+                let fragments = [
+                    json!({
+                        "index": index,
+                        "id": format!("call_{}", index + 1),
+                        "type": "function",
+                        "function": {"name": call.name, "arguments": ""}
+                    }),
+                    json!({
+                        "index": index,
+                        "function": {"arguments": call.arguments.to_string()}
+                    }),
+                ];
+                events.extend(fragments.into_iter().map(|fragment| {
+                    format!(
+                        "data: {}\n\n",
+                        json!({
+                            "id": "chatcmpl-mock",
+                            "object": "chat.completion.chunk",
+                            "created": chrono::Utc::now().timestamp(),
+                            "model": model,
+                            "choices": [{
+                                "index": 0,
+                                "delta": {"tool_calls": [fragment]},
+                                "finish_reason": null
+                            }]
+                        })
+                    )
+                }));
+            }
+            Protocol::Ollama => events.push(format!(
+                "{}\n",
+                json!({
+                    "model": model,
+                    "created_at": chrono::Utc::now().to_rfc3339(),
+                    "message": {
+                        "role": "assistant",
+                        "content": "",
+                        "tool_calls": [{
+                            "function": {"name": call.name, "arguments": call.arguments}
+                        }]
+                    },
+                    "done": false
+                })
+            )),
+        }
+    }
+    events
+}
+
+fn final_events(
+    protocol: Protocol,
+    model: &str,
+    script: &ReplyScript,
+    include_usage: bool,
+) -> Vec<String> {
+    let completion_tokens = count_tokens(&script.tokens.concat());
+    match protocol {
+        Protocol::OpenAI => {
+            let chunk = |choices: Value, usage: Value| {
+                format!(
+                    "data: {}\n\n",
+                    json!({
+                        "id": "chatcmpl-mock",
+                        "object": "chat.completion.chunk",
+                        "created": chrono::Utc::now().timestamp(),
+                        "model": model,
+                        "choices": choices,
+                        "usage": usage
+                    })
+                )
+            };
+            let mut events = vec![chunk(
+                json!([{"index": 0, "delta": {}, "finish_reason": script.finish_reason}]),
+                Value::Null,
+            )];
+            if include_usage && script.report_usage {
+                events.push(chunk(
+                    json!([]),
+                    json!({
+                        "prompt_tokens": 10,
+                        "completion_tokens": completion_tokens,
+                        "total_tokens": 10 + completion_tokens
+                    }),
+                ));
+            }
+            events.push("data: [DONE]\n\n".to_string());
+            events
+        }
+        Protocol::Ollama => {
+            let mut done = json!({
+                "model": model,
+                "created_at": chrono::Utc::now().to_rfc3339(),
+                "message": {"role": "assistant", "content": ""},
+                "done": true,
+                "done_reason": script.finish_reason,
+                "total_duration": 5_000_000u64,
+                "prompt_eval_duration": 1_000_000u64,
+                "eval_duration": 4_000_000u64
+            });
+            if script.report_usage {
+                done["prompt_eval_count"] = json!(10);
+                done["eval_count"] = json!(completion_tokens);
+            }
+            vec![format!("{}\n", done)]
+        }
+    }
+}
+
+fn malformed_event(protocol: Protocol) -> String {
+    match protocol {
+        Protocol::OpenAI => "data: {\"choices\":[{\"delta\":{\"content\n\n".to_string(),
+        Protocol::Ollama => "{\"message\":{\"content\n".to_string(),
+    }
+}
+
+/// Splits after the first byte of the first multi-byte character, if there is one
+fn split_inside_char(event: String) -> Vec<Bytes> {
+    let bytes = Bytes::from(event);
+    match bytes.iter().position(|b| !b.is_ascii()) {
+        Some(at) => vec![bytes.slice(..at + 1), bytes.slice(at + 1..)],
+        None => vec![bytes],
+    }
+}
+
+enum Frame {
+    /// Written after a pause, so it leaves in its own TCP segment
+    Data(Bytes, Duration),
+    Disconnect,
+    Stall,
+}
+
+fn stream_events(
+    protocol: Protocol,
+    model: &str,
+    script: &ReplyScript,
+    include_usage: bool,
+) -> impl futures::Stream<Item = std::io::Result<Bytes>> + Send + 'static {
+    let interval = Duration::from_millis(script.token_interval_ms);
+    // A short pause keeps split frames from being coalesced
+    let split_pause = Duration::from_millis(5);
+    let mut frames = Vec::new();
+    let mut push = |event: String, pause: Duration| {
+        if script.split_utf8 {
+            for (i, part) in split_inside_char(event).into_iter().enumerate() {
+                frames.push(Frame::Data(part, if i == 0 { pause } else { split_pause }));
+            }
+        } else {
+            frames.push(Frame::Data(Bytes::from(event), pause));
+        }
+    };
+
+    // The frame that ends the stream early, checked before each token and after the last
+    let cut_after = |i: usize| {
+        if script.disconnect_after == Some(i) {
+            Some(Frame::Disconnect)
+        } else if script.stall_after == Some(i) {
+            Some(Frame::Stall)
+        } else {
+            None
+        }
+    };
+    let mut cut = None;
+    for (i, token) in script.tokens.iter().enumerate() {
+        if script.malformed_after == Some(i) {
+            push(malformed_event(protocol), Duration::ZERO);
+        }
+        cut = cut_after(i);
+        if cut.is_some() {
+            break;
+        }
+        push(token_event(protocol, model, token), interval);
+    }
+    let sent = script.tokens.len();
+    if cut.is_none() && script.malformed_after == Some(sent) {
+        push(malformed_event(protocol), Duration::ZERO);
+    }
+    if cut.is_none() {
+        cut = cut_after(sent);
+    }
+    match cut {
+        Some(frame) => frames.push(frame),
+        None => {
+            let tool_calls = tool_call_events(protocol, model, &script.tool_calls);
+            for event in
+                tool_calls
+                    .into_iter()
+                    .chain(final_events(protocol, model, script, include_usage))
+            {
+                push(event, Duration::ZERO);
+            }
+        }
+    }
+
+    stream::iter(frames).then(|frame| async move {
+        match frame {
+            Frame::Data(bytes, pause) => {
+                if !pause.is_zero() {
+                    tokio::time::sleep(pause).await;
+                }
+                Ok(bytes)
+            }
+            Frame::Disconnect => {
+                // Let the frames before it reach the client first
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionReset,
+                    "mock upstream disconnected",
+                ))
+            }
+            Frame::Stall => std::future::pending().await,
+        }
+    })
+}
@@ -1,10 +1,170 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::fmt;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
-    pub content: String,
+    // Assistant messages that only carry tool calls send `"content": null`
+    #[serde(default, deserialize_with = "deserialize_null_as_default")]
+    pub content: MessageContent,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Either a plain string or an array of typed parts (text and images)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageUrl {
+    /// `https://...` or a `data:image/...;base64,...` URL
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl From<String> for MessageContent {
+    fn from(text: String) -> Self {
+        MessageContent::Text(text)
+    }
+}
+
+impl From<&str> for MessageContent {
+    fn from(text: &str) -> Self {
+        MessageContent::Text(text.to_string())
+    }
+}
+
+impl fmt::Display for MessageContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text())
+    }
+}
+
+impl MessageContent {
+    /// Text parts only, joined with newlines; images are dropped
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            MessageContent::Text(text) => Cow::Borrowed(text),
+            MessageContent::Parts(parts) => {
+                let texts: Vec<&str> = parts
+                    .iter()
+                    .filter_map(|part| match part {
+                        ContentPart::Text { text } => Some(text.as_str()),
+                        ContentPart::ImageUrl { .. } => None,
+                    })
+                    .collect();
+                match texts.as_slice() {
+                    [single] => Cow::Borrowed(single),
+                    _ => Cow::Owned(texts.join("\n")),
+                }
+            }
+        }
+    }
+
+    pub fn image_urls(&self) -> impl Iterator<Item = &str> {
+        let parts: &[ContentPart] = match self {
+            MessageContent::Text(_) => &[],
+            MessageContent::Parts(parts) => parts,
+        };
+        parts.iter().filter_map(|part| match part {
+            ContentPart::ImageUrl { image_url } => Some(image_url.url.as_str()),
+            ContentPart::Text { .. } => None,
+        })
+    }
+
+    pub fn has_images(&self) -> bool {
+        self.image_urls().next().is_some()
+    }
+
+    /// No non-whitespace text and no images
+    pub fn is_empty(&self) -> bool {
+        self.text().trim().is_empty() && !self.has_images()
+    }
+}
+
+fn deserialize_null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+// Tool/function calling (OpenAI-compatible)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCall {
+    pub name: String,
+    /// JSON-encoded arguments, as produced by the model
+    pub arguments: String,
+}
+
+/// Streamed fragment of a tool call; `arguments` arrive in pieces keyed by `index`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub call_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FunctionCallDelta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arguments: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,9 +182,71 @@ pub struct ChatCompletionRequest {
     // Custom parameter to disable quick response for comparison
     #[serde(default)]
     pub disable_quick_response: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    /// `"none"`, `"auto"`, `"required"` or `{"type": "function", "function": {"name": ...}}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    /// `["text", "audio"]` asks for synthesized speech alongside the text stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioOutputOptions>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamOptions {
+    /// Send a final chunk with token usage before `[DONE]`
+    #[serde(default)]
+    pub include_usage: bool,
+}
+
+/// Audio formats accepted by OpenAI-compatible `/audio/speech` backends
+pub const AUDIO_FORMATS: &[&str] = &["mp3", "opus", "aac", "flac", "wav", "pcm"];
+
+/// Voice and format overrides for spoken output; unset fields use the TTS_* defaults
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AudioOutputOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+}
+
+impl Default for ChatCompletionRequest {
+    fn default() -> Self {
+        Self {
+            model: String::new(),
+            messages: Vec::new(),
+            max_tokens: None,
+            temperature: default_temperature(),
+            stream: default_stream(),
+            stop: None,
+            disable_quick_response: false,
+            tools: None,
+            tool_choice: None,
+            modalities: None,
+            audio: None,
+            stream_options: None,
+        }
+    }
 }
 
 impl ChatCompletionRequest {
+    pub fn wants_usage(&self) -> bool {
+        self.stream_options
+            .as_ref()
+            .is_some_and(|options| options.include_usage)
+    }
+
+    pub fn wants_audio(&self) -> bool {
+        self.modalities
+            .as_ref()
+            .is_some_and(|modalities| modalities.iter().any(|m| m == "audio"))
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         if self.model.trim().is_empty() {
             return Err("Model name cannot be empty".to_string());
@@ -38,11 +260,36 @@ impl ChatCompletionRequest {
             if message.role.trim().is_empty() {
                 return Err(format!("Message {i} role cannot be empty"));
             }
-            if message.content.trim().is_empty() {
+            if !["system", "user", "assistant", "tool"].contains(&message.role.as_str()) {
+                return Err(format!("Message {i} has invalid role: {}", message.role));
+            }
+            // An assistant turn that only calls tools has no text content
+            let has_tool_calls = message
+                .tool_calls
+                .as_ref()
+                .is_some_and(|calls| !calls.is_empty());
+            if message.content.is_empty() && !(message.role == "assistant" && has_tool_calls) {
                 return Err(format!("Message {i} content cannot be empty"));
             }
-            if !["system", "user", "assistant"].contains(&message.role.as_str()) {
-                return Err(format!("Message {i} has invalid role: {}", message.role));
+            if message.content.has_images() && message.role != "user" {
+                return Err(format!("Message {i} image content is only supported for user messages"));
+            }
+            if message.content.image_urls().any(|url| url.trim().is_empty()) {
+                return Err(format!("Message {i} image_url cannot be empty"));
+            }
+            if message.role == "tool" && message.tool_call_id.as_deref().unwrap_or("").trim().is_empty() {
+                return Err(format!("Message {i} with role tool requires tool_call_id"));
+            }
+        }
+
+        if let Some(tools) = &self.tools {
+            for (i, tool) in tools.iter().enumerate() {
+                if tool.tool_type != "function" {
+                    return Err(format!("Tool {i} has unsupported type: {}", tool.tool_type));
+                }
+                if tool.function.name.trim().is_empty() {
+                    return Err(format!("Tool {i} function name cannot be empty"));
+                }
             }
         }
 
@@ -56,6 +303,17 @@ impl ChatCompletionRequest {
             return Err("Temperature must be between 0.0 and 2.0".to_string());
         }
 
+        if let Some(modalities) = &self.modalities {
+            if let Some(other) = modalities.iter().find(|m| !["text", "audio"].contains(&m.as_str())) {
+                return Err(format!("Unsupported modality: {other}"));
+            }
+        }
+        if let Some(format) = self.audio.as_ref().and_then(|audio| audio.format.as_deref()) {
+            if !AUDIO_FORMATS.contains(&format) {
+                return Err(format!("Unsupported audio format: {format}"));
+            }
+        }
+
         Ok(())
     }
 }
@@ -91,21 +349,40 @@ pub struct ChoiceDelta {
     pub finish_reason: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MessageDelta {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
 }
 
+impl Usage {
+    pub fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    pub fn add(&self, other: &Usage) -> Usage {
+        Usage::new(
+            self.prompt_tokens + other.prompt_tokens,
+            self.completion_tokens + other.completion_tokens,
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatCompletionResponse {
     pub id: String,
@@ -123,13 +400,31 @@ pub struct ChatCompletionChunk {
     pub created: i64,
     pub model: String,
     pub choices: Vec<ChoiceDelta>,
+    /// Only set on the final chunk when `stream_options.include_usage` is requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+// Model listing returned by GET /v1/models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub object: String,
+    pub created: i64,
+    pub owned_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelList {
+    pub object: String,
+    pub data: Vec<ModelInfo>,
 }
 
 // OpenAI API request structures for external calls
 #[derive(Debug, Clone, Serialize)]
 pub struct OpenAIRequest {
     pub model: String,
-    pub messages: Vec<HashMap<String, String>>,
+    pub messages: Vec<Message>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -146,11 +441,19 @@ pub struct OpenAIRequest {
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub extra_body: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct OpenAIResponse {
     pub choices: Vec<OpenAIChoice>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -166,27 +469,145 @@ pub struct OpenAIMessage {
     pub role: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
 }
 
-// Ollama API response structures  
+/// Borrowed view of one streamed upstream chunk. Only the first choice is kept, and
+/// strings point into the event data unless they contain escapes.
+#[derive(Debug, Deserialize)]
+pub struct OpenAIStreamChunk<'a> {
+    #[serde(borrow, default, rename = "choices", deserialize_with = "first_choice")]
+    pub choice: Option<OpenAIStreamChoice<'a>>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIStreamChoice<'a> {
+    #[serde(borrow, default)]
+    pub delta: Option<StreamDelta<'a>>,
+    #[serde(borrow, default)]
+    pub message: Option<StreamDelta<'a>>,
+    #[serde(borrow, default, deserialize_with = "borrowed_str")]
+    pub finish_reason: Option<Cow<'a, str>>,
+}
+
+/// A chunk delta as read from upstreams and written to clients, serialized like
+/// [`MessageDelta`]
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct StreamDelta<'a> {
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_str",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub role: Option<Cow<'a, str>>,
+    #[serde(
+        borrow,
+        default,
+        deserialize_with = "borrowed_str",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content: Option<Cow<'a, str>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+// `Option<Cow<str>>` never borrows on its own, only a `Cow` field marked `borrow` does
+fn borrowed_str<'de, D>(deserializer: D) -> Result<Option<Cow<'de, str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Borrowed<'a>(#[serde(borrow)] Cow<'a, str>);
+    Ok(Option::<Borrowed>::deserialize(deserializer)?.map(|borrowed| borrowed.0))
+}
+
+/// Keeps the first element of `choices` without collecting the array
+fn first_choice<'de, D>(deserializer: D) -> Result<Option<OpenAIStreamChoice<'de>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct FirstChoice;
+
+    impl<'de> serde::de::Visitor<'de> for FirstChoice {
+        type Value = Option<OpenAIStreamChoice<'de>>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an array of choices")
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let first = seq.next_element()?;
+            while seq.next_element::<serde::de::IgnoredAny>()?.is_some() {}
+            Ok(first)
+        }
+    }
+
+    deserializer.deserialize_any(FirstChoice)
+}
+
+// Ollama API response structures; strings borrow from the line unless they contain escapes
 #[derive(Debug, Clone, Deserialize)]
-pub struct OllamaResponse {
+pub struct OllamaResponse<'a> {
     // Fields required for deserialization but not used in logic
     #[serde(rename = "model")]
-    _model: String,
+    _model: serde::de::IgnoredAny,
     #[serde(rename = "created_at")]
-    _created_at: String,
-    pub message: OllamaMessage,
-    #[serde(rename = "done")]
-    _done: bool,
+    _created_at: serde::de::IgnoredAny,
+    #[serde(borrow, default)]
+    pub message: OllamaMessage<'a>,
+    pub done: bool,
+    /// Why generation stopped (`stop`, `length`, ...), present on the final (`done`) line
+    #[serde(borrow, default, deserialize_with = "borrowed_str")]
+    pub done_reason: Option<Cow<'a, str>>,
+    /// Token counts, present on the final (`done`) line
+    #[serde(default)]
+    pub prompt_eval_count: Option<u32>,
+    #[serde(default)]
+    pub eval_count: Option<u32>,
+    /// Timings in nanoseconds, present on the final (`done`) line
+    #[serde(default)]
+    pub total_duration: Option<u64>,
+    #[serde(default)]
+    pub load_duration: Option<u64>,
+    #[serde(default)]
+    pub prompt_eval_duration: Option<u64>,
+    #[serde(default)]
+    pub eval_duration: Option<u64>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct OllamaMessage {
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OllamaMessage<'a> {
     // Role field required for deserialization but not used  
     #[serde(rename = "role")]
-    _role: String,
-    pub content: String,
+    _role: serde::de::IgnoredAny,
+    #[serde(borrow)]
+    pub content: Cow<'a, str>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<OllamaToolCall>>,
+}
+
+// Ollama returns complete tool calls with arguments as a JSON object
+#[derive(Debug, Clone, Deserialize)]
+pub struct OllamaToolCall {
+    pub function: OllamaFunctionCall,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OllamaFunctionCall {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
 }
 
 // Quick response categories for voice assistant
@@ -199,6 +620,13 @@ pub enum RequestCategory {
 }
 
 impl RequestCategory {
+    pub const ALL: [RequestCategory; 4] = [
+        RequestCategory::Greeting,
+        RequestCategory::Question,
+        RequestCategory::Request,
+        RequestCategory::Thinking,
+    ];
+
     pub fn get_responses(&self) -> &'static [&'static str] {
         match self {
             RequestCategory::Greeting => &["你好！", "嗨！", "您好，", "我在这里，"],
@@ -211,7 +639,7 @@ impl RequestCategory {
 
 impl Message {
     pub fn categorize(&self) -> RequestCategory {
-        let content = self.content.to_lowercase();
+        let content = self.content.text().to_lowercase();
 
         // Greeting patterns
         if content.contains("你好")
@@ -0,0 +1,101 @@
+use crate::{chunk::MAX_CHUNK_BYTES, errors::LoroError};
+use serde::de::DeserializeOwned;
+
+/// Largest line accepted from an upstream, matching the outgoing chunk limit
+pub const MAX_LINE_BYTES: usize = MAX_CHUNK_BYTES;
+
+/// Incremental decoder for newline-delimited JSON, as streamed by Ollama.
+///
+/// Bytes are fed as they arrive, split anywhere; each complete line is one JSON value.
+/// Blank lines are skipped and a trailing `\r` is ignored. A line that is not valid
+/// JSON is returned as an error without affecting the lines after it.
+pub struct NdjsonDecoder {
+    /// Bytes of the current, incomplete line
+    line: Vec<u8>,
+    max_bytes: usize,
+}
+
+impl Default for NdjsonDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NdjsonDecoder {
+    pub fn new() -> Self {
+        Self::with_limit(MAX_LINE_BYTES)
+    }
+
+    /// A decoder that fails once a line exceeds `max_bytes`
+    pub fn with_limit(max_bytes: usize) -> Self {
+        Self {
+            line: Vec::new(),
+            max_bytes,
+        }
+    }
+
+    /// Decodes the next piece of the stream and returns the values of the lines it completes
+    pub fn feed<T: DeserializeOwned>(
+        &mut self,
+        chunk: &[u8],
+    ) -> Result<Vec<serde_json::Result<T>>, LoroError> {
+        let mut values = Vec::new();
+        self.feed_with(chunk, |line| values.push(serde_json::from_slice(line)))?;
+        Ok(values)
+    }
+
+    /// Like [`feed`](Self::feed), handing each completed line to `on_line` without
+    /// copying it out of the chunk or the decoder, so values can borrow from it
+    pub fn feed_with<F>(&mut self, mut chunk: &[u8], mut on_line: F) -> Result<(), LoroError>
+    where
+        F: FnMut(&[u8]),
+    {
+        while let Some(end) = memchr::memchr(b'\n', chunk) {
+            self.check_line_len(end)?;
+            if self.line.is_empty() {
+                Self::emit(&chunk[..end], &mut on_line);
+            } else {
+                self.line.extend_from_slice(&chunk[..end]);
+                Self::emit(&self.line, &mut on_line);
+                self.line.clear();
+            }
+            chunk = &chunk[end + 1..];
+        }
+        self.check_line_len(chunk.len())?;
+        self.line.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    /// Parses a final line left without a line break once the stream has ended
+    pub fn finish<T: DeserializeOwned>(&mut self) -> Option<serde_json::Result<T>> {
+        let mut value = None;
+        self.finish_with(|line| value = Some(serde_json::from_slice(line)));
+        value
+    }
+
+    /// Like [`finish`](Self::finish), handing the final line to `on_line`
+    pub fn finish_with<F>(&mut self, mut on_line: F)
+    where
+        F: FnMut(&[u8]),
+    {
+        let line = std::mem::take(&mut self.line);
+        Self::emit(&line, &mut on_line);
+    }
+
+    fn check_line_len(&self, more: usize) -> Result<(), LoroError> {
+        if self.line.len() + more > self.max_bytes {
+            return Err(LoroError::StreamProcessing(format!(
+                "JSON line exceeds {} bytes",
+                self.max_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    fn emit<F: FnMut(&[u8])>(line: &[u8], on_line: &mut F) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if !line.iter().all(u8::is_ascii_whitespace) {
+            on_line(line);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::config::ModelConfig;
+use reqwest::Client;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamStatus {
+    pub model: String,
+    pub endpoint: String,
+    pub healthy: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessReport {
+    pub small_model: UpstreamStatus,
+    pub large_model: UpstreamStatus,
+}
+
+impl ReadinessReport {
+    /// The small model has a local fallback, so only the large model gates readiness.
+    pub fn is_ready(&self) -> bool {
+        self.large_model.healthy
+    }
+}
+
+/// Ollama exposes `/api/tags`, OpenAI-compatible providers expose `/models`.
+pub fn probe_endpoint(model: &ModelConfig) -> String {
+    if model.is_ollama() {
+        format!("{}/api/tags", model.base_url)
+    } else {
+        format!("{}/models", model.base_url)
+    }
+}
+
+pub async fn probe_upstream(
+    client: &Client,
+    model: &ModelConfig,
+    probe_timeout: Duration,
+) -> UpstreamStatus {
+    let endpoint = probe_endpoint(model);
+    let mut request_builder = client.get(&endpoint);
+    if model.api_key.expose_secret() != "none" {
+        request_builder = request_builder.header(
+            "Authorization",
+            format!("Bearer {}", model.api_key.expose_secret()),
+        );
+    }
+
+    let start = Instant::now();
+    let result = timeout(probe_timeout, request_builder.send()).await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let (healthy, status_code, error) = match result {
+        Ok(Ok(response)) => {
+            let status = response.status();
+            let error = (!status.is_success()).then(|| format!("Upstream returned {}", status));
+            (status.is_success(), Some(status.as_u16()), error)
+        }
+        Ok(Err(e)) => (false, None, Some(format!("Request failed: {}", e))),
+        Err(_) => (
+            false,
+            None,
+            Some(format!("Probe timeout after {}s", probe_timeout.as_secs())),
+        ),
+    };
+
+    UpstreamStatus {
+        model: model.model_name.clone(),
+        endpoint,
+        healthy,
+        latency_ms,
+        status_code,
+        error,
+        checked_at: chrono::Utc::now().timestamp(),
+    }
+}
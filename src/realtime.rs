@@ -0,0 +1,528 @@
+use crate::errors::ErrorBody;
+use crate::models::{
+    AudioOutputOptions, ChatCompletionChunk, ChatCompletionRequest, Message, MessageContent, Tool,
+    ToolCallDelta,
+};
+use crate::routing::QUICK_MODEL_ALIAS;
+use crate::service::{LoroService, RequestContext};
+use crate::speech::{AudioSegment, ReplyEvent, ReplyStream};
+use axum::extract::ws::{Message as WsMessage, WebSocket};
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Per-session request settings, applied to every turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSettings {
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    pub temperature: f32,
+    pub disable_quick_response: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio: Option<AudioOutputOptions>,
+}
+
+impl Default for SessionSettings {
+    fn default() -> Self {
+        let defaults = ChatCompletionRequest::default();
+        Self {
+            model: QUICK_MODEL_ALIAS.to_string(),
+            max_tokens: None,
+            temperature: defaults.temperature,
+            disable_quick_response: false,
+            tools: None,
+            tool_choice: None,
+            modalities: None,
+            audio: None,
+        }
+    }
+}
+
+/// Partial update sent with `session.update`; omitted fields keep their value.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionUpdate {
+    pub model: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub disable_quick_response: Option<bool>,
+    pub tools: Option<Vec<Tool>>,
+    pub tool_choice: Option<serde_json::Value>,
+    /// `["text", "audio"]` adds synthesized speech to every turn
+    pub modalities: Option<Vec<String>>,
+    pub audio: Option<AudioOutputOptions>,
+    /// Replaces the conversation history, e.g. to seed a system prompt
+    pub messages: Option<Vec<Message>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientEvent {
+    #[serde(rename = "session.update")]
+    SessionUpdate { session: SessionUpdate },
+    /// A user utterance; interrupts the turn in progress, if any
+    #[serde(rename = "turn")]
+    Turn { content: MessageContent },
+    /// Stops the turn in progress
+    #[serde(rename = "cancel")]
+    Cancel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ServerEvent {
+    #[serde(rename = "session.created")]
+    SessionCreated {
+        session_id: String,
+        session: SessionSettings,
+    },
+    #[serde(rename = "session.updated")]
+    SessionUpdated { session: SessionSettings },
+    #[serde(rename = "turn.started")]
+    TurnStarted { turn_id: String },
+    /// The small model's acknowledgement, sent before any large model output
+    #[serde(rename = "response.quick")]
+    ResponseQuick { turn_id: String, content: String },
+    #[serde(rename = "response.delta")]
+    ResponseDelta {
+        turn_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tool_calls: Option<Vec<ToolCallDelta>>,
+    },
+    /// Metadata of the binary frame that immediately follows with the encoded audio
+    #[serde(rename = "response.audio")]
+    ResponseAudio {
+        turn_id: String,
+        #[serde(flatten)]
+        segment: AudioSegment,
+    },
+    #[serde(rename = "turn.done")]
+    TurnDone {
+        turn_id: String,
+        content: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        finish_reason: Option<String>,
+    },
+    /// `content` is what had been streamed before the turn was stopped
+    #[serde(rename = "turn.cancelled")]
+    TurnCancelled { turn_id: String, content: String },
+    #[serde(rename = "error")]
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        turn_id: Option<String>,
+        error: ErrorBody,
+    },
+}
+
+impl ServerEvent {
+    fn error(
+        turn_id: Option<String>,
+        message: impl Into<String>,
+        error_type: &str,
+        code: &str,
+    ) -> Self {
+        ServerEvent::Error {
+            turn_id,
+            error: ErrorBody::new(message, error_type, code),
+        }
+    }
+}
+
+struct ActiveTurn {
+    id: String,
+    /// Resolves to the reply stream and whether it starts with a quick acknowledgement
+    opening: Option<BoxFuture<'static, anyhow::Result<(ReplyStream, bool)>>>,
+    stream: ReplyStream,
+    /// Set until the quick acknowledgement has been forwarded
+    expects_quick: bool,
+    content: String,
+    finish_reason: Option<String>,
+}
+
+/// State of one WebSocket connection: settings, history and the turn being streamed.
+pub struct RealtimeSession {
+    service: Arc<LoroService>,
+    /// Context of the upgrade request, applied to every turn
+    ctx: RequestContext,
+    id: String,
+    settings: SessionSettings,
+    history: Vec<Message>,
+    turn: Option<ActiveTurn>,
+}
+
+impl RealtimeSession {
+    pub fn new(service: Arc<LoroService>, ctx: RequestContext) -> Self {
+        Self {
+            service,
+            ctx,
+            id: format!("sess-{}", Uuid::new_v4()),
+            settings: SessionSettings::default(),
+            history: Vec::new(),
+            turn: None,
+        }
+    }
+
+    pub fn history(&self) -> &[Message] {
+        &self.history
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.turn.is_none()
+    }
+
+    pub fn created_event(&self) -> ServerEvent {
+        ServerEvent::SessionCreated {
+            session_id: self.id.clone(),
+            session: self.settings.clone(),
+        }
+    }
+
+    /// Applies a client event and returns the events to send back
+    pub fn handle_event(&mut self, event: ClientEvent) -> Vec<ServerEvent> {
+        match event {
+            ClientEvent::SessionUpdate { session } => self.update_settings(session),
+            ClientEvent::Turn { content } => self.start_turn(content),
+            ClientEvent::Cancel => self.cancel_turn().into_iter().collect(),
+        }
+    }
+
+    fn update_settings(&mut self, update: SessionUpdate) -> Vec<ServerEvent> {
+        let mut settings = self.settings.clone();
+        if let Some(model) = update.model {
+            settings.model = model;
+        }
+        if let Some(max_tokens) = update.max_tokens {
+            settings.max_tokens = Some(max_tokens);
+        }
+        if let Some(temperature) = update.temperature {
+            settings.temperature = temperature;
+        }
+        if let Some(disable_quick_response) = update.disable_quick_response {
+            settings.disable_quick_response = disable_quick_response;
+        }
+        if update.tools.is_some() {
+            settings.tools = update.tools;
+        }
+        if update.tool_choice.is_some() {
+            settings.tool_choice = update.tool_choice;
+        }
+        if update.modalities.is_some() {
+            settings.modalities = update.modalities;
+        }
+        if update.audio.is_some() {
+            settings.audio = update.audio;
+        }
+
+        // Validate against a representative turn so bad settings fail now, not per turn
+        let mut messages = update
+            .messages
+            .clone()
+            .unwrap_or_else(|| self.history.clone());
+        messages.push(Message {
+            role: "user".to_string(),
+            content: "ping".into(),
+            ..Default::default()
+        });
+        let probe = build_request(&settings, messages);
+        if let Err(e) = probe.validate() {
+            return vec![ServerEvent::error(
+                None,
+                e,
+                "invalid_request_error",
+                "validation_failed",
+            )];
+        }
+        if probe.wants_audio() && !self.service.speech_enabled() {
+            return vec![ServerEvent::error(
+                None,
+                "Audio output is not configured (set TTS_BASE_URL)",
+                "service_unavailable",
+                "speech_not_configured",
+            )];
+        }
+
+        self.settings = settings;
+        if let Some(messages) = update.messages {
+            self.history = messages;
+        }
+        vec![ServerEvent::SessionUpdated {
+            session: self.settings.clone(),
+        }]
+    }
+
+    fn start_turn(&mut self, content: MessageContent) -> Vec<ServerEvent> {
+        let mut events: Vec<ServerEvent> = self.cancel_turn().into_iter().collect();
+        // Each turn is its own request for logs, upstream calls and chunk ids
+        let request_id = Uuid::new_v4().to_string();
+        let turn_id = format!("turn-{}", request_id);
+
+        if self.service.is_draining() {
+            events.push(ServerEvent::error(
+                Some(turn_id),
+                "Server is shutting down",
+                "service_unavailable",
+                "draining",
+            ));
+            return events;
+        }
+
+        let mut messages = self.history.clone();
+        messages.push(Message {
+            role: "user".to_string(),
+            content,
+            ..Default::default()
+        });
+        let request = build_request(&self.settings, messages.clone());
+        if let Err(e) = request.validate() {
+            events.push(ServerEvent::error(
+                Some(turn_id),
+                e,
+                "invalid_request_error",
+                "validation_failed",
+            ));
+            return events;
+        }
+
+        let service = Arc::clone(&self.service);
+        // Resolve the stream lazily so the quick model call never blocks the socket loop
+        let ctx = self.ctx.with_request_id(request_id);
+        let opening = async move { service.reply_stream(request, &ctx).await }.boxed();
+
+        self.history = messages;
+        self.turn = Some(ActiveTurn {
+            id: turn_id.clone(),
+            opening: Some(opening),
+            stream: stream::empty().boxed(),
+            expects_quick: false,
+            content: String::new(),
+            finish_reason: None,
+        });
+        debug!("Realtime session {} started {}", self.id, turn_id);
+        events.push(ServerEvent::TurnStarted { turn_id });
+        events
+    }
+
+    /// Drops the upstream stream and keeps what was already streamed in the history
+    fn cancel_turn(&mut self) -> Option<ServerEvent> {
+        let turn = self.turn.take()?;
+        debug!("Realtime session {} cancelled {}", self.id, turn.id);
+        self.push_assistant(&turn.content);
+        Some(ServerEvent::TurnCancelled {
+            turn_id: turn.id,
+            content: turn.content,
+        })
+    }
+
+    fn push_assistant(&mut self, content: &str) {
+        if !content.is_empty() {
+            self.history.push(Message {
+                role: "assistant".to_string(),
+                content: content.into(),
+                ..Default::default()
+            });
+        }
+    }
+
+    /// Waits for the next output of the turn in progress; pending forever when idle
+    pub async fn next_event(&mut self) -> Option<ServerEvent> {
+        let Some(turn) = self.turn.as_mut() else {
+            return std::future::pending().await;
+        };
+
+        if let Some(opening) = turn.opening.as_mut() {
+            let opened = opening.await;
+            turn.opening = None;
+            match opened {
+                Ok((stream, quick_first)) => {
+                    turn.stream = stream;
+                    turn.expects_quick = quick_first;
+                }
+                Err(e) => return self.fail_turn(e),
+            }
+        }
+
+        loop {
+            match turn.stream.next().await {
+                Some(Ok(ReplyEvent::Audio(segment))) => {
+                    return Some(ServerEvent::ResponseAudio {
+                        turn_id: turn.id.clone(),
+                        segment,
+                    });
+                }
+                // The turn keeps going so a spoken apology can still be delivered
+                Some(Ok(ReplyEvent::Error(error))) => {
+                    warn!(
+                        "Realtime turn {} reported an error: {}",
+                        turn.id, error.message
+                    );
+                    return Some(ServerEvent::Error {
+                        turn_id: Some(turn.id.clone()),
+                        error,
+                    });
+                }
+                Some(Ok(ReplyEvent::Chunk(payload))) if payload == "[DONE]" => break,
+                Some(Ok(ReplyEvent::Chunk(payload))) => {
+                    let chunk: ChatCompletionChunk = match serde_json::from_str(&payload) {
+                        Ok(chunk) => chunk,
+                        Err(e) => {
+                            warn!("Skipping unparsable chunk in realtime session: {}", e);
+                            continue;
+                        }
+                    };
+                    let Some(choice) = chunk.choices.into_iter().next() else {
+                        continue;
+                    };
+                    if choice.finish_reason.is_some() {
+                        turn.finish_reason = choice.finish_reason;
+                    }
+                    let content = choice.delta.content.filter(|c| !c.is_empty());
+                    if let Some(text) = &content {
+                        turn.content.push_str(text);
+                    }
+
+                    if std::mem::take(&mut turn.expects_quick) {
+                        return Some(ServerEvent::ResponseQuick {
+                            turn_id: turn.id.clone(),
+                            content: content.unwrap_or_default(),
+                        });
+                    }
+                    if content.is_none() && choice.delta.tool_calls.is_none() {
+                        continue;
+                    }
+                    return Some(ServerEvent::ResponseDelta {
+                        turn_id: turn.id.clone(),
+                        content,
+                        tool_calls: choice.delta.tool_calls,
+                    });
+                }
+                Some(Err(e)) => return self.fail_turn(e),
+                None => break,
+            }
+        }
+
+        let turn = self.turn.take()?;
+        self.push_assistant(&turn.content);
+        Some(ServerEvent::TurnDone {
+            turn_id: turn.id,
+            content: turn.content,
+            finish_reason: turn.finish_reason,
+        })
+    }
+
+    /// Ends the turn in progress with `e`, keeping what was already streamed
+    fn fail_turn(&mut self, e: anyhow::Error) -> Option<ServerEvent> {
+        let turn = self.turn.take()?;
+        warn!("Realtime turn {} failed: {}", turn.id, e);
+        self.push_assistant(&turn.content);
+        Some(ServerEvent::Error {
+            turn_id: Some(turn.id),
+            error: self.service.error_body(&e),
+        })
+    }
+}
+
+fn build_request(settings: &SessionSettings, messages: Vec<Message>) -> ChatCompletionRequest {
+    ChatCompletionRequest {
+        model: settings.model.clone(),
+        messages,
+        max_tokens: settings.max_tokens,
+        temperature: settings.temperature,
+        disable_quick_response: settings.disable_quick_response,
+        tools: settings.tools.clone(),
+        tool_choice: settings.tool_choice.clone(),
+        modalities: settings.modalities.clone(),
+        audio: settings.audio.clone(),
+        ..Default::default()
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, event: &ServerEvent) -> bool {
+    let sent = match serde_json::to_string(event) {
+        Ok(text) => socket.send(WsMessage::Text(text)).await.is_ok(),
+        Err(e) => {
+            warn!("Failed to serialize realtime event: {}", e);
+            true
+        }
+    };
+    match event {
+        ServerEvent::ResponseAudio { segment, .. } if sent && segment.error.is_none() => socket
+            .send(WsMessage::Binary(segment.audio.to_vec()))
+            .await
+            .is_ok(),
+        _ => sent,
+    }
+}
+
+/// Runs one realtime connection until the client leaves, or the server drains
+/// and no turn is in progress.
+pub async fn handle_socket(mut socket: WebSocket, service: Arc<LoroService>, ctx: RequestContext) {
+    let drain = service.drain_state();
+    let mut session = RealtimeSession::new(service, ctx);
+    info!(
+        "Realtime session {} opened (request {})",
+        session.id, session.ctx.request_id
+    );
+
+    if !send_event(&mut socket, &session.created_event()).await {
+        return;
+    }
+
+    let mut draining = drain.is_draining();
+    loop {
+        if draining && session.is_idle() {
+            let _ = socket.send(WsMessage::Close(None)).await;
+            break;
+        }
+
+        let events = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(WsMessage::Text(text))) => match serde_json::from_str::<ClientEvent>(&text) {
+                    Ok(event) => session.handle_event(event),
+                    Err(e) => vec![ServerEvent::error(
+                        None,
+                        format!("Invalid client event: {}", e),
+                        "invalid_request_error",
+                        "invalid_event",
+                    )],
+                },
+                Some(Ok(WsMessage::Binary(_))) => vec![ServerEvent::error(
+                    None,
+                    "Binary frames are not supported",
+                    "invalid_request_error",
+                    "invalid_event",
+                )],
+                Some(Ok(WsMessage::Close(_))) | None => break,
+                Some(Ok(_)) => Vec::new(),
+                Some(Err(e)) => {
+                    debug!("Realtime socket error: {}", e);
+                    break;
+                }
+            },
+            event = session.next_event() => event.into_iter().collect(),
+            _ = drain.draining(), if !draining => {
+                draining = true;
+                Vec::new()
+            }
+        };
+
+        for event in &events {
+            if !send_event(&mut socket, event).await {
+                info!("Realtime session {} closed by peer", session.id);
+                return;
+            }
+        }
+    }
+
+    info!("Realtime session {} closed", session.id);
+}
@@ -0,0 +1,154 @@
+use crate::timing::{timed_chat, ChatTiming};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A request captured in the conversation audit log (`AUDIT_LOG_PATH`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedRequest {
+    pub timestamp: DateTime<Utc>,
+    pub model: String,
+    pub mode: String,
+    pub messages: Vec<RecordedMessage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// How replayed requests choose between quick and direct responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// As recorded: quick requests stay quick, everything else is sent direct
+    Original,
+    Quick,
+    Direct,
+}
+
+impl ReplayMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "original" => Some(Self::Original),
+            "quick" => Some(Self::Quick),
+            "direct" => Some(Self::Direct),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Original => "original",
+            Self::Quick => "quick",
+            Self::Direct => "direct",
+        }
+    }
+}
+
+impl RecordedRequest {
+    /// Request body for `mode`, and whether it expects a quick acknowledgement
+    pub fn body(&self, mode: ReplayMode) -> (Value, bool) {
+        let quick = match mode {
+            ReplayMode::Original => self.mode == "quick",
+            ReplayMode::Quick => true,
+            ReplayMode::Direct => false,
+        };
+        let messages: Vec<Value> = self
+            .messages
+            .iter()
+            .map(|message| json!({"role": message.role, "content": message.content}))
+            .collect();
+        let body = json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": true,
+            "disable_quick_response": !quick
+        });
+        (body, quick)
+    }
+}
+
+/// Parses audit log lines, ordered by arrival. Blank lines are skipped.
+pub fn load_requests(jsonl: &str) -> Result<Vec<RecordedRequest>> {
+    let mut requests = jsonl
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<RecordedRequest>(line)
+                .with_context(|| format!("Invalid audit record on line {}", i + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // Records are written when replies finish, so file order is completion order
+    requests.sort_by_key(|request| request.timestamp);
+    Ok(requests)
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    /// Divides the recorded gaps between requests; 2.0 replays twice as fast
+    pub speed: f64,
+    pub mode: ReplayMode,
+}
+
+/// Delay of each request after the first, scaled by `speed`
+pub fn schedule(requests: &[RecordedRequest], speed: f64) -> Vec<Duration> {
+    let Some(first) = requests.first() else {
+        return Vec::new();
+    };
+    requests
+        .iter()
+        .map(|request| {
+            let gap = (request.timestamp - first.timestamp)
+                .to_std()
+                .unwrap_or_default();
+            gap.div_f64(speed)
+        })
+        .collect()
+}
+
+/// Sends every request at its scheduled time, overlapping like the original traffic,
+/// and returns the timings in request order
+pub async fn replay(
+    client: &Client,
+    requests: &[RecordedRequest],
+    options: &ReplayOptions,
+) -> Vec<ChatTiming> {
+    let start = Instant::now();
+    let tasks: Vec<_> = requests
+        .iter()
+        .zip(schedule(requests, options.speed))
+        .map(|(request, delay)| {
+            let client = client.clone();
+            let options = options.clone();
+            let (body, quick) = request.body(options.mode);
+            tokio::spawn(async move {
+                tokio::time::sleep_until(start + delay).await;
+                timed_chat(
+                    &client,
+                    &options.base_url,
+                    options.api_key.as_deref(),
+                    &body,
+                    quick,
+                )
+                .await
+            })
+        })
+        .collect();
+
+    let mut timings = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        timings.push(task.await.unwrap_or_else(|e| ChatTiming {
+            error: Some(e.to_string()),
+            ..Default::default()
+        }));
+    }
+    timings
+}
@@ -0,0 +1,145 @@
+use crate::config::{Config, ModelConfig, RouteConfig};
+use serde::{Deserialize, Serialize};
+
+/// Virtual model that answers with a quick acknowledgement before the large model.
+pub const QUICK_MODEL_ALIAS: &str = "loro-quick";
+/// Virtual model that streams the large model directly.
+pub const DIRECT_MODEL_ALIAS: &str = "loro-direct";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseMode {
+    Quick,
+    Direct,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelRoute {
+    pub name: String,
+    /// `None` leaves the choice to the request's `disable_quick_response`
+    pub mode: Option<ResponseMode>,
+    pub small_model: ModelConfig,
+    pub large_model: ModelConfig,
+    /// Overrides for the built-in system prompts
+    pub quick_system_prompt: Option<String>,
+    pub large_system_prompt: Option<String>,
+    /// Used when the request does not set `max_tokens`
+    pub max_tokens: Option<u32>,
+}
+
+impl ModelRoute {
+    fn from_route_config(route: &RouteConfig) -> Self {
+        Self {
+            name: route.name.clone(),
+            mode: route.mode,
+            small_model: route.small_model.clone(),
+            large_model: route.large_model.clone(),
+            quick_system_prompt: route.quick_system_prompt.clone(),
+            large_system_prompt: route.large_system_prompt.clone(),
+            max_tokens: route.max_tokens,
+        }
+    }
+
+    fn builtin(name: &str, mode: Option<ResponseMode>, config: &Config) -> Self {
+        Self {
+            name: name.to_string(),
+            mode,
+            small_model: config.small_model.clone(),
+            large_model: config.large_model.clone(),
+            quick_system_prompt: None,
+            large_system_prompt: None,
+            max_tokens: None,
+        }
+    }
+
+    /// This route answering directly with its small model, for keys over their budget
+    pub fn degraded(&self) -> Self {
+        Self {
+            mode: Some(ResponseMode::Direct),
+            large_model: self.small_model.clone(),
+            ..self.clone()
+        }
+    }
+
+    pub fn is_pattern(&self) -> bool {
+        self.name.contains('*')
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelRouter {
+    routes: Vec<ModelRoute>,
+    default_route: ModelRoute,
+}
+
+impl ModelRouter {
+    /// Configured routes take precedence over the built-in aliases; names that match
+    /// nothing fall back to the default SMALL_MODEL_* / LARGE_MODEL_* backends.
+    pub fn from_config(config: &Config) -> Self {
+        let mut routes: Vec<ModelRoute> = config
+            .model_routes
+            .iter()
+            .map(ModelRoute::from_route_config)
+            .collect();
+
+        for (alias, mode) in [
+            (QUICK_MODEL_ALIAS, ResponseMode::Quick),
+            (DIRECT_MODEL_ALIAS, ResponseMode::Direct),
+        ] {
+            if !routes.iter().any(|route| route.name == alias) {
+                routes.push(ModelRoute::builtin(alias, Some(mode), config));
+            }
+        }
+
+        Self {
+            routes,
+            default_route: ModelRoute::builtin("default", None, config),
+        }
+    }
+
+    /// Exact names win over patterns; patterns are tried in configuration order.
+    pub fn resolve(&self, model: &str) -> &ModelRoute {
+        self.routes
+            .iter()
+            .find(|route| !route.is_pattern() && route.name == model)
+            .or_else(|| {
+                self.routes
+                    .iter()
+                    .find(|route| route.is_pattern() && matches_pattern(&route.name, model))
+            })
+            .unwrap_or(&self.default_route)
+    }
+
+    /// Public model ids, in the order they should be listed by `/v1/models`.
+    /// Wildcard patterns are not listable and are skipped.
+    pub fn model_ids(&self) -> Vec<String> {
+        self.routes
+            .iter()
+            .filter(|route| !route.is_pattern())
+            .map(|route| route.name.clone())
+            .collect()
+    }
+}
+
+/// Glob-style match where `*` matches any (possibly empty) sequence of characters.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No `*` in the pattern: must be an exact match
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
@@ -1,4 +1,22 @@
-use crate::{config::Config, models::*, stats::StatsCollector, errors::LoroError};
+use crate::{
+    access_log::AccessRecord,
+    audit::{AuditCapture, AuditLog, AuditMessage, AuditRecord},
+    chaos::{self, Chaos},
+    chunk::{ChunkTemplate, MAX_CHUNK_BYTES},
+    config::{BudgetAction, Config},
+    errors::{ErrorBody, LoroError},
+    models::*,
+    ndjson::NdjsonDecoder,
+    readiness::{probe_upstream, ReadinessReport},
+    routing::{ModelRoute, ModelRouter, ResponseMode},
+    shutdown::DrainState,
+    stats::StatsCollector,
+    speech::{self, AudioSegment, ReplyEvent, ReplyStream, SpeechSynthesizer},
+    sse::{SseDecoder, SseEventRef},
+    transcription::{encode_multipart, AudioUpload, FormPart, Transcription},
+    usage::{key_label, ClientKey, ReplyPart, UsageCollector, UsageMeter},
+};
+use base64::Engine;
 use secrecy::ExposeSecret;
 use anyhow::{Context, Result};
 use axum::response::{IntoResponse, Response, Sse};
@@ -6,31 +24,153 @@ use futures::stream::{self, Stream, StreamExt};
 use rand::seq::SliceRandom;
 use reqwest::Client;
 use serde_json::json;
+use std::borrow::Cow;
 use std::pin::Pin;
 use std::{
-    collections::HashMap,
     sync::Arc,
     time::{Duration, Instant},
 };
-use tokio::time::timeout;
-use tracing::{debug, error, info, warn};
+use tokio::{sync::RwLock, task::JoinHandle, time::timeout};
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
 use uuid::Uuid;
 
 // Static constants to reduce string allocations
 const SYSTEM_ROLE: &str = "system";
 const USER_ROLE: &str = "user";
 const ASSISTANT_ROLE: &str = "assistant";
-const CHUNK_OBJECT: &str = "chat.completion.chunk";
-const STOP_REASON: &str = "stop";
 const QUICK_SYSTEM_PROMPT: &str = "/no_think 你是一个AI语音助手。请用1-3个字的简短语气词回应用户，比如：'你好！'、'好的，'、'嗯，'、'让我想想，'，要自然像真人对话。只输出语气词，不要完整回答。";
 const LARGE_SYSTEM_PROMPT: &str =
     "你是一个友好的AI语音助手，用自然对话的方式回应用户。回答要简洁明了，适合语音交互。";
 
+pub type ChunkStream = Pin<Box<dyn Stream<Item = Result<String>> + Send>>;
+
+/// Header carrying the request ID from clients, to upstreams and back in responses
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Per-request metadata from the transport, outside the OpenAI request body.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    /// Client key from `Authorization: Bearer ...`, used to attribute usage
+    pub api_key: Option<String>,
+    /// Correlates logs, upstream calls and the `chatcmpl-` id of every chunk
+    pub request_id: String,
+    /// Mode, models and timings for the access log line
+    pub access: AccessRecord,
+}
+
+impl Default for RequestContext {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            request_id: Uuid::new_v4().to_string(),
+            access: AccessRecord::default(),
+        }
+    }
+}
+
+impl RequestContext {
+    /// Reads the bearer key and `X-Request-Id`; a missing or malformed ID is replaced
+    pub fn from_headers(headers: &axum::http::HeaderMap) -> Self {
+        let api_key = headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty());
+        let request_id = headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        Self {
+            api_key,
+            request_id,
+            access: AccessRecord::default(),
+        }
+    }
+
+    /// Same client, new request ID (one per realtime turn)
+    pub fn with_request_id(&self, request_id: String) -> Self {
+        Self {
+            api_key: self.api_key.clone(),
+            request_id,
+            access: AccessRecord::default(),
+        }
+    }
+}
+
+// IDs end up in headers, logs and chunk ids, so only short printable ASCII is accepted
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Token usage of one reply. Owned by the end of the reply stream: `finish` records it
+/// when the stream completes, and dropping it records whatever was metered if the
+/// stream ends early, e.g. when the client disconnects.
+struct ReplyUsage {
+    collector: Arc<UsageCollector>,
+    key: ClientKey,
+    small: Option<(String, UsageMeter)>,
+    large_model: String,
+    large: Arc<std::sync::Mutex<UsageMeter>>,
+    /// Template of the final usage chunk, when the client asked for one
+    usage_chunk: Option<ChunkTemplate>,
+    access: AccessRecord,
+    recorded: bool,
+}
+
+impl ReplyUsage {
+    /// Records both models' usage in metrics and the access log, once
+    fn record(&mut self) -> Usage {
+        self.recorded = true;
+        let mut total = Usage::default();
+        if let Some((model, meter)) = &self.small {
+            let (usage, estimated) = meter.usage();
+            self.collector
+                .record(&self.key, ReplyPart::QuickResponse, model, &usage, estimated);
+            total = total.add(&usage);
+        }
+        let (usage, estimated) = self.large.lock().unwrap_or_else(|e| e.into_inner()).usage();
+        self.collector
+            .record(&self.key, ReplyPart::Answer, &self.large_model, &usage, estimated);
+        total = total.add(&usage);
+        self.access.update(|fields| {
+            fields.prompt_tokens = Some(total.prompt_tokens);
+            fields.completion_tokens = Some(total.completion_tokens);
+        });
+        total
+    }
+
+    /// Records the usage and returns the usage chunk payload if requested
+    fn finish(mut self) -> Option<String> {
+        let total = self.record();
+        self.usage_chunk.take()?.render_usage(&total).ok()
+    }
+}
+
+impl Drop for ReplyUsage {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.record();
+        }
+    }
+}
+
 pub struct LoroService {
     config: Config,
     client: Client,
     quick_stats: Arc<StatsCollector>,
     direct_stats: Arc<StatsCollector>,
+    drain: Arc<DrainState>,
+    readiness: RwLock<Option<ReadinessReport>>,
+    router: ModelRouter,
+    speech: Option<Arc<SpeechSynthesizer>>,
+    usage: Arc<UsageCollector>,
+    audit: Option<Arc<AuditLog>>,
+    chaos: Chaos,
+    started_at: i64,
 }
 
 impl LoroService {
@@ -51,55 +191,357 @@ impl LoroService {
             config.small_model.model_name
         );
         info!("Large model: {}", config.large_model.model_name);
+        if !config.model_routes.is_empty() {
+            info!("Loaded {} model route(s)", config.model_routes.len());
+        }
+
+        let audit = config
+            .audit
+            .as_ref()
+            .map(AuditLog::open)
+            .transpose()?
+            .map(Arc::new);
+
+        let speech = config
+            .speech
+            .clone()
+            .map(|speech| Arc::new(SpeechSynthesizer::new(client.clone(), speech)));
+
+        if config.chaos.is_some() {
+            warn!("Chaos testing is enabled: upstream faults will be injected");
+        }
+        let chaos = Chaos::new(config.chaos.as_ref());
 
         Ok(Self {
             config: config.clone(),
             client,
             quick_stats: Arc::new(StatsCollector::new(config.stats_max_entries)),
             direct_stats: Arc::new(StatsCollector::new(config.stats_max_entries)),
+            drain: Arc::new(DrainState::new()),
+            readiness: RwLock::new(None),
+            router: ModelRouter::from_config(&config),
+            speech,
+            usage: Arc::new(
+                UsageCollector::with_pricing(&config.pricing).with_client_keys(
+                    config
+                        .client_api_keys
+                        .iter()
+                        .map(|key| key.expose_secret().as_str()),
+                ),
+            ),
+            audit,
+            chaos,
+            started_at: chrono::Utc::now().timestamp(),
         })
     }
 
-    pub async fn chat_completion(&self, request: ChatCompletionRequest) -> Result<Response> {
-        let disable_quick = request.disable_quick_response;
+    pub async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+        ctx: &RequestContext,
+    ) -> Result<Response> {
+        let (stream, _) = self.reply_stream(request, ctx).await?;
+        Ok(Sse::new(sse_events(stream)).into_response())
+    }
 
-        debug!(
-            "Processing chat completion request, disable_quick: {}",
-            disable_quick
-        );
+    /// Client-facing body for `e`, with upstream details when `EXPOSE_UPSTREAM_ERRORS` is set
+    pub fn error_body(&self, e: &anyhow::Error) -> ErrorBody {
+        error_body(e, self.config.expose_upstream_errors)
+    }
 
-        let stream: Pin<Box<dyn Stream<Item = Result<String>> + Send>> = if disable_quick {
-            Box::pin(self.stream_direct_response(request).await?)
-        } else {
-            Box::pin(self.stream_quick_response(request).await?)
+    pub fn speech_enabled(&self) -> bool {
+        self.speech.is_some()
+    }
+
+    /// Transport-independent reply events, shared by SSE and realtime: chunk JSON
+    /// payloads ending with `[DONE]` (in quick mode the quick acknowledgement comes
+    /// first), plus synthesized audio segments when the request asks for the
+    /// `audio` modality and a TTS backend is configured. The flag tells whether the
+    /// reply starts with a quick acknowledgement, which budgets can turn off.
+    pub async fn reply_stream(
+        &self,
+        request: ChatCompletionRequest,
+        ctx: &RequestContext,
+    ) -> Result<(ReplyStream, bool)> {
+        let speech = match &self.speech {
+            Some(speech) if request.wants_audio() => {
+                let voice = speech.voice(request.audio.as_ref());
+                Some((Arc::clone(speech), voice))
+            }
+            _ => None,
         };
+        let (chunks, quick_first) = self.open_chunk_stream(request, ctx).await?;
+        let apology = self.config.error_apology.clone();
+        let expose_upstream = self.config.expose_upstream_errors;
 
-        let sse_stream = stream.map(|chunk| match chunk {
-            Ok(data) => Ok::<_, anyhow::Error>(axum::response::sse::Event::default().data(data)),
+        let replies = match speech {
+            Some((synthesizer, voice)) => {
+                speech::speak(chunks, quick_first, synthesizer, voice, apology.clone())
+            }
+            None => speech::text_only(chunks),
+        };
+        // Errors after the response has started are reported in-band as typed events
+        let replies = replies.map(move |event| match event {
             Err(e) => {
-                error!("Stream error: {}", e);
-                // 仅输出负载，由 axum SSE 封装 data: 前缀
-                Ok(axum::response::sse::Event::default().data(format!("[ERROR: {}]", e)))
+                warn!("Reply stream error: {:#}", e);
+                Ok(ReplyEvent::Error(
+                    error_body(&e, expose_upstream).with_apology(apology.clone()),
+                ))
             }
+            event => event,
         });
+        Ok((Box::pin(replies), quick_first))
+    }
+
+    /// Whether `request` asks for a quick acknowledgement before the large model output
+    fn uses_quick_response(&self, request: &ChatCompletionRequest) -> bool {
+        !request.disable_quick_response
+            && self.router.resolve(&request.model).mode != Some(ResponseMode::Direct)
+    }
+
+    /// Applies the key's daily budget: fails when over budget and rejecting,
+    /// returns `true` when the request should be degraded to the small model
+    fn over_budget(&self, ctx: &RequestContext) -> Result<bool> {
+        let Some(budget) = &self.config.budget else {
+            return Ok(false);
+        };
+        let key = self.usage.client_key(ctx.api_key.as_deref());
+        let spent = self.usage.spent_today(&key);
+        if spent < budget.daily_limit_usd {
+            return Ok(false);
+        }
+        match budget.action {
+            BudgetAction::Reject => {
+                warn!("Rejecting request from {}: spent ${:.4} today", key.label(), spent);
+                Err(anyhow::Error::from(LoroError::BudgetExceeded {
+                    limit_usd: budget.daily_limit_usd,
+                }))
+            }
+            BudgetAction::Degrade => {
+                debug!("Degrading request from {}: spent ${:.4} today", key.label(), spent);
+                Ok(true)
+            }
+        }
+    }
+
+    /// Chunk stream plus whether its first payload is a quick acknowledgement
+    async fn open_chunk_stream(
+        &self,
+        request: ChatCompletionRequest,
+        ctx: &RequestContext,
+    ) -> Result<(ChunkStream, bool)> {
+        // Counted as active from here, so shutdown also waits for requests that are
+        // still calling the quick model or connecting to the large model
+        let guard = self.drain.track_stream();
+        // Every stage of the request logs inside this span, including the stream itself
+        let span = info_span!(
+            "request",
+            request_id = %ctx.request_id,
+            model = %request.model,
+            route = tracing::field::Empty,
+            mode = tracing::field::Empty
+        );
+        let first_token = info_span!(parent: &span, "time_to_first_token");
+        let audit = self.start_audit(&request, ctx);
+        let setup = async {
+            let mut route = self.router.resolve(&request.model).clone();
+            let mut disable_quick = !self.uses_quick_response(&request);
+            let mut mode = if disable_quick { "direct" } else { "quick" };
+            if self.over_budget(ctx)? {
+                route = route.degraded();
+                disable_quick = true;
+                mode = "degraded";
+            }
+            Span::current()
+                .record("route", route.name.as_str())
+                .record("mode", mode);
+            ctx.access.update(|fields| {
+                fields.mode = Some(mode);
+                fields.large_model = Some(route.large_model.model_name.clone());
+                if !disable_quick {
+                    fields.small_model = Some(route.small_model.model_name.clone());
+                }
+            });
+
+            debug!(
+                "Processing chat completion request, route: {}, disable_quick: {}",
+                route.name, disable_quick
+            );
+
+            let stream = if disable_quick {
+                self.stream_direct_response(request, &route, ctx).await?
+            } else {
+                self.stream_quick_response(request, &route, ctx).await?
+            };
+
+            // Keep the stream counted as active until the client has received everything
+            let stream: ChunkStream = Box::pin(stream.map(move |chunk| {
+                let _guard = &guard;
+                chunk
+            }));
+            anyhow::Ok((stream, mode))
+        };
+        let (mut stream, mode) = setup.instrument(span.clone()).await?;
+        let quick = mode == "quick";
+        if let Some((log, mut record)) = audit {
+            record.mode = mode;
+            let mut capture = AuditCapture::new(log, record, quick);
+            stream = Box::pin(stream.map(move |chunk| {
+                if let Ok(payload) = &chunk {
+                    capture.observe(payload);
+                }
+                chunk
+            }));
+        }
+        Ok((trace_stream(stream, span, first_token), quick))
+    }
+
+    /// Audit record for a sampled request, completed as the reply streams
+    fn start_audit(
+        &self,
+        request: &ChatCompletionRequest,
+        ctx: &RequestContext,
+    ) -> Option<(Arc<AuditLog>, AuditRecord)> {
+        let log = self.audit.as_ref().filter(|log| log.sampled())?;
+        let record = AuditRecord {
+            timestamp: chrono::Utc::now(),
+            request_id: ctx.request_id.clone(),
+            api_key: key_label(ctx.api_key.as_deref()),
+            model: request.model.clone(),
+            mode: "",
+            messages: request.messages.iter().map(AuditMessage::from).collect(),
+            quick_response: None,
+            reply: String::new(),
+            completed: false,
+        };
+        Some((Arc::clone(log), record))
+    }
 
-        Ok(Sse::new(sse_stream).into_response())
+    pub fn transcription_enabled(&self) -> bool {
+        self.config.transcription.is_some()
+    }
+
+    /// Sends the audio to the configured OpenAI-compatible transcription backend.
+    pub async fn transcribe(
+        &self,
+        upload: &AudioUpload,
+        ctx: &RequestContext,
+    ) -> Result<Transcription> {
+        let transcription = self
+            .config
+            .transcription
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No transcription backend configured"))?;
+        let backend = &transcription.backend;
+
+        let mut parts = vec![
+            FormPart::file(
+                "file",
+                &upload.filename,
+                &upload.content_type,
+                upload.audio.clone(),
+            ),
+            FormPart::text("model", &backend.model_name),
+            FormPart::text("response_format", "json"),
+        ];
+        if let Some(language) = upload.field("language").or(transcription.language.as_deref()) {
+            parts.push(FormPart::text("language", language));
+        }
+        for name in ["prompt", "temperature"] {
+            if let Some(value) = upload.field(name) {
+                parts.push(FormPart::text(name, value));
+            }
+        }
+
+        let boundary = format!("loro-{}", Uuid::new_v4().simple());
+        let mut request_builder = self
+            .client
+            .post(format!("{}/audio/transcriptions", backend.base_url))
+            .header(
+                "Content-Type",
+                format!("multipart/form-data; boundary={}", boundary),
+            )
+            .header(REQUEST_ID_HEADER, ctx.request_id.as_str())
+            .body(encode_multipart(&boundary, &parts));
+        if backend.api_key.expose_secret() != "none" {
+            request_builder = request_builder.header(
+                "Authorization",
+                format!("Bearer {}", backend.api_key.expose_secret()),
+            );
+        }
+
+        let start = Instant::now();
+        let response = execute_with_retry(
+            move || {
+                let builder = match request_builder.try_clone() {
+                    Some(b) => b,
+                    None => {
+                        return Box::pin(async {
+                            Err(anyhow::anyhow!("Failed to clone request builder in retry"))
+                        })
+                    }
+                };
+                Box::pin(async move {
+                    builder
+                        .send()
+                        .await
+                        .map_err(|e| anyhow::Error::from(LoroError::HttpClient(e)))
+                })
+            },
+            self.config.max_retries,
+            "transcription_request",
+        )
+        .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow::Error::from(LoroError::ApiError {
+                provider: "transcription".to_string(),
+                status: status.as_u16(),
+                message: error_text,
+            }));
+        }
+
+        let mut result: Transcription = response
+            .json()
+            .await
+            .context("Failed to parse transcription response")?;
+        result.text = result.text.trim().to_string();
+        debug!(
+            "Transcribed {} bytes of audio in {:.3}s",
+            upload.audio.len(),
+            start.elapsed().as_secs_f64()
+        );
+        Ok(result)
     }
 
     async fn stream_quick_response(
         &self,
         request: ChatCompletionRequest,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<String>> + Send>>> {
+        route: &ModelRoute,
+        ctx: &RequestContext,
+    ) -> Result<ChunkStream> {
         let request_start = Instant::now();
-        let request_id = Uuid::new_v4().to_string();
+        let request_id = &ctx.request_id;
+        let span = Span::current();
 
         // Clone request data for concurrent access
         let messages = request.messages.clone();
-        let model_name = request.model.clone();
+        // Fields shared by every chunk of the reply are rendered once
+        let template = ChunkTemplate::new(request_id, &request.model);
 
         // Step 1: Get quick response first
         let quick_start = Instant::now();
-        let quick_response = self.get_quick_response(&messages).await?;
+        let (quick_response, small_usage) = self
+            .get_quick_response(&messages, route, ctx)
+            .instrument(info_span!(
+                "small_model",
+                model = %route.small_model.model_name,
+                retries = tracing::field::Empty,
+                fallback = tracing::field::Empty
+            ))
+            .await?;
         let quick_time = quick_start.elapsed().as_secs_f64();
 
         debug!(
@@ -107,55 +549,90 @@ impl LoroService {
             quick_time, quick_response
         );
 
-        // Create first chunk with quick response
-        let first_chunk = ChatCompletionChunk {
-            id: format!("chatcmpl-{request_id}"),
-            object: CHUNK_OBJECT.to_string(),
-            created: chrono::Utc::now().timestamp(),
-            model: model_name,
-            choices: vec![ChoiceDelta {
-                index: 0,
-                delta: MessageDelta {
-                    role: Some(ASSISTANT_ROLE.to_string()),
-                    content: Some(quick_response.clone()),
-                },
-                finish_reason: None,
-            }],
-        };
-
         // 构造 JSON 负载（不包含 SSE data: 前缀）
-        let first_chunk_data = serde_json::to_string(&first_chunk)?;
-        if first_chunk_data.len() > 1024 * 1024 {
-            return Err(anyhow::anyhow!(
-                "Response chunk too large: {} bytes",
-                first_chunk_data.len()
-            ));
-        }
+        let first_chunk = StreamDelta {
+            role: Some(Cow::Borrowed(ASSISTANT_ROLE)),
+            content: Some(Cow::Borrowed(quick_response.as_str())),
+            tool_calls: None,
+        };
+        let first_chunk_data =
+            within_chunk_limit(template.render(&first_chunk, None, quick_response.len() + 32)?)?;
 
         // Step 2: Get large model stream with prefix
         let large_start = Instant::now();
+        let usage_chunk = request.wants_usage().then(|| template.clone());
+        let large_usage = Arc::new(std::sync::Mutex::new(UsageMeter::default()));
         let large_stream = self
-            .get_large_model_stream(request, Some(quick_response))
+            .get_large_model_stream(
+                request,
+                Some(quick_response),
+                route,
+                Arc::clone(&large_usage),
+                request_id,
+                template,
+            )
+            .instrument(info_span!(
+                "large_model_connect",
+                model = %route.large_model.model_name,
+                retries = tracing::field::Empty
+            ))
             .await?;
+        let reply_usage = ReplyUsage {
+            collector: Arc::clone(&self.usage),
+            key: self.usage.client_key(ctx.api_key.as_deref()),
+            small: small_usage.map(|meter| (route.small_model.model_name.clone(), meter)),
+            large_model: route.large_model.model_name.clone(),
+            large: large_usage,
+            usage_chunk,
+            access: ctx.access.clone(),
+            recorded: false,
+        };
 
         let stats = Arc::clone(&self.quick_stats);
-        let enhanced_stream = large_stream.map(|chunk_result| chunk_result);
+        let mut large_started = false;
+        let enhanced_stream = {
+            let span = span.clone();
+            let access = ctx.access.clone();
+            large_stream.inspect(move |chunk_result| {
+                if !large_started && chunk_result.is_ok() {
+                    large_started = true;
+                    let elapsed_ms = access.elapsed_ms();
+                    access.update(|fields| fields.first_large_token_ms = Some(elapsed_ms));
+                    span.in_scope(|| {
+                        info!(
+                            elapsed_ms = request_start.elapsed().as_millis() as u64,
+                            "First large model token"
+                        )
+                    });
+                }
+            })
+        };
 
-        // 结束时记录统计并发送 [DONE]
+        // 结束时记录统计与用量，并发送 [DONE]
         let end_event = {
             let stats = Arc::clone(&stats);
             async move {
                 let total_time = request_start.elapsed().as_secs_f64();
                 let large_time = large_start.elapsed().as_secs_f64();
                 stats.add_request(quick_time, total_time, Some(quick_time), Some(large_time));
-                Ok("[DONE]".to_string())
+                info!(elapsed_ms = (total_time * 1000.0) as u64, "Request done");
+                final_events(reply_usage)
             }
+            .instrument(span.clone())
         };
 
         // Combine quick response and large model stream
-        let combined_stream = stream::once(async move { Ok(first_chunk_data) })
+        let access = ctx.access.clone();
+        let first_chunk_event = async move {
+            let elapsed_ms = access.elapsed_ms();
+            access.update(|fields| fields.quick_chunk_ms = Some(elapsed_ms));
+            info!(elapsed_ms = request_start.elapsed().as_millis() as u64, "First token");
+            Ok(first_chunk_data)
+        }
+        .instrument(span);
+        let combined_stream = stream::once(first_chunk_event)
             .chain(enhanced_stream)
-            .chain(stream::once(end_event));
+            .chain(stream::once(end_event).flat_map(stream::iter));
 
         Ok(Box::pin(combined_stream))
     }
@@ -163,19 +640,60 @@ impl LoroService {
     async fn stream_direct_response(
         &self,
         request: ChatCompletionRequest,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<String>> + Send>>> {
+        route: &ModelRoute,
+        ctx: &RequestContext,
+    ) -> Result<ChunkStream> {
         let request_start = Instant::now();
-        let large_stream = self.get_large_model_stream(request, None).await?;
+        let span = Span::current();
+        // Fields shared by every chunk of the reply are rendered once
+        let template = ChunkTemplate::new(&ctx.request_id, &request.model);
+        let usage_chunk = request.wants_usage().then(|| template.clone());
+        let large_usage = Arc::new(std::sync::Mutex::new(UsageMeter::default()));
+        let large_stream = self
+            .get_large_model_stream(
+                request,
+                None,
+                route,
+                Arc::clone(&large_usage),
+                &ctx.request_id,
+                template,
+            )
+            .instrument(info_span!(
+                "large_model_connect",
+                model = %route.large_model.model_name,
+                retries = tracing::field::Empty
+            ))
+            .await?;
+        let reply_usage = ReplyUsage {
+            collector: Arc::clone(&self.usage),
+            key: self.usage.client_key(ctx.api_key.as_deref()),
+            small: None,
+            large_model: route.large_model.model_name.clone(),
+            large: large_usage,
+            usage_chunk,
+            access: ctx.access.clone(),
+            recorded: false,
+        };
 
         let stats = Arc::clone(&self.direct_stats);
         let first_time = Arc::new(std::sync::Mutex::new(None::<f64>));
         let first_time_clone = Arc::clone(&first_time);
+        let first_span = span.clone();
+        let access = ctx.access.clone();
         let enhanced_stream = large_stream.enumerate().map(move |(i, chunk_result)| {
             match chunk_result {
                 Ok(chunk_data) => {
                     if i == 0 {
                         let mut guard = first_time_clone.lock().unwrap();
                         *guard = Some(request_start.elapsed().as_secs_f64());
+                        let elapsed_ms = access.elapsed_ms();
+                        access.update(|fields| fields.first_large_token_ms = Some(elapsed_ms));
+                        first_span.in_scope(|| {
+                            info!(
+                                elapsed_ms = request_start.elapsed().as_millis() as u64,
+                                "First token"
+                            )
+                        });
                     }
                     Ok(chunk_data)
                 }
@@ -190,27 +708,40 @@ impl LoroService {
                 let total_time = request_start.elapsed().as_secs_f64();
                 let first = first_time.lock().unwrap().unwrap_or(total_time);
                 stats.add_request(first, total_time, None, Some(total_time));
-                Ok("[DONE]".to_string())
+                info!(elapsed_ms = (total_time * 1000.0) as u64, "Request done");
+                final_events(reply_usage)
             }
+            .instrument(span)
         };
 
-        let final_stream = enhanced_stream.chain(stream::once(end_event));
+        let final_stream = enhanced_stream.chain(stream::once(end_event).flat_map(stream::iter));
 
         Ok(Box::pin(final_stream))
     }
 
-    async fn get_quick_response(&self, messages: &[Message]) -> Result<String> {
+    /// Returns the quick acknowledgement and, when the small model was called successfully,
+    /// its token usage (billed even if the answer is replaced by a fallback phrase)
+    async fn get_quick_response(
+        &self,
+        messages: &[Message],
+        route: &ModelRoute,
+        ctx: &RequestContext,
+    ) -> Result<(String, Option<UsageMeter>)> {
         // Validate input - prevent panic
         if messages.is_empty() {
             return Err(anyhow::anyhow!("Messages array cannot be empty"));
         }
 
         // Try small model first
-        match self.call_small_model(messages).await {
-            Ok(response) => {
+        let mut small_usage = None;
+        match self.call_small_model(messages, route, &ctx.request_id).await {
+            Ok((response, usage)) => {
                 if response.chars().count() <= 6 && self.is_appropriate_quick_response(&response) {
-                    return Ok(response);
+                    Span::current().record("fallback", false);
+                    ctx.access.update(|fields| fields.fallback = Some(false));
+                    return Ok((response, Some(usage)));
                 }
+                small_usage = Some(usage);
             }
             Err(e) => {
                 warn!("Small model failed: {}, using fallback", e);
@@ -218,8 +749,9 @@ impl LoroService {
         }
 
         // Fallback to predefined responses
-        let last_message = messages
-            .last()
+        Span::current().record("fallback", true);
+        ctx.access.update(|fields| fields.fallback = Some(true));
+        let last_message = quick_response_source(messages)
             .expect("Messages array should not be empty (already checked)");
         let category = last_message.categorize();
         let responses = category.get_responses();
@@ -227,44 +759,64 @@ impl LoroService {
         let response = responses
             .choose(&mut rng)
             .expect("Predefined responses array should never be empty");
-        Ok(response.to_string())
+        Ok((response.to_string(), small_usage))
     }
 
-    async fn call_small_model(&self, messages: &[Message]) -> Result<String> {
+    async fn call_small_model(
+        &self,
+        messages: &[Message],
+        route: &ModelRoute,
+        request_id: &str,
+    ) -> Result<(String, UsageMeter)> {
         if messages.is_empty() {
             return Err(anyhow::anyhow!("Messages array cannot be empty"));
         }
 
-        let last_message = messages
-            .last()
+        let last_message = quick_response_source(messages)
             .expect("Messages array should not be empty (already checked)");
-        if last_message.content.trim().is_empty() {
+        // The quick response only needs the words; image parts are ignored
+        let last_text = last_message.content.text();
+        if last_text.trim().is_empty() {
             return Err(anyhow::anyhow!("Message content cannot be empty"));
         }
 
-        // Use module-level constants to reduce allocations
+        let small_model = &route.small_model;
+        let quick_system_prompt = route
+            .quick_system_prompt
+            .as_deref()
+            .unwrap_or(QUICK_SYSTEM_PROMPT);
 
         let prompt_messages = vec![
-            HashMap::from([(SYSTEM_ROLE.to_string(), QUICK_SYSTEM_PROMPT.to_string())]),
-            HashMap::from([(USER_ROLE.to_string(), last_message.content.clone())]),
+            Message {
+                role: SYSTEM_ROLE.to_string(),
+                content: quick_system_prompt.into(),
+                ..Default::default()
+            },
+            Message {
+                role: USER_ROLE.to_string(),
+                content: last_text.as_ref().into(),
+                ..Default::default()
+            },
         ];
 
+        let mut usage = UsageMeter::new(&prompt_messages);
+
         // Create request body appropriate for the target service
-        let request_body = if self.config.small_model.base_url.contains("11434") {
+        let request_body = if small_model.is_ollama() {
             // Ollama-compatible request
             // Reuse constants to avoid string allocations
             let ollama_messages = vec![
                 json!({
                     "role": SYSTEM_ROLE,
-                    "content": QUICK_SYSTEM_PROMPT
+                    "content": quick_system_prompt
                 }),
                 json!({
                     "role": USER_ROLE,
-                    "content": last_message.content
+                    "content": last_text
                 }),
             ];
             json!({
-                "model": self.config.small_model.model_name,
+                "model": small_model.model_name,
                 "messages": ollama_messages,
                 "stream": false,
                 "keep_alive": "10m",
@@ -279,7 +831,7 @@ impl LoroService {
         } else {
             // Full OpenAI-compatible request
             serde_json::to_value(OpenAIRequest {
-                model: self.config.small_model.model_name.clone(),
+                model: small_model.model_name.clone(),
                 messages: prompt_messages,
                 max_tokens: Some(10),
                 temperature: Some(0.3),
@@ -289,27 +841,31 @@ impl LoroService {
                 stop: None,
                 stream: false,
                 extra_body: None, // Remove extra_body for OpenAI compatibility
+                tools: None,
+                tool_choice: None,
+                stream_options: None,
             })?
         };
 
         // Determine endpoint based on base URL (Ollama vs OpenAI compatible)
-        let endpoint = if self.config.small_model.base_url.contains("11434") {
-            format!("{}/api/chat", self.config.small_model.base_url)
+        let endpoint = if small_model.is_ollama() {
+            format!("{}/api/chat", small_model.base_url)
         } else {
-            format!("{}/chat/completions", self.config.small_model.base_url)
+            format!("{}/chat/completions", small_model.base_url)
         };
 
         let mut request_builder = self
             .client
             .post(endpoint)
             .header("Content-Type", "application/json")
+            .header(REQUEST_ID_HEADER, request_id)
             .json(&request_body);
 
         // Only add Authorization header if API key is not "none" (for local services like Ollama)
-        if self.config.small_model.api_key.expose_secret() != "none" {
+        if small_model.api_key.expose_secret() != "none" {
             request_builder = request_builder.header(
                 "Authorization",
-                format!("Bearer {}", self.config.small_model.api_key.expose_secret()),
+                format!("Bearer {}", small_model.api_key.expose_secret()),
             );
         }
 
@@ -318,6 +874,7 @@ impl LoroService {
             .try_clone()
             .ok_or_else(|| anyhow::anyhow!("Failed to clone request builder"))?;
         let timeout_duration = Duration::from_secs(self.config.small_model_timeout_secs);
+        let injector = self.chaos.small_model.clone();
 
         let response = execute_with_retry(
             move || {
@@ -330,8 +887,9 @@ impl LoroService {
                     }
                 };
                 let timeout_dur = timeout_duration;
+                let injector = injector.clone();
                 Box::pin(async move {
-                    timeout(timeout_dur, builder.send())
+                    timeout(timeout_dur, chaos::send(builder, injector))
                         .await
                         .map_err(|_e| {
                             // 转为类型化超时错误
@@ -355,31 +913,43 @@ impl LoroService {
             }));
         }
 
+        // Read through the chaos layer so body faults reach the quick response too
+        let body = chaos::body_bytes(response, self.chaos.small_model.as_ref())
+            .await
+            .context("Failed to read small model response")?;
+
         // Parse response based on API provider
-        let response_content = if self.config.small_model.base_url.contains("11434") {
+        let response_content = if small_model.is_ollama() {
             // Ollama response format
-            let ollama_response: OllamaResponse = response
-                .json()
-                .await
-                .context("Failed to parse Ollama response")?;
-            ollama_response.message.content
+            let ollama_response: OllamaResponse =
+                serde_json::from_slice(&body).context("Failed to parse Ollama response")?;
+            if let (Some(prompt), Some(completion)) =
+                (ollama_response.prompt_eval_count, ollama_response.eval_count)
+            {
+                usage.report(Usage::new(prompt, completion));
+            }
+            ollama_response.message.content.into_owned()
         } else {
             // OpenAI response format
-            let openai_response: OpenAIResponse = response
-                .json()
-                .await
-                .context("Failed to parse small model response")?;
+            let openai_response: OpenAIResponse =
+                serde_json::from_slice(&body).context("Failed to parse small model response")?;
+            if let Some(reported) = openai_response.usage {
+                usage.report(reported);
+            }
 
             openai_response
                 .choices
                 .first()
                 .and_then(|choice| choice.message.as_ref())
                 .and_then(|msg| msg.content.as_ref())
-                .ok_or_else(|| anyhow::anyhow!("No content in small model response"))?
+                .ok_or_else(|| {
+                    LoroError::SmallModelFailed("No content in small model response".to_string())
+                })?
                 .to_string()
         };
+        usage.add_completion(&response_content);
 
-        Ok(response_content.trim().to_string())
+        Ok((response_content.trim().to_string(), usage))
     }
 
     fn is_appropriate_quick_response(&self, text: &str) -> bool {
@@ -399,47 +969,63 @@ impl LoroService {
         &self,
         request: ChatCompletionRequest,
         _prefix: Option<String>,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<String>> + Send>>> {
+        route: &ModelRoute,
+        usage: Arc<std::sync::Mutex<UsageMeter>>,
+        request_id: &str,
+        template: ChunkTemplate,
+    ) -> Result<ChunkStream> {
+        let large_model = &route.large_model;
+        let large_system_prompt = route
+            .large_system_prompt
+            .as_deref()
+            .unwrap_or(LARGE_SYSTEM_PROMPT);
+
         // Enhance messages with voice assistant context - pre-allocate for performance
         let mut enhanced_messages = Vec::with_capacity(request.messages.len() + 1);
         // Use module-level constants for common strings
 
-        enhanced_messages.push(HashMap::from([
-            ("role".to_string(), SYSTEM_ROLE.to_string()),
-            ("content".to_string(), LARGE_SYSTEM_PROMPT.to_string()),
-        ]));
-
-        // Pre-allocate capacity to avoid reallocations
-        enhanced_messages.reserve(request.messages.len());
-
-        for msg in &request.messages {
-            enhanced_messages.push(HashMap::from([
-                ("role".to_string(), msg.role.clone()),
-                ("content".to_string(), msg.content.clone()),
-            ]));
+        enhanced_messages.push(Message {
+            role: SYSTEM_ROLE.to_string(),
+            content: large_system_prompt.into(),
+            ..Default::default()
+        });
+        enhanced_messages.extend(request.messages.iter().cloned());
+        if let Ok(mut meter) = usage.lock() {
+            *meter = UsageMeter::new(&enhanced_messages);
         }
 
-        let is_ollama = self.config.large_model.base_url.contains("11434");
+        let is_ollama = large_model.is_ollama();
 
         // Build request body and endpoint depending on provider
         let (endpoint, body_value, add_auth) = if is_ollama {
             let mut msgs: Vec<serde_json::Value> = Vec::with_capacity(request.messages.len() + 1);
-            msgs.push(json!({"role": SYSTEM_ROLE, "content": LARGE_SYSTEM_PROMPT}));
-            for m in &request.messages {
-                msgs.push(json!({"role": m.role, "content": m.content}));
-            }
-            let body = json!({
-                "model": self.config.large_model.model_name,
+            msgs.push(json!({"role": SYSTEM_ROLE, "content": large_system_prompt}));
+            msgs.extend(request.messages.iter().map(ollama_message));
+            let mut body = json!({
+                "model": large_model.model_name,
                 "messages": msgs,
                 "stream": true,
-                "keep_alive": "10m"
+                "keep_alive": "10m",
+                "options": {
+                    "num_predict": request.max_tokens.or(route.max_tokens).unwrap_or(150),
+                    "temperature": request.temperature
+                }
             });
-            (format!("{}/api/chat", self.config.large_model.base_url), body, false)
+            // Ollama only takes a list of stop sequences
+            match &request.stop {
+                Some(Stop::Single(stop)) => body["options"]["stop"] = json!([stop]),
+                Some(Stop::Multiple(stops)) => body["options"]["stop"] = json!(stops),
+                None => {}
+            }
+            if let Some(tools) = &request.tools {
+                body["tools"] = serde_json::to_value(tools)?;
+            }
+            (format!("{}/api/chat", large_model.base_url), body, false)
         } else {
             let request_body = OpenAIRequest {
-                model: self.config.large_model.model_name.clone(),
+                model: large_model.model_name.clone(),
                 messages: enhanced_messages,
-                max_tokens: request.max_tokens.or(Some(150)),
+                max_tokens: request.max_tokens.or(route.max_tokens).or(Some(150)),
                 temperature: Some(request.temperature),
                 top_p: None,
                 frequency_penalty: None,
@@ -447,9 +1033,15 @@ impl LoroService {
                 stop: request.stop.clone(),
                 stream: true,
                 extra_body: _prefix.map(|p| json!({"prefix": p})),
+                tools: request.tools.clone(),
+                tool_choice: request.tool_choice.clone(),
+                // Always ask for usage so metrics don't rely on the local estimate
+                stream_options: Some(StreamOptions {
+                    include_usage: true,
+                }),
             };
             (
-                format!("{}/chat/completions", self.config.large_model.base_url),
+                format!("{}/chat/completions", large_model.base_url),
                 serde_json::to_value(request_body)?,
                 true,
             )
@@ -457,12 +1049,13 @@ impl LoroService {
 
         let mut request_builder = self.client.post(endpoint)
             .header("Content-Type", "application/json")
+            .header(REQUEST_ID_HEADER, request_id)
             .json(&body_value);
 
-        if add_auth && self.config.large_model.api_key.expose_secret() != "none" {
+        if add_auth && large_model.api_key.expose_secret() != "none" {
             request_builder = request_builder.header(
                 "Authorization",
-                format!("Bearer {}", self.config.large_model.api_key.expose_secret()),
+                format!("Bearer {}", large_model.api_key.expose_secret()),
             );
         }
 
@@ -470,6 +1063,7 @@ impl LoroService {
         let request_builder_clone = request_builder
             .try_clone()
             .ok_or_else(|| anyhow::anyhow!("Failed to clone request builder"))?;
+        let injector = self.chaos.large_model.clone();
         let response = execute_with_retry(
             move || {
                 let builder = match request_builder_clone.try_clone() {
@@ -480,9 +1074,9 @@ impl LoroService {
                         })
                     }
                 };
+                let injector = injector.clone();
                 Box::pin(async move {
-                    builder
-                        .send()
+                    chaos::send(builder, injector)
                         .await
                         .map_err(|e| anyhow::Error::from(LoroError::HttpClient(e)))
                 })
@@ -495,113 +1089,90 @@ impl LoroService {
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow::anyhow!(
-                "Large model API error {}: {}",
-                status,
-                error_text
-            ));
+            return Err(anyhow::Error::from(LoroError::ApiError {
+                provider: "large".to_string(),
+                status: status.as_u16(),
+                message: error_text,
+            }));
         }
 
-        let byte_stream = response.bytes_stream();
-        let request_id = Uuid::new_v4().to_string();
-        let model_name = request.model.clone();
+        let byte_stream = chaos::body_stream(response, self.chaos.large_model.as_ref());
+
+        if !is_ollama {
+            // The decoder state is unusable after a framing error, so the stream ends there
+            let stream = byte_stream
+                .scan(Some(SseDecoder::new()), move |decoder, chunk_result| {
+                    let Some(active) = decoder.as_mut() else {
+                        return futures::future::ready(None);
+                    };
+                    let mut results = Vec::new();
+                    let mut meter = usage.lock().unwrap_or_else(|e| e.into_inner());
+                    // Events are converted straight out of the decoder's reused buffers
+                    let fed = chunk_result
+                        .map_err(|e| {
+                            error!("Large model stream interrupted: {}", e);
+                            LoroError::StreamProcessing(e.to_string())
+                        })
+                        .and_then(|bytes| {
+                            active.feed_with(&bytes, |event| {
+                                results.extend(
+                                    Self::process_sse_event(event, &template, &mut meter).transpose(),
+                                );
+                            })
+                        });
+                    if let Err(e) = fed {
+                        *decoder = None;
+                        results.push(Err(anyhow::Error::from(e)));
+                    }
+                    futures::future::ready(Some(futures::stream::iter(results)))
+                })
+                .flatten();
+            return Ok(Box::pin(stream));
+        }
 
-        // Process upstream stream with buffering for incomplete chunks (SSE or Ollama JSON lines)
-        let buffer = Arc::new(std::sync::Mutex::new(String::new()));
+        // Ollama streams one JSON object per line; `None` marks the end of the body so a
+        // final line without a line break is still decoded. Messages borrow from the line.
         let stream = byte_stream
-            .map(move |chunk_result| {
-                let request_id = request_id.clone();
-                let model_name = model_name.clone();
-                let buffer = Arc::clone(&buffer);
-                let is_ollama = is_ollama;
-
-                match chunk_result {
-                    Ok(bytes) => {
-                        let mut buffer_guard = match buffer.lock() {
-                            Ok(guard) => guard,
-                            Err(e) => {
-                                error!("SSE buffer lock poisoned: {}", e);
-                                return futures::stream::iter(vec![Err(anyhow::anyhow!(
-                                    "SSE buffer lock poisoned"
-                                ))]);
-                            }
-                        };
-
-                        // Append new data to buffer
-                        buffer_guard.push_str(&String::from_utf8_lossy(&bytes));
-                        
-                        let mut results = Vec::new();
-                        let mut remaining_data = String::new();
-                        
-                        // Process complete lines from buffer
-                        for line in buffer_guard.lines() {
-                            let line = line.trim();
-                            if !line.is_empty() {
-                                if is_ollama {
-                                    // Ollama streaming: each line is a standalone JSON object
-                                    match Self::process_ollama_line_static(line, &request_id, &model_name) {
-                                        Ok(Some(chunk)) => results.push(Ok(chunk)),
-                                        Ok(None) => {}
-                                        Err(_e) => {
-                                            // Likely incomplete JSON, keep for next chunk
-                                            remaining_data = line.to_string();
-                                        }
-                                    }
-                                } else {
-                                    // SSE 格式：形如 data: {...}
-                                    if line.starts_with("data: ") || line == "[DONE]" {
-                                        match Self::process_sse_line_static(line, &request_id, &model_name) {
-                                            Ok(Some(chunk)) => results.push(Ok(chunk)),
-                                            Ok(None) => {}
-                                            Err(_e) => {
-                                                // 可能是半行，保留
-                                                remaining_data = line.to_string();
-                                            }
-                                        }
-                                    } else {
-                                        // 不完整行，保留
-                                        remaining_data = line.to_string();
-                                    }
-                                }
-                            }
+            .map(Some)
+            .chain(futures::stream::once(async { None }))
+            .scan(
+                Some((NdjsonDecoder::new(), 0)),
+                move |state, chunk_result| {
+                    let Some((decoder, tool_calls_seen)) = state.as_mut() else {
+                        return futures::future::ready(None);
+                    };
+                    let mut results = Vec::new();
+                    let mut meter = usage.lock().unwrap_or_else(|e| e.into_inner());
+                    let mut on_line = |line: &[u8]| match serde_json::from_slice(line) {
+                        Ok(resp) => results.extend(
+                            Self::process_ollama_response(
+                                resp,
+                                &template,
+                                &mut meter,
+                                tool_calls_seen,
+                            )
+                            .transpose(),
+                        ),
+                        Err(e) => debug!("Skipping malformed Ollama line: {}", e),
+                    };
+                    let fed = match chunk_result {
+                        Some(Ok(bytes)) => decoder.feed_with(&bytes, &mut on_line),
+                        Some(Err(e)) => {
+                            error!("Large model stream interrupted: {}", e);
+                            Err(LoroError::StreamProcessing(e.to_string()))
                         }
-                        
-                        // Update buffer with remaining incomplete data
-                        *buffer_guard = remaining_data;
-                        
-                        futures::stream::iter(results)
-                    }
-                    Err(e) => {
-                        // Create error chunk similar to Python version
-                        let error_chunk = ChatCompletionChunk {
-                            id: format!("chatcmpl-{request_id}"),
-                            object: CHUNK_OBJECT.to_string(),
-                            created: chrono::Utc::now().timestamp(),
-                            model: model_name.clone(),
-                            choices: vec![ChoiceDelta {
-                                index: 0,
-                                delta: MessageDelta {
-                                    role: None,
-                                    content: Some(" [抱歉，出现了问题]".to_string()),
-                                },
-                                finish_reason: Some(STOP_REASON.to_string()),
-                            }],
-                        };
-
-                        if let Ok(json_str) = serde_json::to_string(&error_chunk) {
-                            if json_str.len() > 1024 * 1024 {
-                                futures::stream::iter(vec![Err(anyhow::anyhow!(
-                                    "Error chunk too large"
-                                ))])
-                            } else {
-                                futures::stream::iter(vec![Ok(json_str)])
-                            }
-                        } else {
-                            futures::stream::iter(vec![Err(anyhow::anyhow!("Stream error: {}", e))])
+                        None => {
+                            decoder.finish_with(&mut on_line);
+                            Ok(())
                         }
+                    };
+                    if let Err(e) = fed {
+                        *state = None;
+                        results.push(Err(anyhow::Error::from(e)));
                     }
-                }
-            })
+                    futures::future::ready(Some(futures::stream::iter(results)))
+                },
+            )
             .flatten();
 
         Ok(Box::pin(stream))
@@ -611,128 +1182,292 @@ impl LoroService {
         line: &str,
         request_id: &str,
         model_name: &str,
+    ) -> Result<Option<String>> {
+        Self::process_ollama_line(line, request_id, model_name, &mut UsageMeter::default())
+    }
+
+    /// Like `process_ollama_line_static`, also recording content and token counts in `meter`
+    fn process_ollama_line(
+        line: &str,
+        request_id: &str,
+        model_name: &str,
+        meter: &mut UsageMeter,
     ) -> Result<Option<String>> {
         // 每一行应为一个 JSON 对象
-        let resp: crate::models::OllamaResponse = serde_json::from_str(line)?;
+        let resp: OllamaResponse = serde_json::from_str(line)?;
+        let template = ChunkTemplate::new(request_id, model_name);
+        Self::process_ollama_response(resp, &template, meter, &mut 0)
+    }
+
+    /// Converts one Ollama message into a chunk payload. The final `done` message becomes
+    /// a finish chunk. `tool_calls_seen` counts tool calls across the whole reply, so each
+    /// call gets its own `index` and the finish reason becomes `tool_calls` when any came.
+    /// Oversized content is an error, like an oversized upstream SSE event.
+    fn process_ollama_response(
+        resp: OllamaResponse<'_>,
+        template: &ChunkTemplate,
+        meter: &mut UsageMeter,
+        tool_calls_seen: &mut u32,
+    ) -> Result<Option<String>> {
+        if let (Some(prompt), Some(completion)) = (resp.prompt_eval_count, resp.eval_count) {
+            meter.report(Usage::new(prompt, completion));
+        }
+        if resp.done {
+            log_ollama_metrics(&resp);
+        }
         let content = resp.message.content;
-        if content.is_empty() {
+        meter.add_completion(&content);
+        // Ollama emits each tool call whole, often one per message; re-shape into
+        // OpenAI-style deltas indexed across the reply
+        let tool_calls = resp.message.tool_calls.map(|calls| {
+            calls
+                .into_iter()
+                .map(|call| {
+                    let index = *tool_calls_seen;
+                    *tool_calls_seen += 1;
+                    ToolCallDelta {
+                        index,
+                        id: Some(format!("call_{}", Uuid::new_v4().simple())),
+                        call_type: Some("function".to_string()),
+                        function: Some(FunctionCallDelta {
+                            name: Some(call.function.name),
+                            arguments: Some(call.function.arguments.to_string()),
+                        }),
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        let finish_reason = resp
+            .done
+            .then(|| ollama_finish_reason(resp.done_reason.as_deref(), *tool_calls_seen > 0));
+        if content.is_empty() && tool_calls.is_none() && finish_reason.is_none() {
             return Ok(None);
         }
-        let chunk = ChatCompletionChunk {
-            id: format!("chatcmpl-{}", request_id),
-            object: CHUNK_OBJECT.to_string(),
-            created: chrono::Utc::now().timestamp(),
-            model: model_name.to_string(),
-            choices: vec![ChoiceDelta {
-                index: 0,
-                delta: MessageDelta { role: None, content: Some(content) },
-                finish_reason: None,
-            }],
+        // `{"content":…}` around the text
+        let size_hint = content.len() + 16;
+        let delta = StreamDelta {
+            role: None,
+            content: (!content.is_empty()).then_some(content),
+            tool_calls,
         };
-        let json_str = serde_json::to_string(&chunk)?;
-        if json_str.len() > 1024 * 1024 { return Ok(None); }
-        Ok(Some(json_str))
+        Ok(Some(within_chunk_limit(template.render(
+            &delta,
+            finish_reason,
+            size_hint,
+        )?)?))
     }
+
     pub fn process_sse_line_static(
         line: &str,
         request_id: &str,
         model_name: &str,
+    ) -> Result<Option<String>> {
+        Self::process_sse_line(line, request_id, model_name, &mut UsageMeter::default())
+    }
+
+    /// Like `process_sse_line_static`, also recording content and reported usage in `meter`
+    fn process_sse_line(
+        line: &str,
+        request_id: &str,
+        model_name: &str,
+        meter: &mut UsageMeter,
     ) -> Result<Option<String>> {
         // Handle SSE format: "data: {json}" or "data: [DONE]"
         if let Some(json_data) = line.strip_prefix("data: ") {
-            if json_data.trim() == "[DONE]" {
-                return Ok(None);
+            let template = ChunkTemplate::new(request_id, model_name);
+            return Self::process_sse_data(json_data, &template, meter);
+        }
+
+        // Skip non-data lines (like event: lines)
+        Ok(None)
+    }
+
+    /// Converts one decoded upstream event: `message` events carry chunks, `error`
+    /// events mean the large model failed and end the reply, anything else is ignored
+    fn process_sse_event(
+        event: SseEventRef<'_>,
+        template: &ChunkTemplate,
+        meter: &mut UsageMeter,
+    ) -> Result<Option<String>> {
+        match event.event {
+            "message" => Self::process_sse_data(event.data, template, meter),
+            "error" => Err(anyhow::Error::from(LoroError::LargeModelFailed(format!(
+                "Upstream error event: {}",
+                event.data
+            )))),
+            other => {
+                debug!("Skipping upstream SSE event of type {}", other);
+                Ok(None)
             }
+        }
+    }
 
-            // Skip empty data lines and event lines
-            let json_data = json_data.trim();
-            if json_data.is_empty() {
+    /// Converts the data of one SSE event into a chunk payload. Strings are borrowed
+    /// from `json_data` and the chunk is rendered from `template` in one allocation.
+    pub fn process_sse_data(
+        json_data: &str,
+        template: &ChunkTemplate,
+        meter: &mut UsageMeter,
+    ) -> Result<Option<String>> {
+        // Skip empty data and the end of stream marker
+        let json_data = json_data.trim();
+        if json_data.is_empty() || json_data == "[DONE]" {
+            return Ok(None);
+        }
+        let upstream_chunk = match serde_json::from_str::<OpenAIStreamChunk>(json_data) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                // Don't return an error, just log and skip this chunk
+                debug!("Skipping malformed SSE chunk: {}, data: {}", e, json_data);
                 return Ok(None);
             }
+        };
+        // With `include_usage` the final chunk has no choices, only usage
+        if let Some(usage) = upstream_chunk.usage {
+            meter.report(usage);
+        }
+        let Some(choice) = upstream_chunk.choice else {
+            return Ok(None);
+        };
+        // Handle both message and delta fields for compatibility
+        let StreamDelta {
+            role,
+            content,
+            tool_calls,
+        } = choice.delta.or(choice.message).unwrap_or_default();
+        let finish_reason = choice.finish_reason.as_deref();
+        if let Some(content) = &content {
+            meter.add_completion(content);
+        }
+        for call in tool_calls.iter().flatten() {
+            if let Some(arguments) = call.function.as_ref().and_then(|f| f.arguments.as_ref()) {
+                meter.add_completion(arguments);
+            }
+        }
 
-            // Parse the JSON chunk with improved error handling
-            match serde_json::from_str::<OpenAIResponse>(json_data) {
-                Ok(openai_chunk) => {
-                    if let Some(choice) = openai_chunk.choices.first() {
-                        // Handle both message and delta fields for compatibility
-                        let (content, role, finish_reason) = if let Some(delta) = &choice.delta {
-                            (
-                                delta.content.as_ref(),
-                                delta.role.as_ref(),
-                                choice.finish_reason.as_ref(),
-                            )
-                        } else if let Some(message) = &choice.message {
-                            (
-                                message.content.as_ref(),
-                                message.role.as_ref(),
-                                choice.finish_reason.as_ref(),
-                            )
-                        } else {
-                            (None, None, choice.finish_reason.as_ref())
-                        };
-
-                        if let Some(content) = content {
-                            // Only process chunks with actual content
-                            if !content.is_empty() {
-                                let chunk = ChatCompletionChunk {
-                                    id: format!("chatcmpl-{request_id}"),
-                                    object: CHUNK_OBJECT.to_string(),
-                                    created: chrono::Utc::now().timestamp(),
-                                    model: model_name.to_string(),
-                                    choices: vec![ChoiceDelta {
-                                        index: 0,
-                                        delta: MessageDelta {
-                                            role: role.cloned(),
-                                            content: Some(content.clone()),
-                                        },
-                                        finish_reason: finish_reason.cloned(),
-                                    }],
-                                };
-
-                                let json_str = serde_json::to_string(&chunk)?;
-                                if json_str.len() > 1024 * 1024 {
-                                    return Ok(None);
-                                }
-                                return Ok(Some(json_str));
-                            }
-                        }
+        let delta = if tool_calls.as_ref().is_some_and(|calls| !calls.is_empty()) {
+            // Forward tool call fragments as-is, along with any content in the same delta
+            StreamDelta {
+                role,
+                content: content.filter(|c| !c.is_empty()),
+                tool_calls,
+            }
+        } else if content.as_ref().is_some_and(|c| !c.is_empty()) {
+            // Only process chunks with actual content
+            StreamDelta {
+                role,
+                content,
+                tool_calls: None,
+            }
+        } else if finish_reason.is_some() && content.is_none() {
+            // Handle finish_reason without content (end of stream)
+            StreamDelta::default()
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(within_chunk_limit(template.render(
+            &delta,
+            finish_reason,
+            json_data.len(),
+        )?)?))
+    }
+
+    pub fn list_models(&self) -> ModelList {
+        ModelList {
+            object: "list".to_string(),
+            data: self
+                .router
+                .model_ids()
+                .into_iter()
+                .map(|id| ModelInfo {
+                    id,
+                    object: "model".to_string(),
+                    created: self.started_at,
+                    owned_by: "loro".to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn drain_state(&self) -> Arc<DrainState> {
+        Arc::clone(&self.drain)
+    }
 
-                        // Handle finish_reason without content (end of stream)
-                        if finish_reason.is_some() && content.is_none() {
-                            let chunk = ChatCompletionChunk {
-                                id: format!("chatcmpl-{request_id}"),
-                                object: CHUNK_OBJECT.to_string(),
-                                created: chrono::Utc::now().timestamp(),
-                                model: model_name.to_string(),
-                                choices: vec![ChoiceDelta {
-                                    index: 0,
-                                    delta: MessageDelta {
-                                        role: None,
-                                        content: None,
-                                    },
-                                    finish_reason: finish_reason.cloned(),
-                                }],
-                            };
-
-                            // Optimize string formatting with safety checks
-                            let json_str = serde_json::to_string(&chunk)?;
-                            if json_str.len() > 1024 * 1024 {
-                                return Ok(None);
-                            }
-                            return Ok(Some(json_str));
+    pub fn is_draining(&self) -> bool {
+        self.drain.is_draining()
+    }
+
+    /// Returns the last background probe result when background probing is enabled,
+    /// otherwise probes both upstreams on demand.
+    pub async fn check_readiness(&self) -> ReadinessReport {
+        if self.config.readiness_probe_interval_secs > 0 {
+            if let Some(report) = self.readiness.read().await.clone() {
+                return report;
+            }
+        }
+        self.refresh_readiness().await
+    }
+
+    pub async fn refresh_readiness(&self) -> ReadinessReport {
+        let probe_timeout = Duration::from_secs(self.config.readiness_probe_timeout_secs);
+        let (small_model, large_model) = tokio::join!(
+            probe_upstream(&self.client, &self.config.small_model, probe_timeout),
+            probe_upstream(&self.client, &self.config.large_model, probe_timeout),
+        );
+        let report = ReadinessReport {
+            small_model,
+            large_model,
+        };
+        *self.readiness.write().await = Some(report.clone());
+        report
+    }
+
+    /// Starts periodic upstream probing if `readiness_probe_interval_secs` is non-zero.
+    /// The task stops once the server begins draining.
+    pub fn spawn_readiness_probe(self: &Arc<Self>) -> Option<JoinHandle<()>> {
+        let interval_secs = self.config.readiness_probe_interval_secs;
+        if interval_secs == 0 {
+            return None;
+        }
+
+        let service = Arc::clone(self);
+        Some(tokio::spawn(async move {
+            let drain = service.drain_state();
+            let mut ticker = tokio::time::interval(Duration::from_secs(interval_secs));
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        let report = service.refresh_readiness().await;
+                        if !report.large_model.healthy {
+                            warn!(
+                                "Large model probe failed: {}",
+                                report.large_model.error.as_deref().unwrap_or("unknown error")
+                            );
+                        }
+                        if !report.small_model.healthy {
+                            debug!(
+                                "Small model probe failed: {}",
+                                report.small_model.error.as_deref().unwrap_or("unknown error")
+                            );
                         }
                     }
-                }
-                Err(e) => {
-                    // Don't return an error, just log and skip this chunk
-                    debug!("Skipping malformed SSE chunk: {}, data: {}", e, json_data);
-                    return Ok(None);
+                    _ = drain.draining() => break,
                 }
             }
-        }
+        }))
+    }
 
-        // Skip non-data lines (like event: lines)
-        Ok(None)
+    /// Pre-renders the fallback quick responses in the background when a TTS
+    /// backend is configured with `precache` enabled.
+    pub fn spawn_speech_warmup(&self) -> Option<JoinHandle<()>> {
+        let synthesizer = Arc::clone(self.speech.as_ref()?);
+        if !synthesizer.config().precache {
+            return None;
+        }
+        Some(tokio::spawn(async move {
+            let cached = synthesizer.warm_cache().await;
+            info!("Pre-rendered audio for {} quick responses", cached);
+        }))
     }
 
     pub async fn get_metrics(&self) -> serde_json::Value {
@@ -754,17 +1489,151 @@ impl LoroService {
                 "quick_mode_requests": self.quick_stats.get_request_count(),
                 "direct_mode_requests": self.direct_stats.get_request_count(),
                 "avg_first_response_improvement": improvement
-            }
+            },
+            "usage": self.usage.snapshot(),
+            "cost": self.usage.cost_snapshot()
         })
     }
 
     pub async fn reset_metrics(&self) {
         self.quick_stats.reset();
         self.direct_stats.reset();
+        self.usage.reset();
         info!("Metrics reset successfully");
     }
 }
 
+/// The quick response acknowledges what the user said, so skip trailing tool results
+fn quick_response_source(messages: &[Message]) -> Option<&Message> {
+    messages
+        .iter()
+        .rev()
+        .find(|m| m.role == USER_ROLE)
+        .or_else(|| messages.last())
+}
+
+/// Rejects a rendered chunk over the size limit. Escaping can make a chunk longer than
+/// the text it was rendered from, so the check runs on the output.
+fn within_chunk_limit(chunk: String) -> Result<String> {
+    if chunk.len() > MAX_CHUNK_BYTES {
+        return Err(anyhow::Error::from(LoroError::StreamProcessing(format!(
+            "Response chunk of {} bytes exceeds {} bytes",
+            chunk.len(),
+            MAX_CHUNK_BYTES
+        ))));
+    }
+    Ok(chunk)
+}
+
+/// OpenAI `finish_reason` for Ollama's `done_reason`
+fn ollama_finish_reason(done_reason: Option<&str>, tool_calls_seen: bool) -> &'static str {
+    match done_reason {
+        _ if tool_calls_seen => "tool_calls",
+        Some("length") => "length",
+        _ => "stop",
+    }
+}
+
+/// Logs the token counts and timings of Ollama's final message
+fn log_ollama_metrics(resp: &OllamaResponse<'_>) {
+    let millis = |nanos: Option<u64>| nanos.map(|nanos| nanos as f64 / 1e6);
+    let tokens_per_second = match (resp.eval_count, resp.eval_duration) {
+        (Some(count), Some(nanos)) if nanos > 0 => Some(f64::from(count) * 1e9 / nanos as f64),
+        _ => None,
+    };
+    info!(
+        done_reason = resp.done_reason.as_deref(),
+        prompt_eval_count = resp.prompt_eval_count,
+        eval_count = resp.eval_count,
+        total_duration_ms = millis(resp.total_duration),
+        load_duration_ms = millis(resp.load_duration),
+        prompt_eval_duration_ms = millis(resp.prompt_eval_duration),
+        eval_duration_ms = millis(resp.eval_duration),
+        tokens_per_second,
+        "Ollama generation finished"
+    );
+}
+
+/// Translates to Ollama's message shape: plain-text `content`, base64 `images`, and
+/// tool call arguments as a JSON object rather than an encoded string
+fn ollama_message(message: &Message) -> serde_json::Value {
+    let mut value = json!({"role": message.role, "content": message.content.text()});
+    if message.content.has_images() {
+        let images: Vec<&str> = message
+            .content
+            .image_urls()
+            .filter_map(|url| {
+                // Ollama only takes inline base64 data; remote URLs are not fetched
+                let data = url
+                    .strip_prefix("data:")
+                    .and_then(|rest| rest.split_once(";base64,"))
+                    .map(|(_, data)| data);
+                if data.is_none() {
+                    warn!("Dropping non-data image URL for Ollama upstream");
+                }
+                data
+            })
+            .collect();
+        value["images"] = json!(images);
+    }
+    if let Some(tool_calls) = &message.tool_calls {
+        value["tool_calls"] = tool_calls
+            .iter()
+            .map(|call| {
+                let arguments = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+                    .unwrap_or_else(|_| json!({}));
+                json!({"function": {"name": call.function.name, "arguments": arguments}})
+            })
+            .collect();
+    }
+    value
+}
+
+/// Usage chunk (when requested) and `[DONE]`, after recording the reply's usage
+fn final_events(usage: ReplyUsage) -> Vec<Result<String>> {
+    let mut events: Vec<Result<String>> = usage.finish().into_iter().map(Ok).collect();
+    events.push(Ok("[DONE]".to_string()));
+    events
+}
+
+fn error_body(e: &anyhow::Error, expose_upstream: bool) -> ErrorBody {
+    let body = ErrorBody::from_error(e);
+    if expose_upstream {
+        body.with_upstream_details(e)
+    } else {
+        body
+    }
+}
+
+/// Base64 payload of an `audio` SSE event
+pub fn audio_event_data(segment: &AudioSegment) -> serde_json::Value {
+    let mut data = json!(segment);
+    if segment.error.is_none() {
+        data["data"] = json!(base64::engine::general_purpose::STANDARD.encode(&segment.audio));
+    }
+    data
+}
+
+/// Wraps chunk payloads as SSE `data:` events and audio segments as `audio` events;
+/// errors become OpenAI-style `{"error": {...}}` payloads.
+pub fn sse_events(
+    stream: ReplyStream,
+) -> impl Stream<Item = Result<axum::response::sse::Event, anyhow::Error>> {
+    stream.map(|event| match event {
+        Ok(ReplyEvent::Chunk(data)) => Ok(axum::response::sse::Event::default().data(data)),
+        Ok(ReplyEvent::Audio(segment)) => Ok(axum::response::sse::Event::default()
+            .event("audio")
+            .data(audio_event_data(&segment).to_string())),
+        Ok(ReplyEvent::Error(error)) => Ok(axum::response::sse::Event::default()
+            .data(json!({ "error": error }).to_string())),
+        Err(e) => {
+            error!("Stream error: {}", e);
+            Ok(axum::response::sse::Event::default()
+                .data(json!({ "error": ErrorBody::from_error(&e) }).to_string()))
+        }
+    })
+}
+
 // Retry helper function
 async fn execute_with_retry<F, T>(
     mut operation: F,
@@ -781,6 +1650,7 @@ where
         attempt += 1;
         match operation().await {
             Ok(result) => {
+                Span::current().record("retries", i64::from(attempt - 1));
                 if attempt > 1 {
                     info!("{} succeeded after {} attempts", operation_name, attempt);
                 }
@@ -810,5 +1680,30 @@ where
         }
     }
 
+    Span::current().record("retries", i64::from(max_retries));
     Err(last_error.unwrap())
 }
+
+/// Keeps the request span open (and entered while polling) until the stream ends.
+/// `time_to_first_token` closes with the first chunk; a `stream` span then lasts until completion.
+fn trace_stream(mut inner: ChunkStream, span: Span, first_token: Span) -> ChunkStream {
+    let mut stage = first_token;
+    let mut waiting_for_first = true;
+    Box::pin(stream::poll_fn(move |cx| {
+        let poll = {
+            let _request = span.enter();
+            let _stage = stage.enter();
+            inner.as_mut().poll_next(cx)
+        };
+        match &poll {
+            std::task::Poll::Ready(Some(_)) if waiting_for_first => {
+                waiting_for_first = false;
+                stage = info_span!(parent: &span, "stream");
+            }
+            // Close the stream span as soon as the reply is complete
+            std::task::Poll::Ready(None) => stage = Span::none(),
+            _ => {}
+        }
+        poll
+    }))
+}
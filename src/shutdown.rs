@@ -0,0 +1,133 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Tracks whether the server is draining and how many SSE streams are still active.
+#[derive(Debug)]
+pub struct DrainState {
+    draining: watch::Sender<bool>,
+    /// Set before draining starts so health checks fail while requests are still served
+    announced: AtomicBool,
+    active_streams: Arc<AtomicUsize>,
+}
+
+/// Held by a streaming response; decrements the active stream count when dropped.
+#[derive(Debug)]
+pub struct StreamGuard {
+    active_streams: Arc<AtomicUsize>,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.active_streams.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Default for DrainState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrainState {
+    pub fn new() -> Self {
+        let (draining, _) = watch::channel(false);
+        Self {
+            draining,
+            announced: AtomicBool::new(false),
+            active_streams: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// Whether `/health` and `/ready` should report draining; true from the
+    /// pre-drain announcement on, even though new requests are still accepted.
+    pub fn is_announced(&self) -> bool {
+        self.announced.load(Ordering::Acquire) || self.is_draining()
+    }
+
+    /// Reports draining to health checks for `delay`, then begins draining.
+    /// Gives load balancers time to stop routing here before connections are refused.
+    pub async fn drain_after(&self, delay: Duration) {
+        if !delay.is_zero() && !self.announced.swap(true, Ordering::AcqRel) {
+            info!(
+                "Reporting draining for {}s before refusing new connections",
+                delay.as_secs()
+            );
+            tokio::time::sleep(delay).await;
+        }
+        self.begin_draining();
+    }
+
+    pub fn begin_draining(&self) {
+        if !self.draining.send_replace(true) {
+            info!(
+                "Draining started, {} active stream(s) remaining",
+                self.active_streams()
+            );
+        }
+    }
+
+    pub fn active_streams(&self) -> usize {
+        self.active_streams.load(Ordering::Acquire)
+    }
+
+    pub fn track_stream(&self) -> StreamGuard {
+        self.active_streams.fetch_add(1, Ordering::AcqRel);
+        StreamGuard {
+            active_streams: Arc::clone(&self.active_streams),
+        }
+    }
+
+    /// Resolves once no stream is active.
+    pub async fn idle(&self) {
+        while self.active_streams() > 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Resolves once `begin_draining` has been called.
+    pub async fn draining(&self) {
+        let mut rx = self.draining.subscribe();
+        // The sender lives in `self`, so the channel cannot close while we wait
+        let _ = rx.wait_for(|draining| *draining).await;
+    }
+}
+
+/// Completes on SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to install Ctrl+C handler: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to install SIGTERM handler: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received SIGINT, starting graceful shutdown"),
+        _ = terminate => info!("Received SIGTERM, starting graceful shutdown"),
+    }
+}
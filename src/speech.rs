@@ -0,0 +1,413 @@
+use crate::config::SpeechConfig;
+use crate::errors::{ErrorBody, LoroError};
+use crate::models::{AudioOutputOptions, ChatCompletionChunk, RequestCategory};
+use crate::service::ChunkStream;
+use anyhow::Result;
+use bytes::Bytes;
+use futures::channel::mpsc;
+use futures::stream::{Stream, StreamExt};
+use futures::SinkExt;
+use reqwest::Client;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Characters that end a spoken sentence
+const SENTENCE_TERMINATORS: &[char] = &['。', '！', '？', '!', '?', '；', ';', '…', '\n'];
+/// Closing quotes and brackets that belong to the sentence before them
+const SENTENCE_CLOSERS: &[char] = &['"', '”', '’', '」', '』', ')', '）'];
+/// Clauses longer than this are split at the last comma so synthesis can start early
+const MAX_SEGMENT_CHARS: usize = 80;
+/// Sentences waiting for synthesis before the text stream waits on the TTS backend
+const SEGMENT_BUFFER: usize = 16;
+/// Reply events waiting for the client before synthesis and the text stream pause
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentSource {
+    /// The quick acknowledgement
+    Quick,
+    /// A sentence of the large model reply
+    Large,
+    /// The apology spoken after the reply failed
+    Apology,
+}
+
+/// One synthesized piece of the reply. `index` gives the playback order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioSegment {
+    pub index: usize,
+    pub source: SegmentSource,
+    pub text: String,
+    pub format: String,
+    /// Set instead of audio when synthesis failed, so clients can skip the gap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub audio: Bytes,
+}
+
+/// Item of a reply stream: a chunk payload as produced by the chat pipeline, audio,
+/// or a typed error reported in-band once the response has started.
+#[derive(Debug, Clone)]
+pub enum ReplyEvent {
+    Chunk(String),
+    Audio(AudioSegment),
+    Error(ErrorBody),
+}
+
+pub type ReplyStream = Pin<Box<dyn Stream<Item = Result<ReplyEvent>> + Send>>;
+
+/// Voice and format resolved from the request and the TTS_* defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Voice {
+    pub voice: String,
+    pub format: String,
+}
+
+pub struct SpeechSynthesizer {
+    client: Client,
+    config: SpeechConfig,
+    /// Pre-rendered audio of the canned quick responses, keyed by voice and text
+    cache: RwLock<HashMap<(Voice, String), Bytes>>,
+}
+
+impl SpeechSynthesizer {
+    pub fn new(client: Client, config: SpeechConfig) -> Self {
+        Self {
+            client,
+            config,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &SpeechConfig {
+        &self.config
+    }
+
+    pub fn default_voice(&self) -> Voice {
+        self.voice(None)
+    }
+
+    pub fn voice(&self, options: Option<&AudioOutputOptions>) -> Voice {
+        let options = options.cloned().unwrap_or_default();
+        Voice {
+            voice: options.voice.unwrap_or_else(|| self.config.voice.clone()),
+            format: options.format.unwrap_or_else(|| self.config.format.clone()),
+        }
+    }
+
+    /// Calls the OpenAI-compatible `/audio/speech` endpoint and returns the encoded audio.
+    pub async fn synthesize(&self, text: &str, voice: &Voice) -> Result<Bytes> {
+        let backend = &self.config.backend;
+        let mut request_builder = self
+            .client
+            .post(format!("{}/audio/speech", backend.base_url))
+            .json(&json!({
+                "model": backend.model_name,
+                "input": text,
+                "voice": voice.voice,
+                "response_format": voice.format,
+            }));
+        if backend.api_key.expose_secret() != "none" {
+            request_builder = request_builder.header(
+                "Authorization",
+                format!("Bearer {}", backend.api_key.expose_secret()),
+            );
+        }
+
+        let start = Instant::now();
+        let response = request_builder
+            .send()
+            .await
+            .map_err(|e| anyhow::Error::from(LoroError::HttpClient(e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(anyhow::Error::from(LoroError::ApiError {
+                provider: "speech".to_string(),
+                status: status.as_u16(),
+                message: error_text,
+            }));
+        }
+
+        let audio = response
+            .bytes()
+            .await
+            .map_err(|e| anyhow::Error::from(LoroError::HttpClient(e)))?;
+        debug!(
+            "Synthesized {} chars into {} bytes in {:.3}s",
+            text.chars().count(),
+            audio.len(),
+            start.elapsed().as_secs_f64()
+        );
+        Ok(audio)
+    }
+
+    /// Synthesizes every `RequestCategory` fallback phrase in the default voice and
+    /// keeps the audio in memory. Returns the number of cached phrases.
+    pub async fn warm_cache(&self) -> usize {
+        let voice = self.default_voice();
+        let start = Instant::now();
+        let renders: Vec<_> = RequestCategory::ALL
+            .iter()
+            .flat_map(|category| category.get_responses().iter().copied())
+            .map(|text| {
+                let voice = voice.clone();
+                async move {
+                    let result = self.synthesize(text, &voice).await;
+                    (text, result)
+                }
+            })
+            .collect();
+        let mut rendered =
+            futures::stream::iter(renders).buffer_unordered(self.config.max_concurrency);
+        let mut cached = 0;
+        while let Some((text, result)) = rendered.next().await {
+            match result {
+                Ok(audio) => {
+                    self.cache
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .insert((voice.clone(), text.to_string()), audio);
+                    cached += 1;
+                }
+                Err(e) => warn!("Failed to pre-render quick response '{}': {}", text, e),
+            }
+        }
+        debug!(
+            "Pre-rendered {} quick responses in {:.3}s",
+            cached,
+            start.elapsed().as_secs_f64()
+        );
+        cached
+    }
+
+    pub fn cached(&self, text: &str, voice: &Voice) -> Option<Bytes> {
+        self.cache
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(voice.clone(), text.to_string()))
+            .cloned()
+    }
+
+    async fn segment(
+        &self,
+        index: usize,
+        source: SegmentSource,
+        text: String,
+        voice: &Voice,
+    ) -> AudioSegment {
+        let result = match self.cached(&text, voice) {
+            Some(audio) => Ok(audio),
+            None => self.synthesize(&text, voice).await,
+        };
+        let (audio, error) = match result {
+            Ok(audio) => (audio, None),
+            Err(e) => {
+                warn!("Speech synthesis failed for segment {}: {}", index, e);
+                (Bytes::new(), Some(e.to_string()))
+            }
+        };
+        AudioSegment {
+            index,
+            source,
+            text,
+            format: voice.format.clone(),
+            error,
+            audio,
+        }
+    }
+}
+
+/// Cuts streamed text into sentences as soon as they are complete.
+#[derive(Debug, Default)]
+pub struct SentenceSplitter {
+    buffer: String,
+}
+
+impl SentenceSplitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends text and returns the sentences it completed.
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        self.buffer.push_str(text);
+        let mut sentences = Vec::new();
+        while let Some(end) = self.boundary() {
+            let sentence: String = self.buffer.drain(..end).collect();
+            let sentence = sentence.trim();
+            if !sentence.is_empty() {
+                sentences.push(sentence.to_string());
+            }
+        }
+        sentences
+    }
+
+    /// Returns whatever is left once the stream has ended.
+    pub fn finish(&mut self) -> Option<String> {
+        let rest = std::mem::take(&mut self.buffer);
+        let rest = rest.trim();
+        (!rest.is_empty()).then(|| rest.to_string())
+    }
+
+    fn boundary(&self) -> Option<usize> {
+        let mut chars = self.buffer.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            // A period only ends a sentence once we see the whitespace after it ("3.5" stays whole)
+            let ends = SENTENCE_TERMINATORS.contains(&c)
+                || (c == '.' && chars.peek().is_some_and(|(_, next)| next.is_whitespace()));
+            if ends {
+                let mut end = i + c.len_utf8();
+                while let Some(&(j, next)) = chars.peek() {
+                    if SENTENCE_TERMINATORS.contains(&next) || SENTENCE_CLOSERS.contains(&next) {
+                        end = j + next.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                return Some(end);
+            }
+        }
+
+        if self.buffer.chars().count() > MAX_SEGMENT_CHARS {
+            let end = match self.buffer.rfind(['，', ',', '、']) {
+                Some(pos) => pos + self.buffer[pos..].chars().next().map_or(1, char::len_utf8),
+                None => self
+                    .buffer
+                    .char_indices()
+                    .nth(MAX_SEGMENT_CHARS)
+                    .map_or(self.buffer.len(), |(i, _)| i),
+            };
+            return Some(end);
+        }
+        None
+    }
+}
+
+/// Aborts the speech pipeline when the client stops reading the reply.
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn chunk_text(payload: &str) -> Option<String> {
+    let chunk: ChatCompletionChunk = serde_json::from_str(payload).ok()?;
+    chunk
+        .choices
+        .into_iter()
+        .next()?
+        .delta
+        .content
+        .filter(|content| !content.is_empty())
+}
+
+/// Forwards the chunk stream unchanged and adds audio for the reply: the quick
+/// acknowledgement right away, then each completed sentence of the large model.
+/// Segments are synthesized concurrently but emitted in order; `[DONE]` is held
+/// back until the last segment has been sent. If the reply fails, `apology` is
+/// spoken once after the text received so far.
+pub fn speak(
+    mut chunks: ChunkStream,
+    quick_first: bool,
+    synthesizer: Arc<SpeechSynthesizer>,
+    voice: Voice,
+    mut apology: Option<String>,
+) -> ReplyStream {
+    let (mut out_tx, out_rx) = mpsc::channel::<Result<ReplyEvent>>(EVENT_BUFFER);
+    let (mut segment_tx, segment_rx) = mpsc::channel::<(SegmentSource, String)>(SEGMENT_BUFFER);
+    let max_concurrency = synthesizer.config.max_concurrency;
+
+    let mut audio_tx = out_tx.clone();
+    let audio = async move {
+        let mut segments = segment_rx
+            .enumerate()
+            .map(|(index, (source, text))| {
+                let synthesizer = Arc::clone(&synthesizer);
+                let voice = voice.clone();
+                async move { synthesizer.segment(index, source, text, &voice).await }
+            })
+            .buffered(max_concurrency);
+        while let Some(segment) = segments.next().await {
+            if audio_tx.send(Ok(ReplyEvent::Audio(segment))).await.is_err() {
+                break;
+            }
+        }
+    };
+
+    let mut text_tx = out_tx.clone();
+    let text = async move {
+        let mut splitter = SentenceSplitter::new();
+        let mut expects_quick = quick_first;
+        let mut done = None;
+        while let Some(item) = chunks.next().await {
+            match item {
+                Ok(payload) if payload == "[DONE]" => {
+                    done = Some(payload);
+                    break;
+                }
+                Ok(payload) => {
+                    if let Some(text) = chunk_text(&payload) {
+                        if std::mem::take(&mut expects_quick) {
+                            let _ = segment_tx.send((SegmentSource::Quick, text)).await;
+                        } else {
+                            for sentence in splitter.push(&text) {
+                                let _ = segment_tx.send((SegmentSource::Large, sentence)).await;
+                            }
+                        }
+                    }
+                    if text_tx.send(Ok(ReplyEvent::Chunk(payload))).await.is_err() {
+                        return (chunks, None);
+                    }
+                }
+                Err(e) => {
+                    if let Some(apology) = apology.take() {
+                        if let Some(rest) = splitter.finish() {
+                            let _ = segment_tx.send((SegmentSource::Large, rest)).await;
+                        }
+                        let _ = segment_tx.send((SegmentSource::Apology, apology)).await;
+                    }
+                    if text_tx.send(Err(e)).await.is_err() {
+                        return (chunks, None);
+                    }
+                }
+            }
+        }
+        if let Some(rest) = splitter.finish() {
+            let _ = segment_tx.send((SegmentSource::Large, rest)).await;
+        }
+        (chunks, done)
+    };
+
+    let handle = tokio::spawn(async move {
+        let ((chunks, done), ()) = futures::join!(text, audio);
+        if let Some(done) = done {
+            let _ = out_tx.send(Ok(ReplyEvent::Chunk(done))).await;
+        }
+        // The chunk stream holds the drain guard; release it only after the audio is out
+        drop(chunks);
+    });
+
+    let guard = AbortOnDrop(handle);
+    Box::pin(out_rx.map(move |event| {
+        let _guard = &guard;
+        event
+    }))
+}
+
+/// Adapts a plain chunk stream to a reply stream without audio.
+pub fn text_only(chunks: ChunkStream) -> ReplyStream {
+    Box::pin(chunks.map(|chunk| chunk.map(ReplyEvent::Chunk)))
+}
@@ -0,0 +1,215 @@
+use crate::{chunk::MAX_CHUNK_BYTES, errors::LoroError};
+use std::time::Duration;
+
+/// Largest line or event accepted from an upstream, matching the outgoing chunk limit
+pub const MAX_EVENT_BYTES: usize = MAX_CHUNK_BYTES;
+
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// A dispatched server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event` field, `message` when the event has none
+    pub event: String,
+    /// The `data` lines of the event, joined with `\n`
+    pub data: String,
+    /// The last `id` received so far on the stream, as the spec defines it
+    pub id: Option<String>,
+}
+
+/// A dispatched event borrowing the decoder's buffers, which are reused for the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SseEventRef<'a> {
+    pub event: &'a str,
+    pub data: &'a str,
+    pub id: Option<&'a str>,
+}
+
+impl SseEventRef<'_> {
+    pub fn to_owned(&self) -> SseEvent {
+        SseEvent {
+            event: self.event.to_string(),
+            data: self.data.to_string(),
+            id: self.id.map(String::from),
+        }
+    }
+}
+
+/// Incremental `text/event-stream` decoder following the WHATWG parsing rules.
+///
+/// Bytes are fed as they arrive from the network, split anywhere: lines are only
+/// decoded once complete, so multi-byte characters cut between chunks survive. Lines
+/// end with CRLF, LF or CR; comments are skipped; `event`, `data`, `id` and `retry` are
+/// interpreted. An event still open when the stream ends is discarded.
+pub struct SseDecoder {
+    /// Bytes of the current, incomplete line
+    line: Vec<u8>,
+    event: String,
+    data: String,
+    last_id: Option<String>,
+    retry: Option<Duration>,
+    /// The previous chunk ended with CR, so a leading LF finishes that line break
+    after_cr: bool,
+    /// Still at the start of the stream, where a byte order mark is dropped
+    at_start: bool,
+    max_bytes: usize,
+}
+
+impl Default for SseDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::with_limit(MAX_EVENT_BYTES)
+    }
+
+    /// A decoder that fails once a line or the data of one event exceeds `max_bytes`
+    pub fn with_limit(max_bytes: usize) -> Self {
+        Self {
+            line: Vec::new(),
+            event: String::new(),
+            data: String::new(),
+            last_id: None,
+            retry: None,
+            after_cr: false,
+            at_start: true,
+            max_bytes,
+        }
+    }
+
+    /// Reconnection time last sent by the server
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_id.as_deref()
+    }
+
+    /// Decodes the next piece of the stream and returns the events it completes
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<SseEvent>, LoroError> {
+        let mut events = Vec::new();
+        self.feed_with(chunk, |event| events.push(event.to_owned()))?;
+        Ok(events)
+    }
+
+    /// Like [`feed`](Self::feed), handing each completed event to `on_event` without
+    /// copying it out of the decoder
+    pub fn feed_with<F>(&mut self, mut chunk: &[u8], mut on_event: F) -> Result<(), LoroError>
+    where
+        F: FnMut(SseEventRef<'_>),
+    {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        if std::mem::take(&mut self.after_cr) && chunk[0] == b'\n' {
+            chunk = &chunk[1..];
+        }
+        while let Some(end) = memchr::memchr2(b'\n', b'\r', chunk) {
+            self.check_line_len(end)?;
+            if self.line.is_empty() {
+                self.process_line(&chunk[..end], &mut on_event)?;
+            } else {
+                let mut line = std::mem::take(&mut self.line);
+                line.extend_from_slice(&chunk[..end]);
+                self.process_line(&line, &mut on_event)?;
+                line.clear();
+                self.line = line;
+            }
+            let mut next = end + 1;
+            if chunk[end] == b'\r' {
+                match chunk.get(next) {
+                    Some(b'\n') => next += 1,
+                    Some(_) => {}
+                    None => self.after_cr = true,
+                }
+            }
+            chunk = &chunk[next..];
+        }
+        self.check_line_len(chunk.len())?;
+        self.line.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    fn check_line_len(&self, more: usize) -> Result<(), LoroError> {
+        if self.line.len() + more > self.max_bytes {
+            return Err(LoroError::StreamProcessing(format!(
+                "SSE line exceeds {} bytes",
+                self.max_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    fn process_line<F>(&mut self, line: &[u8], on_event: &mut F) -> Result<(), LoroError>
+    where
+        F: FnMut(SseEventRef<'_>),
+    {
+        let line = if std::mem::take(&mut self.at_start) {
+            line.strip_prefix(BOM).unwrap_or(line)
+        } else {
+            line
+        };
+        if line.is_empty() {
+            self.dispatch(on_event);
+            return Ok(());
+        }
+        if line[0] == b':' {
+            return Ok(());
+        }
+        let (field, value) = match memchr::memchr(b':', line) {
+            Some(colon) => {
+                let value = &line[colon + 1..];
+                (&line[..colon], value.strip_prefix(b" ").unwrap_or(value))
+            }
+            None => (line, &b""[..]),
+        };
+        let value = String::from_utf8_lossy(value);
+        match field {
+            b"event" => {
+                self.event.clear();
+                self.event.push_str(&value);
+            }
+            b"data" => {
+                if self.data.len() + value.len() + 1 > self.max_bytes {
+                    return Err(LoroError::StreamProcessing(format!(
+                        "SSE event exceeds {} bytes",
+                        self.max_bytes
+                    )));
+                }
+                self.data.push_str(&value);
+                self.data.push('\n');
+            }
+            b"id" if !value.contains('\0') => self.last_id = Some(value.into_owned()),
+            b"retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn dispatch<F>(&mut self, on_event: &mut F)
+    where
+        F: FnMut(SseEventRef<'_>),
+    {
+        if !self.data.is_empty() {
+            on_event(SseEventRef {
+                event: if self.event.is_empty() {
+                    "message"
+                } else {
+                    &self.event
+                },
+                data: &self.data[..self.data.len() - 1],
+                id: self.last_id.as_deref(),
+            });
+        }
+        self.event.clear();
+        self.data.clear();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::config::{OtlpProtocol, TelemetryConfig};
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{Protocol, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+/// OTLP/HTTP path for traces, appended to the collector base URL
+const TRACES_PATH: &str = "/v1/traces";
+
+pub fn traces_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with(TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", endpoint, TRACES_PATH)
+    }
+}
+
+/// Batching tracer provider exporting to the configured collector. Spans are sent from
+/// the SDK's own thread, so exporting never blocks request handling.
+pub fn tracer_provider(config: &TelemetryConfig) -> Result<SdkTracerProvider> {
+    let protocol = match config.protocol {
+        OtlpProtocol::HttpProtobuf => Protocol::HttpBinary,
+        OtlpProtocol::HttpJson => Protocol::HttpJson,
+    };
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_protocol(protocol)
+        .with_endpoint(traces_endpoint(&config.endpoint))
+        .build()
+        .context("Failed to create OTLP span exporter")?;
+
+    Ok(SdkTracerProvider::builder()
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .with_batch_exporter(exporter)
+        .build())
+}
+
+/// `tracing` layer turning spans (with their fields as attributes) into OTLP spans
+pub fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("loro"))
+}
+
+/// Exports the spans still buffered in the batch processor; called once on shutdown
+pub async fn shutdown(provider: Option<SdkTracerProvider>) {
+    let Some(provider) = provider else {
+        return;
+    };
+    // Shutdown blocks until the export thread is done
+    if let Ok(Err(e)) = tokio::task::spawn_blocking(move || provider.shutdown()).await {
+        tracing::warn!("Failed to flush traces on shutdown: {}", e);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::stats::{calculate_stats, LatencyStats};
+use futures::StreamExt;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::Instant;
+
+/// Client-side timings of one streamed chat completion, in seconds since the request
+/// was sent. Used by the `loro-replay` and `loro-bench` tools.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChatTiming {
+    pub status: u16,
+    /// First response body bytes
+    pub ttfb: Option<f64>,
+    /// The quick acknowledgement, when one was expected
+    pub quick_chunk: Option<f64>,
+    /// First content chunk produced by the large model
+    pub first_large_chunk: Option<f64>,
+    pub total: f64,
+    pub error: Option<String>,
+}
+
+impl ChatTiming {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Sends a streaming chat request to loro and times its chunks. With `expect_quick`
+/// the first content chunk is counted as the quick acknowledgement.
+pub async fn timed_chat(
+    client: &Client,
+    base_url: &str,
+    api_key: Option<&str>,
+    body: &Value,
+    expect_quick: bool,
+) -> ChatTiming {
+    let start = Instant::now();
+    let mut timing = ChatTiming::default();
+    let mut request = client
+        .post(format!(
+            "{}/v1/chat/completions",
+            base_url.trim_end_matches('/')
+        ))
+        .json(body);
+    if let Some(key) = api_key {
+        request = request.bearer_auth(key);
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => {
+            timing.total = start.elapsed().as_secs_f64();
+            timing.error = Some(e.to_string());
+            return timing;
+        }
+    };
+    timing.status = response.status().as_u16();
+    if !response.status().is_success() {
+        let text = response.text().await.unwrap_or_default();
+        timing.total = start.elapsed().as_secs_f64();
+        timing.error = Some(format!("HTTP {}: {}", timing.status, text.trim()));
+        return timing;
+    }
+
+    let mut body = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut awaiting_quick = expect_quick;
+    while let Some(bytes) = body.next().await {
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                timing.error = Some(e.to_string());
+                break;
+            }
+        };
+        let elapsed = start.elapsed().as_secs_f64();
+        timing.ttfb.get_or_insert(elapsed);
+        buffer.extend_from_slice(&bytes);
+
+        // Lines are split on bytes, so multi-byte characters are never cut
+        while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            let Some(payload) = std::str::from_utf8(&line)
+                .ok()
+                .and_then(|line| line.trim_end().strip_prefix("data:"))
+                .map(str::trim)
+            else {
+                continue;
+            };
+            let Ok(chunk) = serde_json::from_str::<Value>(payload) else {
+                continue;
+            };
+            if let Some(error) = chunk.get("error") {
+                timing.error = Some(
+                    error["message"]
+                        .as_str()
+                        .unwrap_or("stream error")
+                        .to_string(),
+                );
+                continue;
+            }
+            let delta = &chunk["choices"][0]["delta"];
+            let has_content = delta["content"].as_str().is_some_and(|c| !c.is_empty())
+                || delta
+                    .get("tool_calls")
+                    .is_some_and(|calls| !calls.is_null());
+            if !has_content {
+                continue;
+            }
+            if std::mem::take(&mut awaiting_quick) {
+                timing.quick_chunk = Some(elapsed);
+            } else if timing.first_large_chunk.is_none() {
+                timing.first_large_chunk = Some(elapsed);
+            }
+        }
+    }
+    timing.total = start.elapsed().as_secs_f64();
+    timing
+}
+
+/// Latency distribution of the successful requests in a run
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingSummary {
+    pub requests: usize,
+    pub errors: usize,
+    pub ttfb: LatencyStats,
+    pub quick_chunk: LatencyStats,
+    pub first_large_chunk: LatencyStats,
+    pub total: LatencyStats,
+}
+
+impl TimingSummary {
+    pub fn from_timings(timings: &[ChatTiming]) -> Self {
+        let ok: Vec<&ChatTiming> = timings.iter().filter(|t| t.is_success()).collect();
+        let collect = |field: fn(&ChatTiming) -> Option<f64>| -> Vec<f64> {
+            ok.iter().filter_map(|timing| field(timing)).collect()
+        };
+        Self {
+            requests: timings.len(),
+            errors: timings.len() - ok.len(),
+            ttfb: calculate_stats(&collect(|t| t.ttfb)),
+            quick_chunk: calculate_stats(&collect(|t| t.quick_chunk)),
+            first_large_chunk: calculate_stats(&collect(|t| t.first_large_chunk)),
+            total: calculate_stats(&collect(|t| Some(t.total))),
+        }
+    }
+}
+
+/// Plain-text table with one block of rows per labelled summary, latencies in ms
+pub fn format_table(summaries: &[(String, TimingSummary)]) -> String {
+    let mut out = format!(
+        "{:<10} {:<18} {:>8} {:>8} {:>8} {:>8} {:>8}\n",
+        "run", "metric", "avg", "min", "p50", "p95", "max"
+    );
+    for (label, summary) in summaries {
+        out.push_str(&format!(
+            "{:<10} {} requests, {} errors\n",
+            label, summary.requests, summary.errors
+        ));
+        let rows = [
+            ("ttfb", &summary.ttfb),
+            ("quick_chunk", &summary.quick_chunk),
+            ("first_large_chunk", &summary.first_large_chunk),
+            ("total", &summary.total),
+        ];
+        for (metric, stats) in rows {
+            let ms = |secs: f64| secs * 1000.0;
+            out.push_str(&format!(
+                "{:<10} {:<18} {:>8.1} {:>8.1} {:>8.1} {:>8.1} {:>8.1}\n",
+                "",
+                metric,
+                ms(stats.avg),
+                ms(stats.min),
+                ms(stats.p50),
+                ms(stats.p95),
+                ms(stats.max)
+            ));
+        }
+    }
+    out
+}
@@ -0,0 +1,272 @@
+use crate::models::{AudioOutputOptions, ChatCompletionRequest, Message};
+use crate::routing::QUICK_MODEL_ALIAS;
+use bytes::Bytes;
+use memchr::memmem;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Response of an OpenAI-compatible `/audio/transcriptions` call (`response_format=json`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcription {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+}
+
+/// One field of a `multipart/form-data` body.
+#[derive(Debug, Clone)]
+pub struct FormPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Bytes,
+}
+
+impl FormPart {
+    pub fn text(name: &str, value: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            filename: None,
+            content_type: None,
+            data: Bytes::copy_from_slice(value.as_bytes()),
+        }
+    }
+
+    pub fn file(name: &str, filename: &str, content_type: &str, data: Bytes) -> Self {
+        Self {
+            name: name.to_string(),
+            filename: Some(filename.to_string()),
+            content_type: Some(content_type.to_string()),
+            data,
+        }
+    }
+}
+
+/// Audio sent by a client, either as a multipart `file` field or as a raw `audio/*` body.
+#[derive(Debug, Clone)]
+pub struct AudioUpload {
+    pub audio: Bytes,
+    pub filename: String,
+    pub content_type: String,
+    /// Remaining form fields (or query parameters for raw uploads)
+    pub fields: HashMap<String, String>,
+}
+
+impl AudioUpload {
+    pub fn from_body(content_type: Option<&str>, body: Bytes) -> Result<Self, String> {
+        let content_type = content_type.unwrap_or("application/octet-stream");
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+
+        let upload = if mime == "multipart/form-data" {
+            let boundary = multipart_boundary(content_type)
+                .ok_or_else(|| "multipart/form-data body is missing a boundary".to_string())?;
+            let mut audio = None;
+            let mut fields = HashMap::new();
+            for part in parse_multipart(&body, &boundary)? {
+                if part.name == "file" {
+                    audio = Some(part);
+                } else {
+                    let value = String::from_utf8(part.data.to_vec())
+                        .map_err(|_| format!("Form field '{}' must be UTF-8 text", part.name))?;
+                    fields.insert(part.name, value);
+                }
+            }
+            let file = audio.ok_or_else(|| "Missing 'file' field with the audio".to_string())?;
+            Self {
+                filename: file.filename.unwrap_or_else(|| "audio".to_string()),
+                content_type: file
+                    .content_type
+                    .unwrap_or_else(|| "application/octet-stream".to_string()),
+                audio: file.data,
+                fields,
+            }
+        } else if mime.starts_with("audio/") || mime == "application/octet-stream" {
+            let extension = mime.strip_prefix("audio/").unwrap_or("bin");
+            Self {
+                audio: body,
+                filename: format!("audio.{}", extension),
+                content_type: mime,
+                fields: HashMap::new(),
+            }
+        } else {
+            return Err(format!(
+                "Unsupported content type '{}', expected multipart/form-data or audio/*",
+                mime
+            ));
+        };
+
+        if upload.audio.is_empty() {
+            return Err("Audio file cannot be empty".to_string());
+        }
+        Ok(upload)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.trim().is_empty())
+    }
+
+    fn parse_field<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.field(name)
+            .map(|value| {
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid value for '{}': {}", name, value))
+            })
+            .transpose()
+    }
+
+    /// Builds the chat request for a voice turn: optional `messages` history (JSON),
+    /// then the transcript as the user message. Settings mirror the chat request fields;
+    /// `modalities` is comma-separated and `voice` / `audio_format` configure spoken output.
+    pub fn chat_request(&self, transcript: &str) -> Result<ChatCompletionRequest, String> {
+        let mut messages: Vec<Message> = match self.field("messages") {
+            Some(raw) => serde_json::from_str(raw)
+                .map_err(|e| format!("'messages' must be a JSON array of messages: {}", e))?,
+            None => Vec::new(),
+        };
+        messages.push(Message {
+            role: "user".to_string(),
+            content: transcript.into(),
+            ..Default::default()
+        });
+
+        let defaults = ChatCompletionRequest::default();
+        Ok(ChatCompletionRequest {
+            model: self.field("model").unwrap_or(QUICK_MODEL_ALIAS).to_string(),
+            messages,
+            max_tokens: self.parse_field("max_tokens")?,
+            temperature: self
+                .parse_field("temperature")?
+                .unwrap_or(defaults.temperature),
+            disable_quick_response: self.parse_field("disable_quick_response")?.unwrap_or(false),
+            modalities: self.field("modalities").map(|modalities| {
+                modalities
+                    .split(',')
+                    .map(|modality| modality.trim().to_string())
+                    .collect()
+            }),
+            audio: (self.field("voice").is_some() || self.field("audio_format").is_some()).then(
+                || AudioOutputOptions {
+                    voice: self.field("voice").map(str::to_string),
+                    format: self.field("audio_format").map(str::to_string),
+                },
+            ),
+            ..defaults
+        })
+    }
+}
+
+/// Extracts the `boundary` parameter from a multipart content type.
+pub fn multipart_boundary(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.trim().split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| value.trim().trim_matches('"').to_string())
+            .filter(|boundary| !boundary.is_empty())
+    })
+}
+
+pub fn parse_multipart(body: &Bytes, boundary: &str) -> Result<Vec<FormPart>, String> {
+    let delimiter = format!("--{}", boundary);
+    let next_delimiter = format!("\r\n--{}", boundary);
+    let next_finder = memmem::Finder::new(next_delimiter.as_bytes());
+
+    let mut pos = memmem::find(body, delimiter.as_bytes())
+        .ok_or_else(|| "Multipart body does not contain the boundary".to_string())?
+        + delimiter.len();
+    let mut parts = Vec::new();
+
+    loop {
+        let rest = &body[pos..];
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+        if !rest.starts_with(b"\r\n") {
+            return Err("Malformed multipart boundary line".to_string());
+        }
+        pos += 2;
+
+        let end = next_finder
+            .find(&body[pos..])
+            .map(|offset| pos + offset)
+            .ok_or_else(|| "Unterminated multipart part".to_string())?;
+        let part = &body[pos..end];
+        let (header_block, data_start) = if part.starts_with(b"\r\n") {
+            (&part[..0], 2)
+        } else {
+            let header_end = memmem::find(part, b"\r\n\r\n")
+                .ok_or_else(|| "Multipart part is missing headers".to_string())?;
+            (&part[..header_end], header_end + 4)
+        };
+
+        let headers = std::str::from_utf8(header_block)
+            .map_err(|_| "Multipart headers must be UTF-8".to_string())?;
+        let mut name = None;
+        let mut filename = None;
+        let mut content_type = None;
+        for line in headers.split("\r\n") {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim();
+            if key.eq_ignore_ascii_case("content-disposition") {
+                for param in value.split(';').skip(1) {
+                    if let Some((param_key, param_value)) = param.trim().split_once('=') {
+                        let param_value = param_value.trim().trim_matches('"').to_string();
+                        match param_key.trim() {
+                            "name" => name = Some(param_value),
+                            "filename" => filename = Some(param_value),
+                            _ => {}
+                        }
+                    }
+                }
+            } else if key.eq_ignore_ascii_case("content-type") {
+                content_type = Some(value.trim().to_string());
+            }
+        }
+
+        parts.push(FormPart {
+            name: name.ok_or_else(|| "Multipart part is missing a field name".to_string())?,
+            filename,
+            content_type,
+            data: body.slice(pos + data_start..end),
+        });
+        pos = end + next_delimiter.len();
+    }
+}
+
+pub fn encode_multipart(boundary: &str, parts: &[FormPart]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(parts.iter().map(|part| part.data.len() + 128).sum());
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        let disposition = match &part.filename {
+            Some(filename) => format!(
+                "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
+                part.name,
+                filename.replace('"', "")
+            ),
+            None => format!("Content-Disposition: form-data; name=\"{}\"\r\n", part.name),
+        };
+        body.extend_from_slice(disposition.as_bytes());
+        if let Some(content_type) = &part.content_type {
+            body.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(&part.data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    body
+}
@@ -0,0 +1,336 @@
+use crate::config::ModelPrice;
+use crate::models::{Message, Usage};
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::sync::RwLock;
+
+/// Label used for requests without an `Authorization` header
+pub const ANONYMOUS_KEY: &str = "anonymous";
+
+/// Label shared by every client key missing from `CLIENT_API_KEYS`. Keys are not
+/// authenticated, so unknown ones are folded together to keep the totals bounded.
+pub const OTHER_KEY: &str = "other";
+
+/// Per-message overhead of the chat format (role, separators)
+const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3000..=0x303F     // CJK punctuation
+        | 0x3040..=0x30FF   // Hiragana, Katakana
+        | 0x3400..=0x4DBF   // CJK Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF   // Hangul syllables
+        | 0xF900..=0xFAFF   // CJK compatibility ideographs
+        | 0xFF00..=0xFFEF) // Full-width forms
+}
+
+/// Running character counts used to estimate tokens when the upstream reports none:
+/// one token per CJK character, one per four other non-space characters.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokenEstimator {
+    cjk: u32,
+    other: u32,
+}
+
+impl TokenEstimator {
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            if is_cjk(c) {
+                self.cjk += 1;
+            } else if !c.is_whitespace() {
+                self.other += 1;
+            }
+        }
+    }
+
+    pub fn tokens(&self) -> u32 {
+        self.cjk + self.other.div_ceil(4)
+    }
+}
+
+pub fn estimate_tokens(text: &str) -> u32 {
+    let mut estimator = TokenEstimator::default();
+    estimator.push(text);
+    estimator.tokens()
+}
+
+pub fn estimate_prompt_tokens(messages: &[Message]) -> u32 {
+    messages
+        .iter()
+        .map(|message| estimate_tokens(&message.content.text()) + MESSAGE_OVERHEAD_TOKENS)
+        .sum()
+}
+
+/// Token usage of one upstream call: the upstream's own count when it reports one,
+/// otherwise the local estimate.
+#[derive(Debug, Default)]
+pub struct UsageMeter {
+    reported: Option<Usage>,
+    prompt_estimate: u32,
+    completion: TokenEstimator,
+}
+
+impl UsageMeter {
+    pub fn new(prompt: &[Message]) -> Self {
+        Self {
+            prompt_estimate: estimate_prompt_tokens(prompt),
+            ..Default::default()
+        }
+    }
+
+    pub fn report(&mut self, usage: Usage) {
+        self.reported = Some(usage);
+    }
+
+    pub fn add_completion(&mut self, text: &str) {
+        self.completion.push(text);
+    }
+
+    /// Returns the usage and whether it was estimated locally
+    pub fn usage(&self) -> (Usage, bool) {
+        match self.reported {
+            Some(usage) => (usage, false),
+            None => (
+                Usage::new(self.prompt_estimate, self.completion.tokens()),
+                true,
+            ),
+        }
+    }
+}
+
+/// Identifies a client key in metrics without revealing it: `sk-…abcd`
+pub fn key_label(api_key: Option<&str>) -> String {
+    match api_key {
+        None => ANONYMOUS_KEY.to_string(),
+        Some(key) if key.chars().count() <= 8 => "…".to_string(),
+        Some(key) => {
+            let prefix: String = key.chars().take(3).collect();
+            let suffix: String = key
+                .chars()
+                .rev()
+                .take(4)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+                .collect();
+            format!("{}…{}", prefix, suffix)
+        }
+    }
+}
+
+/// A client key as usage is accounted: a digest of the full key, so configured keys
+/// that share a label are never merged, plus the label shown in metrics
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientKey {
+    digest: u64,
+    label: String,
+}
+
+impl ClientKey {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+/// Which part of a reply the usage belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyPart {
+    /// The small model's quick acknowledgement
+    QuickResponse,
+    /// The streamed answer (the large model, or the small one when degraded)
+    Answer,
+}
+
+impl ModelPrice {
+    /// Cost in USD of `usage` at this price
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (f64::from(usage.prompt_tokens) * self.input_per_1k
+            + f64::from(usage.completion_tokens) * self.output_per_1k)
+            / 1000.0
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    /// Requests counted with the local estimate because the upstream reported nothing
+    pub estimated_requests: u64,
+    /// USD at the configured price; 0 for models without one
+    pub cost_usd: f64,
+}
+
+/// Spend split by reply part, showing what the quick acknowledgements add to the bill
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CostTotals {
+    pub quick_response_usd: f64,
+    pub answer_usd: f64,
+    pub total_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageEntry {
+    pub api_key: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+#[derive(Debug, Default)]
+struct UsageState {
+    /// Keyed by key digest and model; the entry keeps the key label for reporting
+    totals: HashMap<(u64, String), UsageEntry>,
+    costs: CostTotals,
+    /// UTC day `daily_spend` belongs to; spend from earlier days is dropped
+    spend_day: Option<NaiveDate>,
+    /// Spend per key digest on `spend_day`
+    daily_spend: HashMap<u64, f64>,
+}
+
+/// Token and cost totals per client key and upstream model.
+#[derive(Debug, Default)]
+pub struct UsageCollector {
+    pricing: HashMap<String, ModelPrice>,
+    /// Randomly keyed per process, so digests cannot be matched to keys offline
+    hasher: RandomState,
+    /// Digests of the keys accounted on their own
+    client_keys: HashSet<u64>,
+    state: RwLock<UsageState>,
+}
+
+impl UsageCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_pricing(pricing: &[ModelPrice]) -> Self {
+        Self {
+            pricing: pricing
+                .iter()
+                .map(|price| (price.model.clone(), price.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Accounts each of `keys` on its own; any other key is counted as [`OTHER_KEY`]
+    pub fn with_client_keys<'a>(mut self, keys: impl IntoIterator<Item = &'a str>) -> Self {
+        self.client_keys = keys
+            .into_iter()
+            .map(|key| self.hasher.hash_one(Some(key)))
+            .collect();
+        self
+    }
+
+    /// Identifies `api_key` for `record` and `spent_today`
+    pub fn client_key(&self, api_key: Option<&str>) -> ClientKey {
+        let digest = self.hasher.hash_one(api_key);
+        if api_key.is_some() && !self.client_keys.contains(&digest) {
+            return ClientKey {
+                digest: self.hasher.hash_one(OTHER_KEY),
+                label: OTHER_KEY.to_string(),
+            };
+        }
+        ClientKey {
+            digest,
+            label: key_label(api_key),
+        }
+    }
+
+    /// Cost of `usage` on `model`; 0 when the model has no configured price
+    pub fn cost(&self, model: &str, usage: &Usage) -> f64 {
+        self.pricing
+            .get(model)
+            .map_or(0.0, |price| price.cost(usage))
+    }
+
+    pub fn record(
+        &self,
+        key: &ClientKey,
+        part: ReplyPart,
+        model: &str,
+        usage: &Usage,
+        estimated: bool,
+    ) {
+        let cost = self.cost(model, usage);
+        let mut state = match self.state.write() {
+            Ok(guard) => guard,
+            Err(e) => {
+                tracing::error!("UsageCollector lock poisoned: {}", e);
+                return;
+            }
+        };
+        let entry = &mut state
+            .totals
+            .entry((key.digest, model.to_string()))
+            .or_insert_with(|| UsageEntry {
+                api_key: key.label.clone(),
+                model: model.to_string(),
+                totals: UsageTotals::default(),
+            })
+            .totals;
+        entry.requests += 1;
+        entry.prompt_tokens += u64::from(usage.prompt_tokens);
+        entry.completion_tokens += u64::from(usage.completion_tokens);
+        entry.total_tokens += u64::from(usage.total_tokens);
+        if estimated {
+            entry.estimated_requests += 1;
+        }
+        entry.cost_usd += cost;
+
+        match part {
+            ReplyPart::QuickResponse => state.costs.quick_response_usd += cost,
+            ReplyPart::Answer => state.costs.answer_usd += cost,
+        }
+        state.costs.total_usd += cost;
+
+        let today = Utc::now().date_naive();
+        if state.spend_day != Some(today) {
+            state.spend_day = Some(today);
+            state.daily_spend.clear();
+        }
+        *state.daily_spend.entry(key.digest).or_default() += cost;
+    }
+
+    /// USD recorded for `key` since UTC midnight
+    pub fn spent_today(&self, key: &ClientKey) -> f64 {
+        let Ok(state) = self.state.read() else {
+            return 0.0;
+        };
+        if state.spend_day != Some(Utc::now().date_naive()) {
+            return 0.0;
+        }
+        state.daily_spend.get(&key.digest).copied().unwrap_or(0.0)
+    }
+
+    /// Entries sorted by key label, then model
+    pub fn snapshot(&self) -> Vec<UsageEntry> {
+        let Ok(state) = self.state.read() else {
+            return Vec::new();
+        };
+        let mut entries: Vec<UsageEntry> = state.totals.values().cloned().collect();
+        entries.sort_by(|a, b| (&a.api_key, &a.model).cmp(&(&b.api_key, &b.model)));
+        entries
+    }
+
+    pub fn cost_snapshot(&self) -> CostTotals {
+        self.state
+            .read()
+            .map(|state| state.costs.clone())
+            .unwrap_or_default()
+    }
+
+    /// Clears the reported totals; daily spend is kept so budgets still apply
+    pub fn reset(&self) {
+        if let Ok(mut state) = self.state.write() {
+            state.totals.clear();
+            state.costs = CostTotals::default();
+        }
+    }
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use axum::{body::Body, http::Request, middleware, routing::post, Router};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+use loro::{
+    access_log,
+    config::Config,
+    mock_upstream::{MockScript, ReplyScript},
+    service::LoroService,
+};
+
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sends each body through the middleware stack of `main` and returns the access lines
+async fn access_lines(bodies: Vec<Value>) -> Vec<Value> {
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_writer(move || writer.clone())
+        .with_max_level(tracing::Level::INFO)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let upstream = common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["今天是晴天。".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let config = Config {
+        log_format: "json".to_string(),
+        ..common::test_config(&upstream.openai_base_url())
+    };
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .layer(middleware::from_fn(access_log::access_log))
+        .layer(middleware::from_fn(loro::propagate_request_id))
+        .with_state(service);
+
+    for (i, body) in bodies.into_iter().enumerate() {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/v1/chat/completions")
+                    .header("content-type", "application/json")
+                    .header("x-request-id", format!("access-{}", i))
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response.into_body().collect().await.unwrap();
+    }
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    output
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter(|line| line["target"] == access_log::ACCESS_TARGET)
+        .collect()
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_access_line_per_request() {
+    let messages = json!([{"role": "user", "content": "今天天气怎么样"}]);
+    let lines = access_lines(vec![
+        json!({"model": "loro-quick", "messages": messages}),
+        json!({"model": "loro-direct", "messages": messages}),
+        json!({"model": "loro-quick", "messages": []}),
+    ])
+    .await;
+    assert_eq!(lines.len(), 3, "{:?}", lines);
+
+    let quick = &lines[0];
+    assert_eq!(quick["method"], "POST");
+    assert_eq!(quick["path"], "/v1/chat/completions");
+    assert_eq!(quick["status"], 200);
+    assert_eq!(quick["request_id"], "access-0");
+    assert_eq!(quick["mode"], "quick");
+    assert_eq!(quick["small_model"], "small-model");
+    assert_eq!(quick["large_model"], "large-model");
+    assert_eq!(quick["fallback"], false);
+    assert_eq!(quick["prompt_tokens"], 20);
+    assert_eq!(quick["completion_tokens"], 9);
+    let quick_ms = quick["quick_chunk_ms"].as_u64().unwrap();
+    let large_ms = quick["first_large_token_ms"].as_u64().unwrap();
+    assert!(quick_ms <= large_ms && large_ms <= quick["total_ms"].as_u64().unwrap());
+
+    let direct = &lines[1];
+    assert_eq!(direct["mode"], "direct");
+    assert_eq!(direct["large_model"], "large-model");
+    assert_eq!(direct["completion_tokens"], 6);
+    assert!(direct["first_large_token_ms"].is_u64());
+    // Fields that don't apply are left out
+    for field in ["small_model", "quick_chunk_ms", "fallback"] {
+        assert!(direct.get(field).is_none(), "{}", field);
+    }
+
+    let rejected = &lines[2];
+    assert_eq!(rejected["status"], 400);
+    assert_eq!(rejected["request_id"], "access-2");
+    assert!(rejected.get("mode").is_none());
+    assert!(rejected["total_ms"].is_u64());
+}
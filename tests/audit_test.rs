@@ -0,0 +1,229 @@
+mod common;
+
+use axum::{body::Body, http::Request, routing::post, Router};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tower::ServiceExt;
+
+use loro::{
+    audit::{redact, AuditLog, AuditSink, FileSink},
+    config::{AuditConfig, Config},
+    mock_upstream::{MockScript, ReplyScript},
+    service::LoroService,
+};
+
+async fn spawn_mock_upstream() -> String {
+    let upstream = common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["已记下您的电话".to_string(), "13800138000。".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    upstream.openai_base_url()
+}
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("loro-audit-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn audit_config(path: &Path, sample_rate: f64, max_file_bytes: u64) -> AuditConfig {
+    AuditConfig {
+        path: path.to_string_lossy().into_owned(),
+        sample_rate,
+        max_file_bytes,
+        retention_days: 7,
+    }
+}
+
+fn create_test_config(base_url: &str, audit: AuditConfig) -> Config {
+    Config {
+        audit: Some(audit),
+        ..common::test_config(base_url)
+    }
+}
+
+async fn send_chat(service: Arc<LoroService>, content: &str) {
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let body = json!({"model": "loro-quick", "messages": [{"role": "user", "content": content}]});
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .header("x-request-id", "audit-1")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    response.into_body().collect().await.unwrap();
+}
+
+/// Records are written from a background thread, so wait for them to show up
+async fn read_records(path: &Path, expected: usize) -> Vec<Value> {
+    for _ in 0..100 {
+        let records: Vec<Value> = fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        if records.len() >= expected {
+            return records;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!(
+        "expected {} audit record(s) in {}",
+        expected,
+        path.display()
+    );
+}
+
+#[test]
+fn test_redaction() {
+    let cases = [
+        ("邮箱是 zhang.san+work@example.com.cn。", "邮箱是 [EMAIL]。"),
+        ("我的电话13800138000，明天打", "我的电话[PHONE]，明天打"),
+        (
+            "call 138 0013 8000 or +86 138-0013-8000",
+            "call [PHONE] or [PHONE]",
+        ),
+        ("座机 010-62345678", "座机 [PHONE]"),
+        ("身份证号11010519491231002X", "身份证号[ID]"),
+        ("old id 110105491231002", "old id [ID]"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(redact(input), expected, "{}", input);
+    }
+
+    // Ordinary numbers and addresses without a domain are kept
+    for text in [
+        "2024年3月15日，价格12.5元",
+        "pi is 3.14159265358979",
+        "order A13800138000B",
+        "mention @someone here",
+        "12345678901234567890 items",
+    ] {
+        assert_eq!(redact(text), text);
+    }
+}
+
+#[test]
+fn test_file_sink_rotates_and_removes_expired_files() {
+    let dir = temp_dir();
+    let path = dir.join("audit.jsonl");
+    // An old rotated file from a previous run
+    let stale = dir.join("audit.jsonl.20200101T000000.000Z");
+    File::create(&stale)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60))
+        .unwrap();
+    let unrelated = dir.join("other.jsonl.20200101T000000.000Z");
+    File::create(&unrelated).unwrap();
+
+    let mut sink = FileSink::open(&audit_config(&path, 1.0, 64)).unwrap();
+    assert!(!stale.exists());
+    assert!(unrelated.exists());
+
+    let line = "x".repeat(40);
+    for _ in 0..3 {
+        sink.write_line(&line).unwrap();
+    }
+    let rotated: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("audit.jsonl."))
+        .collect();
+    assert_eq!(rotated.len(), 2, "{:?}", rotated);
+    assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", line));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_expired_files_are_removed_while_the_log_is_quiet() {
+    let dir = temp_dir();
+    let path = dir.join("audit.jsonl");
+    let sink = FileSink::open(&audit_config(&path, 1.0, 1024 * 1024)).unwrap();
+    // Expires after the log was opened, with nothing written since
+    let stale = dir.join("audit.jsonl.20200101T000000.000Z");
+    File::create(&stale)
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60))
+        .unwrap();
+
+    let _log = AuditLog::with_maintenance_interval(1.0, sink, Duration::from_millis(20));
+    for _ in 0..100 {
+        if !stale.exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!stale.exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_sampled_conversation_is_recorded_redacted() {
+    let base_url = spawn_mock_upstream().await;
+    let dir = temp_dir();
+    let path = dir.join("audit.jsonl");
+    let service = Arc::new(
+        LoroService::new(create_test_config(
+            &base_url,
+            audit_config(&path, 1.0, 1024 * 1024),
+        ))
+        .await
+        .unwrap(),
+    );
+
+    send_chat(service, "请打给 13912345678 或发邮件到 li@example.org").await;
+
+    let records = read_records(&path, 1).await;
+    let record = &records[0];
+    assert_eq!(record["request_id"], "audit-1");
+    assert_eq!(record["mode"], "quick");
+    assert_eq!(record["model"], "loro-quick");
+    assert_eq!(
+        record["messages"],
+        json!([{"role": "user", "content": "请打给 [PHONE] 或发邮件到 [EMAIL]"}])
+    );
+    assert_eq!(record["quick_response"], "好的，");
+    assert_eq!(record["reply"], "已记下您的电话[PHONE]。");
+    assert_eq!(record["completed"], true);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_unsampled_conversation_is_not_recorded() {
+    let base_url = spawn_mock_upstream().await;
+    let dir = temp_dir();
+    let path = dir.join("audit.jsonl");
+    let service = Arc::new(
+        LoroService::new(create_test_config(
+            &base_url,
+            audit_config(&path, 0.0, 1024),
+        ))
+        .await
+        .unwrap(),
+    );
+
+    send_chat(service, "你好").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "");
+    fs::remove_dir_all(dir).unwrap();
+}
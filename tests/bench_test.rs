@@ -0,0 +1,123 @@
+mod common;
+
+use axum::{routing::post, Router};
+use std::sync::Arc;
+use std::time::Duration;
+
+use loro::{
+    bench::{parse_corpus, run, to_csv, BenchOptions},
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+    timing::{format_table, ChatTiming, TimingSummary},
+};
+
+async fn spawn_loro(upstream: &MockUpstream) -> String {
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    common::serve(app).await
+}
+
+async fn start_upstream() -> MockUpstream {
+    common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["收到。".to_string()],
+            latency_ms: 40,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await
+}
+
+fn bench_options(base_url: &str) -> BenchOptions {
+    BenchOptions {
+        base_url: base_url.to_string(),
+        api_key: None,
+        model: "loro-voice-assistant".to_string(),
+        concurrency: 2,
+        rate: None,
+        duration: Duration::from_millis(300),
+        prompts: vec!["你好".to_string(), "几点了".to_string()],
+    }
+}
+
+#[test]
+fn test_parse_corpus() {
+    let corpus = "# greetings\n你好！\n\n  今天天气怎么样？  \r\n#\nbye\n";
+    assert_eq!(parse_corpus(corpus), ["你好！", "今天天气怎么样？", "bye"]);
+}
+
+#[test]
+fn test_csv_and_table() {
+    let ok = ChatTiming {
+        status: 200,
+        ttfb: Some(0.010),
+        quick_chunk: Some(0.0125),
+        first_large_chunk: Some(0.2),
+        total: 0.5,
+        error: None,
+    };
+    let failed = ChatTiming {
+        status: 502,
+        total: 0.05,
+        error: Some("HTTP 502: \"bad gateway\"".to_string()),
+        ..Default::default()
+    };
+    let runs = vec![("quick".to_string(), vec![ok, failed])];
+    assert_eq!(
+        to_csv(&runs),
+        "run,status,ttfb_ms,quick_chunk_ms,first_large_chunk_ms,total_ms,error\n\
+         quick,200,10.000,12.500,200.000,500.000,\n\
+         quick,502,,,,50.000,\"HTTP 502: \"\"bad gateway\"\"\"\n"
+    );
+
+    let summary = TimingSummary::from_timings(&runs[0].1);
+    assert_eq!((summary.requests, summary.errors), (2, 1));
+    let table = format_table(&[("quick".to_string(), summary)]);
+    assert!(table.contains("quick      2 requests, 1 errors"), "{}", table);
+    assert!(table.contains("first_large_chunk     200.0"), "{}", table);
+}
+
+#[tokio::test]
+async fn test_bench_compares_quick_and_direct_within_concurrency() {
+    let upstream = start_upstream().await;
+    let base_url = spawn_loro(&upstream).await;
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let options = bench_options(&base_url);
+
+    let quick = run(&client, &options, false).await;
+    let direct = run(&client, &options, true).await;
+    assert!(upstream.peak_streams() <= 2);
+
+    for timings in [&quick, &direct] {
+        // 300ms of 40ms+ requests, two at a time
+        assert!((2..=16).contains(&timings.len()), "{}", timings.len());
+        assert!(timings.iter().all(ChatTiming::is_success));
+    }
+    assert!(quick.iter().all(|t| t.quick_chunk.is_some()));
+    assert!(quick.iter().all(|t| t.first_large_chunk.is_some()));
+    assert!(direct.iter().all(|t| t.quick_chunk.is_none()));
+    assert!(direct.iter().all(|t| t.first_large_chunk.is_some()));
+}
+
+#[tokio::test]
+async fn test_bench_rate_limits_request_starts() {
+    let base_url = spawn_loro(&start_upstream().await).await;
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let options = BenchOptions {
+        concurrency: 8,
+        rate: Some(20.0),
+        duration: Duration::from_millis(260),
+        ..bench_options(&base_url)
+    };
+
+    // Starts at 0, 50, ..., 250ms
+    let timings = run(&client, &options, true).await;
+    assert!((5..=6).contains(&timings.len()), "{}", timings.len());
+}
@@ -0,0 +1,228 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use loro::{
+    config::{BudgetAction, BudgetConfig, Config, ModelPrice},
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+};
+use secrecy::Secret;
+
+async fn start_upstream() -> MockUpstream {
+    common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["今天是晴天。".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await
+}
+
+fn create_test_config(base_url: &str, budget: Option<BudgetConfig>) -> Config {
+    let price = |name: &str, per_1k: f64| ModelPrice {
+        model: name.to_string(),
+        input_per_1k: per_1k,
+        output_per_1k: per_1k,
+    };
+    let keys = [
+        "sk-test-abcd1234",
+        "sk-other-key-9999",
+        "sk-first-1234",
+        "sk-second-1234",
+    ];
+    Config {
+        pricing: vec![price("small-model", 1.0), price("large-model", 10.0)],
+        client_api_keys: keys.map(|key| Secret::new(key.to_string())).to_vec(),
+        budget,
+        ..common::test_config(base_url)
+    }
+}
+
+async fn send_chat(service: Arc<LoroService>, api_key: &str) -> (StatusCode, String) {
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let body =
+        json!({"model": "loro-quick", "messages": [{"role": "user", "content": "今天天气怎么样"}]});
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .header("authorization", format!("Bearer {}", api_key))
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+fn assert_usd(value: &Value, expected: f64) {
+    let actual = value.as_f64().unwrap();
+    assert!(
+        (actual - expected).abs() < 1e-9,
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+#[tokio::test]
+async fn test_costs_are_split_between_quick_response_and_answer() {
+    let upstream = start_upstream().await;
+    let base_url = upstream.openai_base_url();
+    let service = Arc::new(
+        LoroService::new(create_test_config(&base_url, None))
+            .await
+            .unwrap(),
+    );
+
+    let (status, _) = send_chat(Arc::clone(&service), "sk-test-abcd1234").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let metrics = service.get_metrics().await;
+    // 13 tokens at $1/1K and 16 tokens at $10/1K
+    assert_usd(&metrics["cost"]["quick_response_usd"], 0.013);
+    assert_usd(&metrics["cost"]["answer_usd"], 0.16);
+    assert_usd(&metrics["cost"]["total_usd"], 0.173);
+    let large = metrics["usage"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["model"] == "large-model")
+        .unwrap();
+    assert_usd(&large["cost_usd"], 0.16);
+}
+
+#[tokio::test]
+async fn test_budget_rejects_key_over_daily_limit() {
+    let upstream = start_upstream().await;
+    let base_url = upstream.openai_base_url();
+    let budget = BudgetConfig {
+        daily_limit_usd: 0.1,
+        action: BudgetAction::Reject,
+    };
+    let service = Arc::new(
+        LoroService::new(create_test_config(&base_url, Some(budget)))
+            .await
+            .unwrap(),
+    );
+
+    let (status, _) = send_chat(Arc::clone(&service), "sk-test-abcd1234").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = send_chat(Arc::clone(&service), "sk-test-abcd1234").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let error: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(error["error"]["code"], "budget_exceeded");
+    assert_eq!(error["error"]["type"], "rate_limit_error");
+
+    // Other keys have their own budget
+    let (status, _) = send_chat(Arc::clone(&service), "sk-other-key-9999").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_unconfigured_keys_share_one_budget() {
+    let upstream = start_upstream().await;
+    let base_url = upstream.openai_base_url();
+    let budget = BudgetConfig {
+        daily_limit_usd: 0.1,
+        action: BudgetAction::Reject,
+    };
+    let service = Arc::new(
+        LoroService::new(create_test_config(&base_url, Some(budget)))
+            .await
+            .unwrap(),
+    );
+
+    let (status, _) = send_chat(Arc::clone(&service), "sk-unknown-0001").await;
+    assert_eq!(status, StatusCode::OK);
+
+    // A fresh unknown key does not get a fresh budget
+    let (status, _) = send_chat(Arc::clone(&service), "sk-unknown-0002").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let (status, _) = send_chat(Arc::clone(&service), "sk-test-abcd1234").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_keys_sharing_a_label_have_separate_budgets() {
+    let upstream = start_upstream().await;
+    let base_url = upstream.openai_base_url();
+    let budget = BudgetConfig {
+        daily_limit_usd: 0.1,
+        action: BudgetAction::Reject,
+    };
+    let service = Arc::new(
+        LoroService::new(create_test_config(&base_url, Some(budget)))
+            .await
+            .unwrap(),
+    );
+
+    // Both keys are shown as sk-…1234
+    let (status, _) = send_chat(Arc::clone(&service), "sk-first-1234").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send_chat(Arc::clone(&service), "sk-second-1234").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send_chat(Arc::clone(&service), "sk-first-1234").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+
+    let metrics = service.get_metrics().await;
+    let large: Vec<&Value> = metrics["usage"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry["model"] == "large-model")
+        .collect();
+    assert_eq!(large.len(), 2);
+    assert!(large
+        .iter()
+        .all(|entry| entry["api_key"] == "sk-…1234" && entry["requests"] == 1));
+}
+
+#[tokio::test]
+async fn test_budget_degrades_to_small_model() {
+    let upstream = start_upstream().await;
+    let base_url = upstream.openai_base_url();
+    let budget = BudgetConfig {
+        daily_limit_usd: 0.1,
+        action: BudgetAction::Degrade,
+    };
+    let service = Arc::new(
+        LoroService::new(create_test_config(&base_url, Some(budget)))
+            .await
+            .unwrap(),
+    );
+
+    let (status, _) = send_chat(Arc::clone(&service), "sk-test-abcd1234").await;
+    assert_eq!(status, StatusCode::OK);
+    let before = upstream.calls().len();
+
+    let (status, body) = send_chat(Arc::clone(&service), "sk-test-abcd1234").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("今天是晴天。"));
+
+    // One streamed call to the small model, without a quick acknowledgement
+    let degraded_calls: Vec<_> = upstream.calls()[before..]
+        .iter()
+        .map(|call| (call.body["model"].clone(), call.is_stream()))
+        .collect();
+    assert_eq!(degraded_calls, vec![(json!("small-model"), true)]);
+}
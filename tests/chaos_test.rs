@@ -0,0 +1,270 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+
+use loro::{
+    chaos::truncate_line,
+    config::{ChaosConfig, Config, FaultConfig},
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+};
+
+const REPLY: &str = "今天是晴天，适合出门。";
+
+fn create_test_config(base_url: &str, chaos: ChaosConfig) -> Config {
+    Config {
+        small_model_timeout_secs: 1,
+        chaos: Some(chaos),
+        ..common::test_config(base_url)
+    }
+}
+
+async fn start_mock() -> MockUpstream {
+    MockUpstream::start(MockScript {
+        stream: ReplyScript {
+            token_interval_ms: 5,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+fn large(faults: FaultConfig) -> ChaosConfig {
+    ChaosConfig {
+        small_model: None,
+        large_model: Some(faults),
+    }
+}
+
+fn small(faults: FaultConfig) -> ChaosConfig {
+    ChaosConfig {
+        small_model: Some(faults),
+        large_model: None,
+    }
+}
+
+/// Sends a streaming chat request and returns the response status and SSE data payloads
+async fn chat(config: Config, disable_quick_response: bool) -> (StatusCode, Vec<String>) {
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let body = json!({
+        "model": "loro-voice-assistant",
+        "messages": [{"role": "user", "content": "今天天气怎么样？"}],
+        "stream": true,
+        "disable_quick_response": disable_quick_response
+    });
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let payloads = std::str::from_utf8(&bytes)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(str::to_string)
+        .collect();
+    (status, payloads)
+}
+
+fn contents(payloads: &[String]) -> Vec<String> {
+    payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+        .filter_map(|chunk| {
+            chunk["choices"][0]["delta"]["content"]
+                .as_str()
+                .map(String::from)
+        })
+        .filter(|content| !content.is_empty())
+        .collect()
+}
+
+fn error_code(payloads: &[String]) -> Option<String> {
+    payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+        .find_map(|payload| payload["error"]["code"].as_str().map(String::from))
+}
+
+#[test]
+fn test_truncate_line_keeps_line_break() {
+    let event = b"data: {\"a\":1}\n\ndata: {\"b\":2}\n\n";
+    assert_eq!(
+        &truncate_line(event, 9)[..],
+        b"data: {\"a\n\ndata: {\"b\":2}\n\n"
+    );
+    assert_eq!(
+        &truncate_line(event, 20)[..],
+        b"data: {\"a\":1}\n\ndata:\n\n"
+    );
+    // A cut on the line break itself removes nothing
+    assert_eq!(&truncate_line(event, 13)[..], &event[..]);
+    assert_eq!(&truncate_line(b"data: {", 3)[..], b"dat");
+}
+
+#[tokio::test]
+async fn test_injected_status_replaces_large_model_response() {
+    let mock = start_mock().await;
+    let chaos = large(FaultConfig {
+        error_probability: 1.0,
+        error_statuses: vec![429],
+        ..Default::default()
+    });
+    let (status, _) = chat(create_test_config(&mock.openai_base_url(), chaos), true).await;
+
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(mock.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_small_model_faults_fall_back_to_canned_quick_response() {
+    // An injected 503, and added latency past SMALL_MODEL_TIMEOUT_SECS
+    let faults = [
+        FaultConfig {
+            error_probability: 1.0,
+            error_statuses: vec![503],
+            ..Default::default()
+        },
+        FaultConfig {
+            latency_probability: 1.0,
+            latency_ms: 1500,
+            ..Default::default()
+        },
+    ];
+    for faults in faults {
+        let mock = start_mock().await;
+        let (status, payloads) = chat(
+            create_test_config(&mock.openai_base_url(), small(faults)),
+            false,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let contents = contents(&payloads);
+        assert!(!contents[0].is_empty());
+        assert_eq!(contents[1..].concat(), REPLY);
+        // Only the large model was reached
+        let calls = mock.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(calls[0].is_stream());
+    }
+}
+
+#[tokio::test]
+async fn test_small_model_body_faults_fall_back_to_canned_quick_response() {
+    let faults = [
+        FaultConfig {
+            disconnect_probability: 1.0,
+            ..Default::default()
+        },
+        FaultConfig {
+            truncate_probability: 1.0,
+            ..Default::default()
+        },
+    ];
+    for faults in faults {
+        let mock = MockUpstream::start(MockScript {
+            quick: ReplyScript {
+                tokens: vec!["喵".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let (status, payloads) = chat(
+            create_test_config(&mock.openai_base_url(), small(faults)),
+            false,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        // The small model answered, but its body never arrived intact
+        let contents = contents(&payloads);
+        assert_ne!(contents[0], "喵");
+        assert_eq!(contents[1..].concat(), REPLY);
+        assert_eq!(mock.calls().len(), 2);
+    }
+}
+
+#[tokio::test]
+async fn test_added_latency() {
+    let mock = start_mock().await;
+    let chaos = large(FaultConfig {
+        latency_probability: 1.0,
+        latency_ms: 200,
+        ..Default::default()
+    });
+    let start = Instant::now();
+    let (status, payloads) = chat(create_test_config(&mock.openai_base_url(), chaos), true).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(contents(&payloads).concat(), REPLY);
+}
+
+#[tokio::test]
+async fn test_dropped_connection_mid_stream() {
+    let mock = start_mock().await;
+    let chaos = large(FaultConfig {
+        disconnect_probability: 1.0,
+        ..Default::default()
+    });
+    let (status, payloads) = chat(create_test_config(&mock.openai_base_url(), chaos), true).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(contents(&payloads).is_empty());
+    assert_eq!(error_code(&payloads).as_deref(), Some("stream_interrupted"));
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+}
+
+#[tokio::test]
+async fn test_truncated_lines_lose_content_but_stream_completes() {
+    let mock = start_mock().await;
+    let chaos = large(FaultConfig {
+        truncate_probability: 1.0,
+        ..Default::default()
+    });
+    let (status, payloads) = chat(create_test_config(&mock.openai_base_url(), chaos), true).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(contents(&payloads).concat(), REPLY);
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+}
+
+#[tokio::test]
+async fn test_faults_of_other_role_are_not_applied() {
+    let mock = start_mock().await;
+    let chaos = small(FaultConfig {
+        disconnect_probability: 1.0,
+        truncate_probability: 1.0,
+        ..Default::default()
+    });
+    let (status, payloads) = chat(create_test_config(&mock.openai_base_url(), chaos), true).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contents(&payloads).concat(), REPLY);
+}
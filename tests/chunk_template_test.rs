@@ -0,0 +1,186 @@
+use loro::{
+    chunk::{ChunkTemplate, CHUNK_OBJECT},
+    models::{
+        ChatCompletionChunk, ChoiceDelta, FunctionCallDelta, MessageDelta, OllamaResponse,
+        OpenAIStreamChunk, StreamDelta, ToolCallDelta, Usage,
+    },
+    service::LoroService,
+    usage::UsageMeter,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::borrow::Cow;
+use std::cell::Cell;
+
+/// Counts allocations made on the current thread, so parallel tests don't interfere
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let value = f();
+    (value, ALLOCATIONS.with(Cell::get) - before)
+}
+
+fn chunk(delta: MessageDelta, finish_reason: Option<&str>) -> String {
+    serde_json::to_string(&ChatCompletionChunk {
+        id: "chatcmpl-rid".to_string(),
+        object: CHUNK_OBJECT.to_string(),
+        created: 1700000000,
+        model: "loro \"voice\"".to_string(),
+        choices: vec![ChoiceDelta {
+            index: 0,
+            delta,
+            finish_reason: finish_reason.map(String::from),
+        }],
+        usage: None,
+    })
+    .unwrap()
+}
+
+#[test]
+fn test_render_matches_serialized_chunk() {
+    let template = ChunkTemplate::with_created("rid", "loro \"voice\"", 1700000000);
+    let tool_calls = vec![ToolCallDelta {
+        index: 0,
+        id: Some("call_1".to_string()),
+        call_type: Some("function".to_string()),
+        function: Some(FunctionCallDelta {
+            name: Some("set_light".to_string()),
+            arguments: Some("{\"on\":true}".to_string()),
+        }),
+    }];
+
+    let rendered = template
+        .render(
+            &StreamDelta {
+                role: Some(Cow::Borrowed("assistant")),
+                content: Some(Cow::Borrowed("今天\n是\"晴天\"")),
+                tool_calls: None,
+            },
+            None,
+            0,
+        )
+        .unwrap();
+    let expected = chunk(
+        MessageDelta {
+            role: Some("assistant".to_string()),
+            content: Some("今天\n是\"晴天\"".to_string()),
+            tool_calls: None,
+        },
+        None,
+    );
+    assert_eq!(rendered, expected);
+
+    let rendered = template
+        .render(
+            &StreamDelta {
+                tool_calls: Some(tool_calls.clone()),
+                ..Default::default()
+            },
+            Some("tool_calls"),
+            0,
+        )
+        .unwrap();
+    let expected = chunk(
+        MessageDelta {
+            tool_calls: Some(tool_calls),
+            ..Default::default()
+        },
+        Some("tool_calls"),
+    );
+    assert_eq!(rendered, expected);
+
+    let rendered = template
+        .render(&StreamDelta::default(), Some("stop"), 0)
+        .unwrap();
+    assert_eq!(rendered, chunk(MessageDelta::default(), Some("stop")));
+}
+
+#[test]
+fn test_render_usage_matches_serialized_chunk() {
+    let template = ChunkTemplate::with_created("rid", "loro \"voice\"", 1700000000);
+    let usage = Usage::new(10, 4);
+    let expected = serde_json::to_string(&ChatCompletionChunk {
+        id: "chatcmpl-rid".to_string(),
+        object: CHUNK_OBJECT.to_string(),
+        created: 1700000000,
+        model: "loro \"voice\"".to_string(),
+        choices: Vec::new(),
+        usage: Some(usage),
+    })
+    .unwrap();
+    assert_eq!(template.render_usage(&usage).unwrap(), expected);
+}
+
+#[test]
+fn test_upstream_strings_are_borrowed_unless_escaped() {
+    let data = r#"{"id":"x","choices":[{"index":0,"delta":{"role":"assistant","content":"今天"},"finish_reason":null},{"index":1}]}"#;
+    let parsed: OpenAIStreamChunk = serde_json::from_str(data).unwrap();
+    let delta = parsed.choice.unwrap().delta.unwrap();
+    assert!(matches!(delta.content, Some(Cow::Borrowed("今天"))));
+    assert!(matches!(delta.role, Some(Cow::Borrowed("assistant"))));
+
+    let data = r#"{"choices":[{"delta":{"content":"今\n"},"finish_reason":"stop"}]}"#;
+    let parsed: OpenAIStreamChunk = serde_json::from_str(data).unwrap();
+    let choice = parsed.choice.unwrap();
+    assert!(
+        matches!(choice.delta.unwrap().content, Some(Cow::Owned(content)) if content == "今\n")
+    );
+    assert_eq!(choice.finish_reason.as_deref(), Some("stop"));
+
+    let parsed: OpenAIStreamChunk = serde_json::from_str(
+        r#"{"choices":[],"usage":{"prompt_tokens":1,"completion_tokens":2,"total_tokens":3}}"#,
+    )
+    .unwrap();
+    assert!(parsed.choice.is_none());
+    assert_eq!(parsed.usage.unwrap().total_tokens, 3);
+}
+
+#[test]
+fn test_ollama_strings_are_borrowed_unless_escaped() {
+    let line = r#"{"model":"qwen","created_at":"2024-01-01","message":{"role":"assistant","content":"今天"},"done":true,"done_reason":"stop"}"#;
+    let parsed: OllamaResponse = serde_json::from_str(line).unwrap();
+    assert!(matches!(parsed.message.content, Cow::Borrowed("今天")));
+    assert!(matches!(parsed.done_reason, Some(Cow::Borrowed("stop"))));
+
+    let line = r#"{"model":"qwen","created_at":"2024-01-01","message":{"role":"assistant","content":"今\n"},"done":false}"#;
+    let parsed: OllamaResponse = serde_json::from_str(line).unwrap();
+    assert!(matches!(parsed.message.content, Cow::Owned(content) if content == "今\n"));
+}
+
+#[test]
+fn test_content_delta_costs_one_allocation() {
+    let template = ChunkTemplate::with_created("rid", "loro-voice-assistant", 1700000000);
+    let mut meter = UsageMeter::default();
+    let data = r#"{"id":"chatcmpl-123","object":"chat.completion.chunk","created":1700000000,"model":"deepseek-chat","system_fingerprint":"fp_1","choices":[{"index":0,"delta":{"content":"适合出门"},"logprobs":null,"finish_reason":null}]}"#;
+
+    let (payload, count) =
+        allocations(|| LoroService::process_sse_data(data, &template, &mut meter).unwrap());
+    // Only the payload handed to the client is allocated
+    assert_eq!(count, 1);
+    let payload: serde_json::Value = serde_json::from_str(&payload.unwrap()).unwrap();
+    assert_eq!(payload["choices"][0]["delta"]["content"], "适合出门");
+    assert_eq!(payload["model"], "loro-voice-assistant");
+}
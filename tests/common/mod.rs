@@ -0,0 +1,66 @@
+//! Helpers shared by the integration tests; each test crate uses a subset.
+#![allow(dead_code)]
+
+use axum::Router;
+use secrecy::Secret;
+use tokio::net::TcpListener;
+
+use loro::{
+    config::{Config, ModelConfig},
+    mock_upstream::{MockScript, MockUpstream},
+};
+
+pub fn model(base_url: &str, name: &str) -> ModelConfig {
+    ModelConfig {
+        api_key: Secret::new("none".to_string()),
+        base_url: base_url.to_string(),
+        model_name: name.to_string(),
+    }
+}
+
+/// Both models at `base_url` without retries; every optional feature is off
+pub fn test_config(base_url: &str) -> Config {
+    Config {
+        host: "127.0.0.1".to_string(),
+        port: 3000,
+        log_level: "info".to_string(),
+        small_model: model(base_url, "small-model"),
+        large_model: model(base_url, "large-model"),
+        http_timeout_secs: 30,
+        small_model_timeout_secs: 5,
+        max_retries: 0,
+        stats_max_entries: 1000,
+        shutdown_drain_timeout_secs: 30,
+        shutdown_pre_drain_delay_secs: 0,
+        readiness_probe_interval_secs: 0,
+        readiness_probe_timeout_secs: 2,
+        model_routes: Vec::new(),
+        transcription: None,
+        max_audio_upload_bytes: 26214400,
+        speech: None,
+        error_apology: None,
+        expose_upstream_errors: false,
+        pricing: Vec::new(),
+        client_api_keys: Vec::new(),
+        budget: None,
+        telemetry: None,
+        log_format: "text".to_string(),
+        access_log: true,
+        audit: None,
+        chaos: None,
+    }
+}
+
+pub async fn mock_upstream(script: MockScript) -> MockUpstream {
+    MockUpstream::start(script).await.unwrap()
+}
+
+/// Serves `app` on an ephemeral port and returns its base URL
+pub async fn serve(app: Router) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+    format!("http://{}", addr)
+}
@@ -1,8 +1,27 @@
-use loro::config::{Config, ModelConfig};
+mod common;
+
+use loro::config::{
+    AuditConfig, BudgetAction, BudgetConfig, ChaosConfig, Config, FaultConfig, ModelConfig, ModelPrice,
+};
 use secrecy::Secret;
 use std::env;
 use serial_test::serial;
 
+/// Passes validation; tests break one setting at a time
+fn valid_config() -> Config {
+    let model = |name: &str| ModelConfig {
+        api_key: Secret::new("valid-key".to_string()),
+        base_url: "https://api.example.com/v1".to_string(),
+        model_name: name.to_string(),
+    };
+    Config {
+        small_model: model("small-model"),
+        large_model: model("large-model"),
+        max_retries: 3,
+        ..common::test_config("https://api.example.com/v1")
+    }
+}
+
 #[tokio::test]
 #[serial]
 async fn test_config_from_env_missing_keys() {
@@ -43,6 +62,9 @@ async fn test_config_default_values() {
     env::remove_var("HOST");
     env::remove_var("PORT");
     env::remove_var("LOG_LEVEL");
+    env::remove_var("LOG_FORMAT");
+    env::remove_var("ACCESS_LOG");
+    env::remove_var("SHUTDOWN_PRE_DRAIN_DELAY_SECS");
     
     let result = Config::from_env();
     assert!(result.is_ok());
@@ -51,6 +73,9 @@ async fn test_config_default_values() {
     assert_eq!(config.host, "0.0.0.0");  // Default host
     assert_eq!(config.port, 8000);       // Default port
     assert_eq!(config.log_level, "info"); // Default log level
+    assert_eq!(config.log_format, "text");
+    assert!(config.access_log);
+    assert_eq!(config.shutdown_pre_drain_delay_secs, 0);
 }
 
 #[tokio::test]
@@ -84,23 +109,9 @@ fn test_model_config_debug_hides_secret() {
 #[test] 
 fn test_config_clone() {
     let config = Config {
-        host: "127.0.0.1".to_string(),
         port: 8080,
         log_level: "debug".to_string(),
-        small_model: ModelConfig {
-            api_key: Secret::new("small-key".to_string()),
-            base_url: "https://small.api.com/v1".to_string(),
-            model_name: "small-model".to_string(),
-        },
-        large_model: ModelConfig {
-            api_key: Secret::new("large-key".to_string()),
-            base_url: "https://large.api.com/v1".to_string(),
-            model_name: "large-model".to_string(),
-        },
-        http_timeout_secs: 30,
-        small_model_timeout_secs: 10,
-        max_retries: 3,
-        stats_max_entries: 1000,
+        ..valid_config()
     };
     
     let cloned_config = config.clone();
@@ -111,25 +122,8 @@ fn test_config_clone() {
 
 #[test]
 fn test_config_validation_empty_api_key() {
-    let config = Config {
-        host: "127.0.0.1".to_string(),
-        port: 8080,
-        log_level: "debug".to_string(),
-        small_model: ModelConfig {
-            api_key: Secret::new("".to_string()), // Empty key
-            base_url: "https://api.example.com/v1".to_string(),
-            model_name: "test-model".to_string(),
-        },
-        large_model: ModelConfig {
-            api_key: Secret::new("valid-key".to_string()),
-            base_url: "https://api.example.com/v1".to_string(),
-            model_name: "test-model".to_string(),
-        },
-        http_timeout_secs: 30,
-        small_model_timeout_secs: 10,
-        max_retries: 3,
-        stats_max_entries: 1000,
-    };
+    let mut config = valid_config();
+    config.small_model.api_key = Secret::new("".to_string()); // Empty key
     
     let result = config.validate();
     assert!(result.is_err());
@@ -137,25 +131,8 @@ fn test_config_validation_empty_api_key() {
 
 #[test]
 fn test_config_validation_invalid_url() {
-    let config = Config {
-        host: "127.0.0.1".to_string(),
-        port: 8080,
-        log_level: "debug".to_string(),
-        small_model: ModelConfig {
-            api_key: Secret::new("valid-key".to_string()),
-            base_url: "invalid-url".to_string(), // Invalid URL
-            model_name: "test-model".to_string(),
-        },
-        large_model: ModelConfig {
-            api_key: Secret::new("valid-key".to_string()),
-            base_url: "https://api.example.com/v1".to_string(),
-            model_name: "test-model".to_string(),
-        },
-        http_timeout_secs: 30,
-        small_model_timeout_secs: 10,
-        max_retries: 3,
-        stats_max_entries: 1000,
-    };
+    let mut config = valid_config();
+    config.small_model.base_url = "invalid-url".to_string(); // Invalid URL
     
     let result = config.validate();
     assert!(result.is_err());
@@ -164,25 +141,7 @@ fn test_config_validation_invalid_url() {
 #[test]
 fn test_config_validation_timeout_ranges() {
     // Test invalid timeout values
-    let mut config = Config {
-        host: "127.0.0.1".to_string(),
-        port: 8080,
-        log_level: "debug".to_string(),
-        small_model: ModelConfig {
-            api_key: Secret::new("valid-key".to_string()),
-            base_url: "https://api.example.com/v1".to_string(),
-            model_name: "test-model".to_string(),
-        },
-        large_model: ModelConfig {
-            api_key: Secret::new("valid-key".to_string()),
-            base_url: "https://api.example.com/v1".to_string(),
-            model_name: "test-model".to_string(),
-        },
-        http_timeout_secs: 30,
-        small_model_timeout_secs: 10,
-        max_retries: 3,
-        stats_max_entries: 1000,
-    };
+    let mut config = valid_config();
     
     // Test invalid http timeout (too low)
     config.http_timeout_secs = 1;
@@ -195,4 +154,123 @@ fn test_config_validation_timeout_ranges() {
     // Test valid timeout
     config.http_timeout_secs = 30;
     assert!(config.validate().is_ok());
+
+    // Log format is text or json, in any case
+    config.log_format = "JSON".to_string();
+    assert!(config.validate().is_ok());
+    config.log_format = "logfmt".to_string();
+    assert!(config.validate().is_err());
+    config.log_format = "text".to_string();
+
+    // The pre-drain delay is capped so shutdown cannot stall for long
+    config.shutdown_pre_drain_delay_secs = 61;
+    assert!(config.validate().is_err());
+    config.shutdown_pre_drain_delay_secs = 5;
+    assert!(config.validate().is_ok());
+
+    // Audit sampling is a fraction
+    let audit = AuditConfig {
+        path: "/var/log/loro/audit.jsonl".to_string(),
+        sample_rate: 0.1,
+        max_file_bytes: 1024,
+        retention_days: 30,
+    };
+    config.audit = Some(audit.clone());
+    assert!(config.validate().is_ok());
+    config.audit = Some(AuditConfig {
+        sample_rate: 1.5,
+        ..audit.clone()
+    });
+    assert!(config.validate().is_err());
+    config.audit = Some(AuditConfig {
+        max_file_bytes: 0,
+        ..audit
+    });
+    assert!(config.validate().is_err());
+    config.audit = None;
+
+    // Chaos probabilities are fractions and injected statuses are 429 or 5xx
+    let faults = FaultConfig {
+        error_probability: 0.2,
+        ..Default::default()
+    };
+    config.chaos = Some(ChaosConfig {
+        small_model: None,
+        large_model: Some(faults.clone()),
+    });
+    assert!(config.validate().is_ok());
+    config.chaos.as_mut().unwrap().large_model = Some(FaultConfig {
+        truncate_probability: 1.1,
+        ..faults.clone()
+    });
+    assert!(config.validate().is_err());
+    config.chaos.as_mut().unwrap().large_model = Some(FaultConfig {
+        error_statuses: vec![503, 404],
+        ..faults.clone()
+    });
+    assert!(config.validate().is_err());
+    config.chaos.as_mut().unwrap().large_model = Some(FaultConfig {
+        error_statuses: Vec::new(),
+        ..faults
+    });
+    assert!(config.validate().is_err());
+}
+
+#[tokio::test]
+#[serial]
+async fn test_config_chaos_from_env() {
+    env::set_var("SMALL_MODEL_API_KEY", "test-small-key");
+    env::set_var("LARGE_MODEL_API_KEY", "test-large-key");
+
+    env::remove_var("CHAOS");
+    assert!(Config::from_env().unwrap().chaos.is_none());
+
+    env::set_var(
+        "CHAOS",
+        r#"{"large_model": {"disconnect_probability": 0.05, "latency_probability": 0.5, "latency_ms": 800}}"#,
+    );
+    let chaos = Config::from_env().unwrap().chaos.unwrap();
+    assert!(chaos.small_model.is_none());
+    let faults = chaos.large_model.unwrap();
+    assert_eq!(faults.disconnect_probability, 0.05);
+    assert_eq!(faults.latency_ms, 800);
+    assert_eq!(faults.error_probability, 0.0);
+    assert_eq!(faults.error_statuses, [500, 502, 503, 429]);
+
+    // Typos are rejected rather than silently injecting nothing
+    env::set_var("CHAOS", r#"{"large_model": {"disconect_probability": 0.05}}"#);
+    assert!(Config::from_env().is_err());
+    env::remove_var("CHAOS");
+}
+
+#[test]
+fn test_config_validation_pricing_and_budget() {
+    let mut config = Config {
+        budget: Some(BudgetConfig {
+            daily_limit_usd: 5.0,
+            action: BudgetAction::Reject,
+        }),
+        ..valid_config()
+    };
+
+    // A budget needs prices to count against
+    assert!(config.validate().is_err());
+
+    config.pricing = vec![ModelPrice {
+        model: "large-model".to_string(),
+        input_per_1k: 0.001,
+        output_per_1k: 0.002,
+    }];
+    assert!(config.validate().is_ok());
+
+    config.pricing[0].output_per_1k = -1.0;
+    assert!(config.validate().is_err());
+    config.pricing[0].output_per_1k = 0.002;
+
+    config.pricing.push(config.pricing[0].clone());
+    assert!(config.validate().is_err());
+    config.pricing.pop();
+
+    config.budget.as_mut().unwrap().daily_limit_usd = 0.0;
+    assert!(config.validate().is_err());
 }
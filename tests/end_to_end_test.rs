@@ -3,7 +3,11 @@ use axum::{
     http::{Request, StatusCode},
     Router,
 };
-use loro::{config::Config, models::*, service::LoroService};
+use loro::{
+    config::Config,
+    models::*,
+    service::{LoroService, RequestContext},
+};
 use serde_json::json;
 use std::sync::Arc;
 use tower::util::ServiceExt;
@@ -182,7 +186,8 @@ async fn test_request_validation_edge_cases() {
     for (input, expected) in test_cases {
         let message = Message {
             role: "user".to_string(),
-            content: input.to_string(),
+            content: input.into(),
+            ..Default::default()
         };
 
         let category = message.categorize();
@@ -341,25 +346,29 @@ async fn test_full_server_integration() {
             model: "test-model".to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "你好".to_string(), // Greeting
+                content: "你好".into(), // Greeting
+                ..Default::default()
             }],
             max_tokens: Some(50),
             temperature: 0.7,
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         },
         ChatCompletionRequest {
             model: "test-model".to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "What is the weather like?".to_string(), // Question
+                content: "What is the weather like?".into(), // Question
+                ..Default::default()
             }],
             max_tokens: Some(100),
             temperature: 0.5,
             stream: true,
             stop: None,
             disable_quick_response: true, // Test direct mode
+            ..Default::default()
         },
     ];
 
@@ -368,7 +377,7 @@ async fn test_full_server_integration() {
         let service_clone = service.clone();
         let result = tokio::spawn(async move {
             // These will fail due to no real API endpoints, but shouldn't panic
-            let _result = service_clone.chat_completion(request).await;
+            let _result = service_clone.chat_completion(request, &RequestContext::default()).await;
             i
         })
         .await;
@@ -1,3 +1,5 @@
+mod common;
+
 use axum::{
     body::Body,
     http::{Request, StatusCode},
@@ -10,32 +12,10 @@ use std::sync::Arc;
 use tower::ServiceExt;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 
-use loro::{
-    config::{Config, ModelConfig},
-    service::LoroService,
-};
-use secrecy::Secret;
+use loro::service::LoroService;
 
 async fn create_test_app() -> Router {
-    let config = Config {
-        host: "127.0.0.1".to_string(),
-        port: 3000,
-        log_level: "info".to_string(),
-        small_model: ModelConfig {
-            api_key: Secret::new("test-key-small".to_string()),
-            base_url: "https://api.openai.com/v1".to_string(),
-            model_name: "gpt-3.5-turbo".to_string(),
-        },
-        large_model: ModelConfig {
-            api_key: Secret::new("test-key-large".to_string()),
-            base_url: "https://api.openai.com/v1".to_string(),
-            model_name: "gpt-4".to_string(),
-        },
-        http_timeout_secs: 30,
-        small_model_timeout_secs: 10,
-        max_retries: 3,
-        stats_max_entries: 1000,
-    };
+    let config = common::test_config("https://api.openai.com/v1");
 
     let loro_service = Arc::new(LoroService::new(config).await.unwrap());
 
@@ -43,6 +23,7 @@ async fn create_test_app() -> Router {
         .route("/", get(loro::root))
         .route("/health", get(loro::health))
         .route("/v1/chat/completions", post(loro::chat_completions))
+        .route("/v1/models", get(loro::list_models))
         .route("/metrics", get(loro::get_metrics))
         .route("/metrics/reset", post(loro::reset_metrics))
         .layer(TraceLayer::new_for_http())
@@ -88,6 +69,34 @@ async fn test_health_endpoint() {
     assert_eq!(json["status"].as_str().unwrap(), "healthy");
 }
 
+#[tokio::test]
+async fn test_models_endpoint() {
+    let app = create_test_app().await;
+
+    let request = Request::builder()
+        .uri("/v1/models")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["object"], "list");
+    let ids: Vec<&str> = json["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["id"].as_str().unwrap())
+        .collect();
+    assert!(ids.contains(&"loro-quick"));
+    assert!(ids.contains(&"loro-direct"));
+    assert_eq!(json["data"][0]["object"], "model");
+    assert_eq!(json["data"][0]["owned_by"], "loro");
+}
+
 #[tokio::test]
 async fn test_metrics_endpoint() {
     let app = create_test_app().await;
@@ -1,5 +1,11 @@
+mod common;
+
 use loro::{
-    config::Config, errors::LoroError, models::*, service::LoroService, stats::StatsCollector,
+    config::Config,
+    errors::LoroError,
+    models::*,
+    service::{LoroService, RequestContext},
+    stats::StatsCollector,
 };
 use secrecy::Secret;
 use serial_test::serial;
@@ -20,17 +26,20 @@ async fn test_service_initialization() {
 async fn test_message_categorization() {
     let greeting = Message {
         role: "user".to_string(),
-        content: "你好".to_string(),
+        content: "你好".into(),
+        ..Default::default()
     };
 
     let question = Message {
         role: "user".to_string(),
-        content: "什么是人工智能？".to_string(),
+        content: "什么是人工智能？".into(),
+        ..Default::default()
     };
 
     let request = Message {
         role: "user".to_string(),
-        content: "请帮我设个闹钟".to_string(),
+        content: "请帮我设个闹钟".into(),
+        ..Default::default()
     };
 
     // Test categorization logic
@@ -97,13 +106,15 @@ mod mock_tests {
             model: "test-model".to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "Hello".to_string(),
+                content: "Hello".into(),
+                ..Default::default()
             }],
             max_tokens: Some(100),
             temperature: 0.7,
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
 
         let json = serde_json::to_string(&request).expect("Should serialize");
@@ -127,9 +138,11 @@ mod mock_tests {
                 delta: MessageDelta {
                     role: Some("assistant".to_string()),
                     content: Some("Hello!".to_string()),
+                    tool_calls: None,
                 },
                 finish_reason: None,
             }],
+            usage: None,
         };
 
         let json = serde_json::to_string(&chunk).expect("Should serialize chunk");
@@ -192,6 +205,7 @@ mod mock_tests {
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
         assert!(
             request.validate().is_err(),
@@ -203,13 +217,15 @@ mod mock_tests {
             model: "test".to_string(),
             messages: vec![Message {
                 role: "invalid".to_string(),
-                content: "test".to_string(),
+                content: "test".into(),
+                ..Default::default()
             }],
             max_tokens: Some(100),
             temperature: 0.7,
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
         assert!(request.validate().is_err(), "Should fail with invalid role");
 
@@ -218,13 +234,15 @@ mod mock_tests {
             model: "test".to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "test".to_string(),
+                content: "test".into(),
+                ..Default::default()
             }],
             max_tokens: Some(100),
             temperature: 3.0, // Invalid
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
         assert!(
             request.validate().is_err(),
@@ -236,13 +254,15 @@ mod mock_tests {
             model: "test".to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "test".to_string(),
+                content: "test".into(),
+                ..Default::default()
             }],
             max_tokens: Some(100),
             temperature: 0.7,
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
         assert!(request.validate().is_ok(), "Should pass with valid request");
     }
@@ -275,7 +295,8 @@ mod mock_tests {
         // Test empty content
         let message = Message {
             role: "user".to_string(),
-            content: "".to_string(),
+            content: "".into(),
+            ..Default::default()
         };
         // Should not panic and should return some category
         let _category = message.categorize();
@@ -283,7 +304,8 @@ mod mock_tests {
         // Test mixed language
         let message = Message {
             role: "user".to_string(),
-            content: "Hello 你好 how are you？".to_string(),
+            content: "Hello 你好 how are you？".into(),
+            ..Default::default()
         };
         let category = message.categorize();
         assert!(matches!(category, RequestCategory::Greeting));
@@ -291,7 +313,8 @@ mod mock_tests {
         // Test special characters
         let message = Message {
             role: "user".to_string(),
-            content: "!@#$%^&*()".to_string(),
+            content: "!@#$%^&*()".into(),
+            ..Default::default()
         };
         let _category = message.categorize();
         // Should not panic
@@ -328,7 +351,7 @@ mod mock_tests {
 
         // Add known data points
         let data_points = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
-        for (_i, &point) in data_points.iter().enumerate() {
+        for &point in data_points.iter() {
             collector.add_request(point, point * 2.0, Some(point), Some(point * 1.5));
         }
 
@@ -371,7 +394,6 @@ mod mock_tests {
     async fn test_sse_line_processing() {
         // This function is private, so we test the behavior through integration tests
         // We verify that SSE parsing works correctly in the service layer
-        assert!(true, "SSE parsing is tested through integration tests");
     }
 
     #[tokio::test]
@@ -411,13 +433,15 @@ mod mock_tests {
             model: "test-model".to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "Test concurrent request".to_string(),
+                content: "Test concurrent request".into(),
+                ..Default::default()
             }],
             max_tokens: Some(50),
             temperature: 0.7,
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
 
         // Spawn multiple concurrent requests
@@ -428,7 +452,7 @@ mod mock_tests {
                 tokio::spawn(async move {
                     // These will fail because we don't have real API endpoints,
                     // but we're testing that they don't panic or deadlock
-                    let _result = service.chat_completion(req).await;
+                    let _result = service.chat_completion(req, &RequestContext::default()).await;
                     i
                 })
             })
@@ -489,13 +513,15 @@ mod mock_tests {
             model: "test".to_string(),
             messages: vec![Message {
                 role: "user".to_string(),
-                content: "test".to_string(),
+                content: "test".into(),
+                ..Default::default()
             }],
             max_tokens: Some(100),
             temperature: 0.7,
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
 
         // Valid request should pass
@@ -511,7 +537,8 @@ mod mock_tests {
         assert!(request.validate().is_err());
         request.messages = vec![Message {
             role: "user".to_string(),
-            content: "test".to_string(),
+            content: "test".into(),
+            ..Default::default()
         }];
 
         // Test invalid role
@@ -520,9 +547,9 @@ mod mock_tests {
         request.messages[0].role = "user".to_string();
 
         // Test empty content
-        request.messages[0].content = "".to_string();
+        request.messages[0].content = "".into();
         assert!(request.validate().is_err());
-        request.messages[0].content = "test".to_string();
+        request.messages[0].content = "test".into();
 
         // Test invalid max_tokens
         request.max_tokens = Some(0);
@@ -559,6 +586,9 @@ mod mock_tests {
             stop: None,
             stream: true,
             extra_body: Some(json!({"prefix": "你好！"})),
+            tools: None,
+            tool_choice: None,
+            stream_options: None,
         };
 
         // Verify prefix is in extra_body
@@ -577,7 +607,7 @@ mod mock_tests {
         use loro::service::LoroService;
 
         // Test various SSE line formats
-        let test_cases = vec![
+        let test_cases = [
             "data: {\"choices\":[{\"delta\":{\"content\":\"Hello\"}}]}",
             "data: {\"choices\":[{\"message\":{\"content\":\"World\"}}]}",
             "data: [DONE]",
@@ -615,23 +645,8 @@ mod mock_tests {
 
         // Test invalid timeout values - directly test validation function
         let mut config = loro::config::Config {
-            host: "127.0.0.1".to_string(),
-            port: 8000,
-            log_level: "info".to_string(),
-            small_model: loro::config::ModelConfig {
-                api_key: Secret::new("test-key".to_string()),
-                base_url: "https://api.example.com/v1".to_string(),
-                model_name: "test-model".to_string(),
-            },
-            large_model: loro::config::ModelConfig {
-                api_key: Secret::new("test-key".to_string()),
-                base_url: "https://api.example.com/v1".to_string(),
-                model_name: "test-model".to_string(),
-            },
             http_timeout_secs: 400, // Too high
-            small_model_timeout_secs: 5,
-            max_retries: 3,
-            stats_max_entries: 1000,
+            ..crate::common::test_config("https://api.example.com/v1")
         };
 
         // Should fail with high timeout
@@ -0,0 +1,355 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use futures::StreamExt;
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use loro::{
+    config::Config,
+    mock_upstream::{InjectedError, MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+};
+
+const REPLY: &str = "今天是晴天，适合出门。";
+
+fn paced(stream: ReplyScript) -> MockScript {
+    MockScript {
+        stream: ReplyScript {
+            token_interval_ms: 5,
+            ..stream
+        },
+        ..Default::default()
+    }
+}
+
+/// Sends a streaming chat request and returns the response status and SSE data payloads
+async fn chat(config: Config, disable_quick_response: bool) -> (StatusCode, Vec<String>) {
+    let body = json!({
+        "model": "loro-voice-assistant",
+        "messages": [{"role": "user", "content": "今天天气怎么样？"}],
+        "stream": true,
+        "disable_quick_response": disable_quick_response
+    });
+    chat_body(config, body).await
+}
+
+async fn chat_body(config: Config, body: Value) -> (StatusCode, Vec<String>) {
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let payloads = std::str::from_utf8(&bytes)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(str::to_string)
+        .collect();
+    (status, payloads)
+}
+
+/// Content of each chunk that carries any
+fn contents(payloads: &[String]) -> Vec<String> {
+    payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+        .filter_map(|chunk| {
+            chunk["choices"][0]["delta"]["content"]
+                .as_str()
+                .map(String::from)
+        })
+        .filter(|content| !content.is_empty())
+        .collect()
+}
+
+fn error_code(payloads: &[String]) -> Option<String> {
+    payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+        .find_map(|payload| payload["error"]["code"].as_str().map(String::from))
+}
+
+#[tokio::test]
+async fn test_openai_quick_response() {
+    let mock = MockUpstream::start(paced(ReplyScript::default()))
+        .await
+        .unwrap();
+    let url = mock.openai_base_url();
+    let (status, payloads) = chat(common::test_config(&url), false).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let contents = contents(&payloads);
+    assert_eq!(contents[0], "好的，");
+    assert_eq!(contents[1..].concat(), REPLY);
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+
+    let calls = mock.calls();
+    assert_eq!(calls.len(), 2);
+    assert!(calls.iter().all(|call| call.path == "/v1/chat/completions"));
+    assert!(!calls[0].is_stream());
+    assert_eq!(calls[0].body["model"], "small-model");
+    assert!(calls[1].is_stream());
+    assert_eq!(calls[1].body["model"], "large-model");
+    assert_eq!(calls[1].body["stream_options"]["include_usage"], true);
+}
+
+#[tokio::test]
+async fn test_openai_direct_response() {
+    let mock = MockUpstream::start(paced(ReplyScript::default()))
+        .await
+        .unwrap();
+    let url = mock.openai_base_url();
+    let (status, payloads) = chat(common::test_config(&url), true).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contents(&payloads).concat(), REPLY);
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+    let calls = mock.calls();
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].is_stream());
+}
+
+#[tokio::test]
+async fn test_ollama_quick_and_direct_responses() {
+    let mock = MockUpstream::start(paced(ReplyScript::default()))
+        .await
+        .unwrap();
+    let url = mock.ollama_base_url();
+
+    let (status, payloads) = chat(common::test_config(&url), false).await;
+    assert_eq!(status, StatusCode::OK);
+    let quick = contents(&payloads);
+    assert_eq!(quick[0], "好的，");
+    assert_eq!(quick[1..].concat(), REPLY);
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+
+    let (status, payloads) = chat(common::test_config(&url), true).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contents(&payloads).concat(), REPLY);
+
+    let calls = mock.calls();
+    assert_eq!(calls.len(), 3);
+    assert!(calls.iter().all(|call| call.path == "/11434/api/chat"));
+    assert_eq!(calls[0].body["stream"], false);
+    assert_eq!(calls[0].body["options"]["num_predict"], 3);
+}
+
+#[tokio::test]
+async fn test_ollama_final_message_finishes_the_reply() {
+    let mock = MockUpstream::start(paced(ReplyScript {
+        finish_reason: "length".to_string(),
+        split_utf8: true,
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    let url = mock.ollama_base_url();
+    let body = json!({
+        "model": "loro-voice-assistant",
+        "messages": [{"role": "user", "content": "今天天气怎么样？"}],
+        "stream": true,
+        "stream_options": {"include_usage": true},
+        "disable_quick_response": true
+    });
+    let (status, payloads) = chat_body(common::test_config(&url), body).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contents(&payloads).concat(), REPLY);
+    let chunks: Vec<Value> = payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str(payload).ok())
+        .collect();
+    let finish: Vec<&Value> = chunks
+        .iter()
+        .filter(|chunk| !chunk["choices"][0]["finish_reason"].is_null())
+        .collect();
+    assert_eq!(finish.len(), 1);
+    assert_eq!(finish[0]["choices"][0]["finish_reason"], "length");
+    // Token counts come from Ollama's eval_count rather than the local estimate
+    let usage = &chunks.iter().find(|chunk| chunk.get("usage").is_some()).unwrap()["usage"];
+    assert_eq!(usage["prompt_tokens"], 10);
+    assert_eq!(usage["completion_tokens"], REPLY.chars().count());
+}
+
+#[tokio::test]
+async fn test_small_model_error_falls_back_to_canned_quick_response() {
+    let mut script = paced(ReplyScript::default());
+    script.quick.error = Some(InjectedError {
+        status: 500,
+        message: "small model down".to_string(),
+        times: None,
+    });
+    let mock = MockUpstream::start(script).await.unwrap();
+    let url = mock.openai_base_url();
+    let (status, payloads) = chat(common::test_config(&url), false).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let contents = contents(&payloads);
+    // The failed small model call is replaced by a canned acknowledgement
+    assert!(!mock.calls()[0].is_stream());
+    assert!(!contents[0].is_empty());
+    assert_eq!(contents[1..].concat(), REPLY);
+}
+
+#[tokio::test]
+async fn test_large_model_error_status_is_returned() {
+    let script = paced(ReplyScript {
+        error: Some(InjectedError {
+            status: 429,
+            message: "slow down".to_string(),
+            times: None,
+        }),
+        ..Default::default()
+    });
+    let mock = MockUpstream::start(script).await.unwrap();
+    let url = mock.openai_base_url();
+    let (status, _) = chat(common::test_config(&url), true).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_injected_error_only_fails_the_first_calls() {
+    let script = paced(ReplyScript {
+        error: Some(InjectedError {
+            status: 503,
+            message: "warming up".to_string(),
+            times: Some(1),
+        }),
+        ..Default::default()
+    });
+    let mock = MockUpstream::start(script).await.unwrap();
+    let url = mock.openai_base_url();
+
+    let (status, _) = chat(common::test_config(&url), true).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let (status, payloads) = chat(common::test_config(&url), true).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contents(&payloads).concat(), REPLY);
+}
+
+#[tokio::test]
+async fn test_disconnect_mid_stream_emits_error_event() {
+    for base_url in [
+        |mock: &MockUpstream| mock.openai_base_url(),
+        |mock: &MockUpstream| mock.ollama_base_url(),
+    ] {
+        let mock = MockUpstream::start(paced(ReplyScript {
+            disconnect_after: Some(2),
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+        let url = base_url(&mock);
+        let (status, payloads) = chat(common::test_config(&url), true).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(contents(&payloads).concat(), "今天是晴天");
+        assert_eq!(error_code(&payloads).as_deref(), Some("stream_interrupted"));
+        assert_eq!(payloads.last().unwrap(), "[DONE]");
+    }
+}
+
+/// Raw body frames of a streaming call made straight to the mock
+async fn raw_frames(url: String) -> Vec<Vec<u8>> {
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let response = client
+        .post(url)
+        .json(&json!({"model": "m", "messages": [], "stream": true}))
+        .send()
+        .await
+        .unwrap();
+    response
+        .bytes_stream()
+        .map(|frame| frame.unwrap().to_vec())
+        .collect()
+        .await
+}
+
+#[tokio::test]
+async fn test_split_utf8_frames() {
+    let script = ReplyScript {
+        split_utf8: true,
+        ..Default::default()
+    };
+    let mock = MockUpstream::start(MockScript {
+        stream: script.clone(),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    for url in [
+        format!("{}/chat/completions", mock.openai_base_url()),
+        format!("{}/api/chat", mock.ollama_base_url()),
+    ] {
+        let frames = raw_frames(url).await;
+        // Some frame ends inside a character, yet the whole body is valid UTF-8
+        assert!(frames
+            .iter()
+            .any(|frame| std::str::from_utf8(frame).is_err()));
+        let body = String::from_utf8(frames.concat()).unwrap();
+        for token in &script.tokens {
+            assert!(body.contains(token.as_str()), "{}", body);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_split_utf8_reply_is_intact() {
+    let mock = MockUpstream::start(paced(ReplyScript {
+        split_utf8: true,
+        ..Default::default()
+    }))
+    .await
+    .unwrap();
+    let url = mock.openai_base_url();
+    let (status, payloads) = chat(common::test_config(&url), true).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contents(&payloads).concat(), REPLY);
+}
+
+#[tokio::test]
+async fn test_malformed_line_and_final_stats() {
+    let mock = MockUpstream::start(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["你好".to_string()],
+            malformed_after: Some(1),
+            finish_reason: "length".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let frames = raw_frames(format!("{}/api/chat", mock.ollama_base_url())).await;
+    let body = String::from_utf8(frames.concat()).unwrap();
+    let lines: Vec<&str> = body.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(serde_json::from_str::<Value>(lines[1]).is_err());
+    let done: Value = serde_json::from_str(lines[2]).unwrap();
+    assert_eq!(done["done"], true);
+    assert_eq!(done["done_reason"], "length");
+    assert_eq!(done["eval_count"], 2);
+}
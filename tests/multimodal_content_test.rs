@@ -0,0 +1,160 @@
+mod common;
+
+use futures::StreamExt;
+use serde_json::json;
+
+use loro::{
+    config::Config,
+    mock_upstream::{MockScript, ReplyScript},
+    models::{ChatCompletionRequest, ContentPart, Message, MessageContent, RequestCategory},
+    service::{LoroService, RequestContext},
+};
+
+const IMAGE_DATA_URL: &str = "data:image/png;base64,iVBORw0KGgo=";
+
+fn vision_request() -> serde_json::Value {
+    json!({
+        "model": "loro-quick",
+        "messages": [{
+            "role": "user",
+            "content": [
+                {"type": "text", "text": "这张图片里有什么？"},
+                {"type": "image_url", "image_url": {"url": IMAGE_DATA_URL, "detail": "low"}},
+                {"type": "image_url", "image_url": {"url": "https://example.com/cat.jpg"}}
+            ]
+        }]
+    })
+}
+
+#[test]
+fn test_content_accepts_string_and_parts() {
+    let plain: Message = serde_json::from_value(json!({"role": "user", "content": "你好"})).unwrap();
+    assert_eq!(plain.content, MessageContent::Text("你好".to_string()));
+
+    let request: ChatCompletionRequest = serde_json::from_value(vision_request()).unwrap();
+    let content = &request.messages[0].content;
+    assert!(matches!(content, MessageContent::Parts(parts) if parts.len() == 3));
+    assert_eq!(content.text(), "这张图片里有什么？");
+    assert_eq!(
+        content.image_urls().collect::<Vec<_>>(),
+        vec![IMAGE_DATA_URL, "https://example.com/cat.jpg"]
+    );
+}
+
+#[test]
+fn test_content_serializes_in_original_shape() {
+    let request: ChatCompletionRequest = serde_json::from_value(vision_request()).unwrap();
+    let value = serde_json::to_value(&request.messages[0]).unwrap();
+    assert_eq!(value["content"], vision_request()["messages"][0]["content"]);
+
+    let plain = Message {
+        role: "user".to_string(),
+        content: "你好".into(),
+        ..Default::default()
+    };
+    assert_eq!(serde_json::to_value(&plain).unwrap()["content"], "你好");
+}
+
+#[test]
+fn test_text_joins_multiple_text_parts() {
+    let content = MessageContent::Parts(vec![
+        ContentPart::Text { text: "第一句".to_string() },
+        ContentPart::Text { text: "第二句".to_string() },
+    ]);
+    assert_eq!(content.text(), "第一句\n第二句");
+    assert!(!content.has_images());
+}
+
+#[test]
+fn test_multimodal_validation() {
+    let request: ChatCompletionRequest = serde_json::from_value(vision_request()).unwrap();
+    assert!(request.validate().is_ok());
+
+    // Image-only user message is valid
+    let image_only: ChatCompletionRequest = serde_json::from_value(json!({
+        "model": "loro-quick",
+        "messages": [{"role": "user", "content": [
+            {"type": "image_url", "image_url": {"url": IMAGE_DATA_URL}}
+        ]}]
+    }))
+    .unwrap();
+    assert!(image_only.validate().is_ok());
+
+    // Empty parts array is not
+    let empty: ChatCompletionRequest = serde_json::from_value(json!({
+        "model": "loro-quick",
+        "messages": [{"role": "user", "content": []}]
+    }))
+    .unwrap();
+    assert!(empty.validate().unwrap_err().contains("content cannot be empty"));
+
+    // Images are only accepted from the user
+    let system_image: ChatCompletionRequest = serde_json::from_value(json!({
+        "model": "loro-quick",
+        "messages": [
+            {"role": "system", "content": [{"type": "image_url", "image_url": {"url": IMAGE_DATA_URL}}]},
+            {"role": "user", "content": "你好"}
+        ]
+    }))
+    .unwrap();
+    assert!(system_image.validate().is_err());
+
+    // Unknown part types fail to deserialize
+    let unknown = serde_json::from_value::<ChatCompletionRequest>(json!({
+        "model": "loro-quick",
+        "messages": [{"role": "user", "content": [{"type": "video", "url": "x"}]}]
+    }));
+    assert!(unknown.is_err());
+}
+
+#[test]
+fn test_categorize_uses_text_parts() {
+    let request: ChatCompletionRequest = serde_json::from_value(vision_request()).unwrap();
+    assert!(matches!(
+        request.messages[0].categorize(),
+        RequestCategory::Question
+    ));
+}
+
+#[tokio::test]
+async fn test_images_reach_large_model_and_quick_path_uses_text() {
+    let upstream = common::mock_upstream(MockScript {
+        quick: ReplyScript {
+            tokens: vec!["我看看，".to_string()],
+            ..Default::default()
+        },
+        stream: ReplyScript {
+            tokens: vec!["一只猫".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let config = Config {
+        large_model: common::model(&upstream.ollama_base_url(), "llava"),
+        ..common::test_config(&upstream.openai_base_url())
+    };
+    let service = LoroService::new(config).await.unwrap();
+
+    let request: ChatCompletionRequest = serde_json::from_value(vision_request()).unwrap();
+    let response = service.chat_completion(request, &RequestContext::default()).await.unwrap();
+    let mut body = response.into_body().into_data_stream();
+    let mut text = String::new();
+    while let Some(chunk) = body.next().await {
+        text.push_str(&String::from_utf8_lossy(&chunk.unwrap()));
+    }
+    assert!(text.contains("我看看，"));
+    assert!(text.contains("一只猫"));
+
+    let calls = upstream.calls();
+
+    // Small model: text only
+    let small = &calls.iter().find(|c| c.body["model"] == "small-model").unwrap().body;
+    assert_eq!(small["messages"][1]["content"], "这张图片里有什么？");
+
+    // Large model (Ollama): text in content, inline base64 in images, remote URL dropped
+    let large = &calls.iter().find(|c| c.body["model"] == "llava").unwrap().body;
+    let user = &large["messages"][1];
+    assert_eq!(user["content"], "这张图片里有什么？");
+    assert_eq!(user["images"], json!(["iVBORw0KGgo="]));
+}
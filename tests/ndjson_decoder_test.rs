@@ -0,0 +1,88 @@
+use loro::ndjson::NdjsonDecoder;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use serde_json::{json, Value};
+
+fn decode(chunks: &[&[u8]]) -> Vec<Value> {
+    let mut decoder = NdjsonDecoder::new();
+    let mut values: Vec<Value> = chunks
+        .iter()
+        .flat_map(|chunk| decoder.feed(chunk).unwrap())
+        .map(Result::unwrap)
+        .collect();
+    values.extend(decoder.finish().map(Result::unwrap));
+    values
+}
+
+#[test]
+fn test_lines_split_across_chunks() {
+    let bytes = "{\"content\":\"今天\"}\n{\"content\":\"是晴天\"}\n".as_bytes();
+    // Cut inside a character, then inside the second object
+    let values = decode(&[&bytes[..14], &bytes[14..25], &bytes[25..]]);
+    assert_eq!(
+        values,
+        vec![json!({"content": "今天"}), json!({"content": "是晴天"})]
+    );
+}
+
+#[test]
+fn test_blank_lines_and_crlf() {
+    let values = decode(&[b"\n{\"a\":1}\r\n\r\n  \n{\"b\":2}\n"]);
+    assert_eq!(values, vec![json!({"a": 1}), json!({"b": 2})]);
+}
+
+#[test]
+fn test_malformed_line_does_not_affect_the_next() {
+    let mut decoder = NdjsonDecoder::new();
+    let values = decoder
+        .feed::<Value>(b"{\"a\":1}\n{\"broken\n{\"b\":2}\n")
+        .unwrap();
+    assert_eq!(values.len(), 3);
+    assert!(values[1].is_err());
+    assert_eq!(values[2].as_ref().unwrap(), &json!({"b": 2}));
+}
+
+#[test]
+fn test_final_line_without_line_break() {
+    let mut decoder = NdjsonDecoder::new();
+    assert!(decoder.feed::<Value>(b"{\"done\":").unwrap().is_empty());
+    assert!(decoder.feed::<Value>(b"true}").unwrap().is_empty());
+    assert_eq!(
+        decoder.finish::<Value>().unwrap().unwrap(),
+        json!({"done": true})
+    );
+    assert!(decoder.finish::<Value>().is_none());
+}
+
+#[test]
+fn test_buffer_is_bounded() {
+    let mut decoder = NdjsonDecoder::with_limit(8);
+    decoder.feed::<Value>(b"{\"a\":1}\n[1,2,3,").unwrap();
+    assert!(decoder.feed::<Value>(b"4,5]").is_err());
+}
+
+proptest! {
+    #[test]
+    fn prop_arbitrary_chunk_splits_decode_identically(
+        texts in proptest::collection::vec("[a-z0-9 \"\\\\，。今天是晴天😀]{0,12}", 0..8),
+        crlf in any::<bool>(),
+        cuts in proptest::collection::vec(any::<Index>(), 0..10),
+    ) {
+        let expected: Vec<Value> = texts.iter().map(|text| json!({"content": text})).collect();
+        let eol = if crlf { "\r\n" } else { "\n" };
+        let bytes: Vec<u8> = expected
+            .iter()
+            .flat_map(|value| format!("{value}{eol}").into_bytes())
+            .collect();
+        let mut cuts: Vec<usize> = cuts.iter().map(|cut| cut.index(bytes.len() + 1)).collect();
+        cuts.sort_unstable();
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        for cut in cuts.into_iter().chain([bytes.len()]) {
+            chunks.push(&bytes[start..cut]);
+            start = cut;
+        }
+        prop_assert_eq!(decode(&chunks), expected);
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::get,
+    Router,
+};
+use http_body_util::BodyExt;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+use loro::{
+    config::{Config, ModelConfig},
+    mock_upstream::MockScript,
+    readiness::probe_endpoint,
+    service::LoroService,
+};
+use secrecy::Secret;
+
+async fn spawn_mock_upstream() -> String {
+    common::mock_upstream(MockScript::default())
+        .await
+        .openai_base_url()
+}
+
+// An address nothing is listening on
+async fn unreachable_base_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+    format!("http://{}/v1", addr)
+}
+
+fn create_test_config(small_base_url: String, large_base_url: String) -> Config {
+    Config {
+        small_model: common::model(&small_base_url, "small-model"),
+        large_model: common::model(&large_base_url, "large-model"),
+        ..common::test_config(&large_base_url)
+    }
+}
+
+async fn get_ready(service: Arc<LoroService>) -> (StatusCode, serde_json::Value) {
+    let app = Router::new()
+        .route("/ready", get(loro::ready))
+        .with_state(service);
+
+    let request = Request::builder()
+        .uri("/ready")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[test]
+fn test_probe_endpoint_by_provider() {
+    let openai = ModelConfig {
+        api_key: Secret::new("key".to_string()),
+        base_url: "https://api.example.com/v1".to_string(),
+        model_name: "m".to_string(),
+    };
+    let ollama = ModelConfig {
+        api_key: Secret::new("none".to_string()),
+        base_url: "http://127.0.0.1:11434".to_string(),
+        model_name: "m".to_string(),
+    };
+
+    assert_eq!(probe_endpoint(&openai), "https://api.example.com/v1/models");
+    assert_eq!(probe_endpoint(&ollama), "http://127.0.0.1:11434/api/tags");
+}
+
+#[tokio::test]
+async fn test_ready_when_both_upstreams_reachable() {
+    let upstream = spawn_mock_upstream().await;
+    let config = create_test_config(upstream.clone(), upstream);
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+
+    let (status, json) = get_ready(service).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["status"], "ready");
+    assert_eq!(json["upstreams"]["small_model"]["healthy"], true);
+    assert_eq!(json["upstreams"]["large_model"]["healthy"], true);
+    assert_eq!(json["upstreams"]["large_model"]["status_code"], 200);
+    assert!(json["upstreams"]["large_model"]["latency_ms"].as_f64().unwrap() >= 0.0);
+}
+
+#[tokio::test]
+async fn test_not_ready_when_large_model_unreachable() {
+    let upstream = spawn_mock_upstream().await;
+    let config = create_test_config(upstream, unreachable_base_url().await);
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+
+    let (status, json) = get_ready(service).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(json["status"], "not_ready");
+    assert_eq!(json["upstreams"]["small_model"]["healthy"], true);
+    assert_eq!(json["upstreams"]["large_model"]["healthy"], false);
+    assert!(json["upstreams"]["large_model"]["error"].is_string());
+}
+
+#[tokio::test]
+async fn test_ready_when_only_small_model_unreachable() {
+    // The quick response falls back to canned phrases, so the small model is not required
+    let upstream = spawn_mock_upstream().await;
+    let config = create_test_config(unreachable_base_url().await, upstream);
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+
+    let (status, json) = get_ready(service).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["upstreams"]["small_model"]["healthy"], false);
+}
+
+#[tokio::test]
+async fn test_background_probe_populates_cached_report() {
+    let upstream = spawn_mock_upstream().await;
+    let mut config = create_test_config(upstream.clone(), upstream);
+    config.readiness_probe_interval_secs = 60;
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+
+    let handle = service
+        .spawn_readiness_probe()
+        .expect("probe task should start when interval is set");
+
+    // The first tick fires immediately; wait for the report to land
+    let mut report = None;
+    for _ in 0..50 {
+        let current = service.check_readiness().await;
+        if current.large_model.healthy {
+            report = Some(current);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert!(report.unwrap().is_ready());
+
+    // The probe task exits once draining begins
+    service.drain_state().begin_draining();
+    tokio::time::timeout(std::time::Duration::from_secs(1), handle)
+        .await
+        .expect("probe task should stop when draining")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_not_ready_while_draining() {
+    let upstream = spawn_mock_upstream().await;
+    let config = create_test_config(upstream.clone(), upstream);
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+    service.drain_state().begin_draining();
+
+    let (status, json) = get_ready(service).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(json["draining"], true);
+}
@@ -0,0 +1,341 @@
+mod common;
+
+use axum::{routing::get, Router};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+use loro::{
+    config::{BudgetAction, BudgetConfig, Config, ModelPrice},
+    mock_upstream::{MockScript, MockUpstream, PromptScript, ReplyScript},
+    realtime::{ClientEvent, ServerEvent},
+    service::LoroService,
+};
+
+type Client = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn spawn_loro() -> (Client, MockUpstream, Arc<LoroService>) {
+    spawn_loro_with(|config| config).await
+}
+
+/// Like `spawn_loro`, with the service config adjusted by `configure`
+async fn spawn_loro_with(
+    configure: impl FnOnce(Config) -> Config,
+) -> (Client, MockUpstream, Arc<LoroService>) {
+    let reply = |tokens: &[&str]| ReplyScript {
+        tokens: tokens.iter().map(|token| token.to_string()).collect(),
+        ..Default::default()
+    };
+    // Replies to prompts containing "慢" stall after the first chunk
+    let upstream = common::mock_upstream(MockScript {
+        stream: reply(&["今天", "天气很好。"]),
+        prompts: vec![PromptScript {
+            contains: "慢".to_string(),
+            stream: ReplyScript {
+                stall_after: Some(1),
+                ..reply(&["今天", "天气很好。"])
+            },
+        }],
+        ..Default::default()
+    })
+    .await;
+    let service = Arc::new(
+        LoroService::new(configure(common::test_config(&upstream.openai_base_url())))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/realtime", get(loro::realtime))
+        .with_state(Arc::clone(&service));
+    let base_url = common::serve(app).await;
+
+    let mut request = format!("{}/v1/realtime", base_url.replace("http", "ws"))
+        .into_client_request()
+        .unwrap();
+    request
+        .headers_mut()
+        .insert("authorization", "Bearer sk-test-abcd1234".parse().unwrap());
+    let (client, _) = connect_async(request).await.unwrap();
+    (client, upstream, service)
+}
+
+async fn send(client: &mut Client, event: Value) {
+    client.send(Message::Text(event.to_string())).await.unwrap();
+}
+
+async fn recv(client: &mut Client) -> Value {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .expect("timed out waiting for server event")
+            .expect("socket closed")
+            .unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+/// Collects events up to and including the one of type `last`
+async fn recv_until(client: &mut Client, last: &str) -> Vec<Value> {
+    let mut events = Vec::new();
+    loop {
+        let event = recv(client).await;
+        let done = event["type"] == last;
+        events.push(event);
+        if done {
+            return events;
+        }
+    }
+}
+
+fn types(events: &[Value]) -> Vec<&str> {
+    events.iter().map(|e| e["type"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_client_events_deserialize() {
+    let update: ClientEvent = serde_json::from_value(json!({
+        "type": "session.update",
+        "session": {"model": "loro-direct", "max_tokens": 64}
+    }))
+    .unwrap();
+    assert!(
+        matches!(update, ClientEvent::SessionUpdate { session } if session.max_tokens == Some(64))
+    );
+
+    let turn: ClientEvent =
+        serde_json::from_value(json!({"type": "turn", "content": "你好"})).unwrap();
+    assert!(matches!(turn, ClientEvent::Turn { content } if content.text() == "你好"));
+
+    let cancel: ClientEvent = serde_json::from_value(json!({"type": "cancel"})).unwrap();
+    assert!(matches!(cancel, ClientEvent::Cancel));
+
+    let unknown = serde_json::from_value::<ClientEvent>(json!({
+        "type": "session.update",
+        "session": {"voice": "alloy"}
+    }));
+    assert!(unknown.is_err());
+}
+
+#[test]
+fn test_server_event_wire_format() {
+    let event = ServerEvent::ResponseDelta {
+        turn_id: "turn-1".to_string(),
+        content: Some("你好".to_string()),
+        tool_calls: None,
+    };
+    assert_eq!(
+        serde_json::to_value(&event).unwrap(),
+        json!({"type": "response.delta", "turn_id": "turn-1", "content": "你好"})
+    );
+}
+
+#[tokio::test]
+async fn test_turn_streams_quick_ack_deltas_and_done() {
+    let (mut client, _, _) = spawn_loro().await;
+
+    let created = recv(&mut client).await;
+    assert_eq!(created["type"], "session.created");
+    assert_eq!(created["session"]["model"], "loro-quick");
+
+    send(
+        &mut client,
+        json!({"type": "turn", "content": "今天天气怎么样"}),
+    )
+    .await;
+    let events = recv_until(&mut client, "turn.done").await;
+
+    assert_eq!(
+        types(&events),
+        vec![
+            "turn.started",
+            "response.quick",
+            "response.delta",
+            "response.delta",
+            "turn.done"
+        ]
+    );
+    let turn_id = &events[0]["turn_id"];
+    assert!(events.iter().all(|e| &e["turn_id"] == turn_id));
+    assert_eq!(events[1]["content"], "好的，");
+    assert_eq!(events[4]["content"], "好的，今天天气很好。");
+}
+
+#[tokio::test]
+async fn test_follow_up_turn_includes_history() {
+    let (mut client, upstream, _) = spawn_loro().await;
+    recv(&mut client).await;
+
+    send(&mut client, json!({"type": "turn", "content": "第一句"})).await;
+    recv_until(&mut client, "turn.done").await;
+    send(&mut client, json!({"type": "turn", "content": "第二句"})).await;
+    recv_until(&mut client, "turn.done").await;
+
+    let calls = upstream.calls();
+    let large = &calls
+        .iter()
+        .rfind(|call| call.is_stream())
+        .expect("large model request")
+        .body;
+    let roles: Vec<&str> = large["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["role"].as_str().unwrap())
+        .collect();
+    assert_eq!(roles, vec!["system", "user", "assistant", "user"]);
+    assert_eq!(large["messages"][2]["content"], "好的，今天天气很好。");
+    assert_eq!(large["messages"][3]["content"], "第二句");
+}
+
+#[tokio::test]
+async fn test_session_update_switches_to_direct_mode() {
+    let (mut client, upstream, _) = spawn_loro().await;
+    recv(&mut client).await;
+
+    send(
+        &mut client,
+        json!({"type": "session.update", "session": {"disable_quick_response": true, "max_tokens": 42}}),
+    )
+    .await;
+    let updated = recv(&mut client).await;
+    assert_eq!(updated["type"], "session.updated");
+    assert_eq!(updated["session"]["disable_quick_response"], true);
+
+    send(&mut client, json!({"type": "turn", "content": "你好"})).await;
+    let events = recv_until(&mut client, "turn.done").await;
+    assert_eq!(
+        types(&events),
+        vec![
+            "turn.started",
+            "response.delta",
+            "response.delta",
+            "turn.done"
+        ]
+    );
+
+    let calls = upstream.calls();
+    assert_eq!(
+        calls.len(),
+        1,
+        "small model must not be called in direct mode"
+    );
+    assert_eq!(calls[0].body["max_tokens"], 42);
+}
+
+#[tokio::test]
+async fn test_over_budget_turn_has_no_quick_ack() {
+    let (mut client, upstream, _) = spawn_loro_with(|config| Config {
+        pricing: vec![ModelPrice {
+            model: "large-model".to_string(),
+            input_per_1k: 10.0,
+            output_per_1k: 10.0,
+        }],
+        budget: Some(BudgetConfig {
+            daily_limit_usd: 0.01,
+            action: BudgetAction::Degrade,
+        }),
+        ..config
+    })
+    .await;
+    recv(&mut client).await;
+
+    send(&mut client, json!({"type": "turn", "content": "第一句"})).await;
+    let events = recv_until(&mut client, "turn.done").await;
+    assert_eq!(types(&events)[1], "response.quick");
+    let before = upstream.calls().len();
+
+    // Degraded turns stream the small model's answer without an acknowledgement
+    send(&mut client, json!({"type": "turn", "content": "第二句"})).await;
+    let events = recv_until(&mut client, "turn.done").await;
+    assert_eq!(
+        types(&events),
+        vec![
+            "turn.started",
+            "response.delta",
+            "response.delta",
+            "turn.done"
+        ]
+    );
+    assert_eq!(events[3]["content"], "今天天气很好。");
+    let calls = upstream.calls();
+    assert_eq!(calls.len(), before + 1);
+    assert_eq!(calls[before].body["model"], "small-model");
+}
+
+#[tokio::test]
+async fn test_invalid_session_update_is_rejected() {
+    let (mut client, _, _) = spawn_loro().await;
+    recv(&mut client).await;
+
+    send(
+        &mut client,
+        json!({"type": "session.update", "session": {"max_tokens": 0}}),
+    )
+    .await;
+    let event = recv(&mut client).await;
+    assert_eq!(event["type"], "error");
+    assert_eq!(event["error"]["code"], "validation_failed");
+
+    send(&mut client, json!({"type": "bogus"})).await;
+    let event = recv(&mut client).await;
+    assert_eq!(event["type"], "error");
+    assert_eq!(event["error"]["code"], "invalid_event");
+}
+
+#[tokio::test]
+async fn test_cancel_stops_turn_and_keeps_partial_reply() {
+    let (mut client, _, service) = spawn_loro().await;
+    recv(&mut client).await;
+
+    send(&mut client, json!({"type": "turn", "content": "慢慢说"})).await;
+    let events = recv_until(&mut client, "response.delta").await;
+    assert_eq!(
+        types(&events),
+        vec!["turn.started", "response.quick", "response.delta"]
+    );
+    assert_eq!(service.drain_state().active_streams(), 1);
+
+    send(&mut client, json!({"type": "cancel"})).await;
+    let cancelled = recv(&mut client).await;
+    assert_eq!(cancelled["type"], "turn.cancelled");
+    assert_eq!(cancelled["turn_id"], events[0]["turn_id"]);
+    assert_eq!(cancelled["content"], "好的，今天");
+
+    // Dropping the turn releases its stream slot
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(service.drain_state().active_streams(), 0);
+}
+
+#[tokio::test]
+async fn test_new_turn_interrupts_current_one() {
+    let (mut client, _, _) = spawn_loro().await;
+    recv(&mut client).await;
+
+    send(&mut client, json!({"type": "turn", "content": "慢慢说"})).await;
+    recv_until(&mut client, "response.delta").await;
+
+    send(&mut client, json!({"type": "turn", "content": "算了"})).await;
+    let events = recv_until(&mut client, "turn.done").await;
+    assert_eq!(types(&events)[..2], ["turn.cancelled", "turn.started"]);
+    assert_eq!(events.last().unwrap()["content"], "好的，今天天气很好。");
+}
+
+#[tokio::test]
+async fn test_draining_closes_idle_session() {
+    let (mut client, _, service) = spawn_loro().await;
+    recv(&mut client).await;
+
+    service.drain_state().begin_draining();
+    let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+        .await
+        .expect("session should close while draining");
+    assert!(matches!(message, Some(Ok(Message::Close(_))) | None));
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use axum::{routing::post, Router};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+use loro::{
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    replay::{load_requests, replay, schedule, ReplayMode, ReplayOptions},
+    service::LoroService,
+    timing::TimingSummary,
+};
+
+const AUDIT_LOG: &str = r#"{"timestamp":"2025-03-01T10:00:02.500Z","request_id":"b","api_key":"anonymous","model":"loro-quick","mode":"direct","messages":[{"role":"user","content":"讲个笑话"}],"reply":"好。","completed":true}
+{"timestamp":"2025-03-01T10:00:00Z","request_id":"a","api_key":"anonymous","model":"loro-quick","mode":"quick","messages":[{"role":"system","content":"简短回答"},{"role":"user","content":"今天天气怎么样"}],"quick_response":"好的，","reply":"晴天。","completed":true}
+
+{"timestamp":"2025-03-01T10:00:01Z","request_id":"c","api_key":"anonymous","model":"loro-quick","mode":"quick","messages":[{"role":"user","content":"你好"}],"reply":"","completed":false}
+"#;
+
+/// A loro server backed by the mock upstream
+async fn spawn_loro() -> (String, MockUpstream) {
+    let upstream = common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["今天是晴天。".to_string()],
+            latency_ms: 20,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    (common::serve(app).await, upstream)
+}
+
+/// Quick-response (non-streaming) calls loro made to the small model
+fn quick_calls(upstream: &MockUpstream) -> usize {
+    upstream
+        .calls()
+        .iter()
+        .filter(|call| !call.is_stream())
+        .count()
+}
+
+#[test]
+fn test_load_requests_orders_by_arrival() {
+    let requests = load_requests(AUDIT_LOG).unwrap();
+    let prompts: Vec<&str> = requests
+        .iter()
+        .map(|request| request.messages.last().unwrap().content.as_str())
+        .collect();
+    assert_eq!(prompts, ["今天天气怎么样", "你好", "讲个笑话"]);
+
+    assert_eq!(
+        schedule(&requests, 1.0),
+        [
+            Duration::ZERO,
+            Duration::from_secs(1),
+            Duration::from_millis(2500)
+        ]
+    );
+    assert_eq!(
+        schedule(&requests, 10.0),
+        [
+            Duration::ZERO,
+            Duration::from_millis(100),
+            Duration::from_millis(250)
+        ]
+    );
+
+    let (body, quick) = requests[0].body(ReplayMode::Original);
+    assert!(quick);
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["disable_quick_response"], false);
+    assert_eq!(body["messages"][0], json!({"role": "system", "content": "简短回答"}));
+    let (body, quick) = requests[2].body(ReplayMode::Original);
+    assert!(!quick);
+    assert_eq!(body["disable_quick_response"], true);
+    let (body, quick) = requests[2].body(ReplayMode::Quick);
+    assert!(quick);
+    assert_eq!(body["disable_quick_response"], false);
+}
+
+#[test]
+fn test_load_requests_reports_bad_line() {
+    let error = load_requests("{\"timestamp\":\"2025-03-01T10:00:00Z\"}\n")
+        .unwrap_err()
+        .to_string();
+    assert_eq!(error, "Invalid audit record on line 1");
+}
+
+#[tokio::test]
+async fn test_replay_quick_and_direct() {
+    let (base_url, upstream) = spawn_loro().await;
+    let requests = load_requests(AUDIT_LOG).unwrap();
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let options = |mode| ReplayOptions {
+        base_url: base_url.clone(),
+        api_key: None,
+        speed: 100.0,
+        mode,
+    };
+
+    let quick = replay(&client, &requests, &options(ReplayMode::Quick)).await;
+    assert_eq!(quick_calls(&upstream), 3);
+    for timing in &quick {
+        assert!(timing.is_success(), "{:?}", timing);
+        assert_eq!(timing.status, 200);
+        let quick_chunk = timing.quick_chunk.unwrap();
+        let first_large_chunk = timing.first_large_chunk.unwrap();
+        assert!(timing.ttfb.unwrap() <= quick_chunk);
+        assert!(quick_chunk <= first_large_chunk);
+        assert!(first_large_chunk <= timing.total);
+    }
+
+    let direct = replay(&client, &requests, &options(ReplayMode::Direct)).await;
+    assert_eq!(quick_calls(&upstream), 3);
+    for timing in &direct {
+        assert!(timing.is_success(), "{:?}", timing);
+        assert!(timing.quick_chunk.is_none());
+        assert!(timing.first_large_chunk.is_some());
+    }
+
+    let summary = TimingSummary::from_timings(&direct);
+    assert_eq!(summary.requests, 3);
+    assert_eq!(summary.errors, 0);
+    assert_eq!(summary.quick_chunk.max, 0.0);
+    assert!(summary.first_large_chunk.p50 > 0.0);
+}
+
+#[tokio::test]
+async fn test_replay_reports_unreachable_server() {
+    let requests = load_requests(AUDIT_LOG).unwrap();
+    let client = reqwest::Client::builder().no_proxy().build().unwrap();
+    let options = ReplayOptions {
+        // Nothing listens on the discard port
+        base_url: "http://127.0.0.1:9".to_string(),
+        api_key: None,
+        speed: 1000.0,
+        mode: ReplayMode::Original,
+    };
+    let timings = replay(&client, &requests[..1], &options).await;
+    assert!(timings[0].error.is_some());
+    assert_eq!(TimingSummary::from_timings(&timings).errors, 1);
+}
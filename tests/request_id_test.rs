@@ -0,0 +1,143 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    middleware,
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+use loro::{
+    mock_upstream::MockUpstream,
+    service::{LoroService, REQUEST_ID_HEADER},
+};
+
+/// `X-Request-Id` of each upstream call
+fn seen_ids(upstream: &MockUpstream) -> Vec<Option<String>> {
+    upstream
+        .calls()
+        .iter()
+        .map(|call| {
+            call.headers
+                .get(REQUEST_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Sends a quick-mode chat request and returns the response request ID and chunk ids
+async fn chat(base_url: &str, request_id: Option<&str>) -> (String, Vec<String>) {
+    let service = Arc::new(
+        LoroService::new(common::test_config(base_url))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .layer(middleware::from_fn(loro::propagate_request_id))
+        .with_state(service);
+    let body =
+        json!({"model": "loro-quick", "messages": [{"role": "user", "content": "今天天气怎么样"}]});
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json");
+    if let Some(id) = request_id {
+        request = request.header(REQUEST_ID_HEADER, id);
+    }
+    let response = app
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response_id = response.headers()[REQUEST_ID_HEADER]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    let chunk_ids = std::str::from_utf8(&bytes)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+        .map(|chunk| chunk["id"].as_str().unwrap().to_string())
+        .collect();
+    (response_id, chunk_ids)
+}
+
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_client_request_id_is_used_everywhere() {
+    let upstream = common::mock_upstream(Default::default()).await;
+    let base_url = upstream.openai_base_url();
+
+    let (response_id, chunk_ids) = chat(&base_url, Some("client-req-42")).await;
+
+    assert_eq!(response_id, "client-req-42");
+    // The quick chunk and the large model chunks share one id
+    assert!(chunk_ids.len() >= 2);
+    assert!(chunk_ids.iter().all(|id| id == "chatcmpl-client-req-42"));
+    // Both the small and the large model call carry the ID upstream
+    assert_eq!(
+        seen_ids(&upstream),
+        vec![Some("client-req-42".to_string()); 2]
+    );
+}
+
+#[tokio::test]
+async fn test_request_id_is_generated_when_missing_or_invalid() {
+    let upstream = common::mock_upstream(Default::default()).await;
+    let base_url = upstream.openai_base_url();
+
+    for client_id in [None, Some("has spaces"), Some(&*"x".repeat(200))] {
+        let (response_id, chunk_ids) = chat(&base_url, client_id).await;
+        assert!(!response_id.is_empty());
+        assert_ne!(Some(response_id.as_str()), client_id);
+        let expected = format!("chatcmpl-{}", response_id);
+        assert!(chunk_ids.iter().all(|id| *id == expected));
+    }
+    assert!(seen_ids(&upstream).iter().all(Option::is_some));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_stage_logs_carry_request_id() {
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .with_max_level(tracing::Level::INFO)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let upstream = common::mock_upstream(Default::default()).await;
+    chat(&upstream.openai_base_url(), Some("trace-me-7")).await;
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    for message in ["First token", "First large model token", "Request done"] {
+        let line = output
+            .lines()
+            .find(|line| line.contains(message))
+            .unwrap_or_else(|| panic!("no '{}' log in:\n{}", message, output));
+        assert!(line.contains("request_id=trace-me-7"), "{}", line);
+    }
+}
@@ -0,0 +1,237 @@
+mod common;
+
+use axum::{body::Body, http::Request, routing::post, Router};
+use http_body_util::BodyExt;
+use loro::{
+    config::{Config, ModelConfig, RouteConfig},
+    mock_upstream::MockScript,
+    routing::{matches_pattern, ModelRouter, ResponseMode, DIRECT_MODEL_ALIAS, QUICK_MODEL_ALIAS},
+    service::LoroService,
+};
+use secrecy::{ExposeSecret, Secret};
+use serial_test::serial;
+use std::env;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn model(base_url: &str, model_name: &str) -> ModelConfig {
+    ModelConfig {
+        api_key: Secret::new("test-key".to_string()),
+        base_url: base_url.to_string(),
+        model_name: model_name.to_string(),
+    }
+}
+
+fn create_test_config(model_routes: Vec<RouteConfig>) -> Config {
+    Config {
+        small_model: model("https://small.example.com/v1", "default-small"),
+        large_model: model("https://large.example.com/v1", "default-large"),
+        model_routes,
+        ..common::test_config("https://large.example.com/v1")
+    }
+}
+
+fn route(name: &str, large_model_name: &str) -> RouteConfig {
+    RouteConfig {
+        name: name.to_string(),
+        mode: None,
+        large_model: model("https://large.example.com/v1", large_model_name),
+        small_model: model("https://small.example.com/v1", "default-small"),
+        large_system_prompt: None,
+        quick_system_prompt: None,
+        max_tokens: None,
+    }
+}
+
+#[test]
+fn test_aliases_resolve_to_modes() {
+    let router = ModelRouter::from_config(&create_test_config(Vec::new()));
+
+    let quick = router.resolve(QUICK_MODEL_ALIAS);
+    assert_eq!(quick.mode, Some(ResponseMode::Quick));
+    assert_eq!(quick.large_model.model_name, "default-large");
+
+    let direct = router.resolve(DIRECT_MODEL_ALIAS);
+    assert_eq!(direct.mode, Some(ResponseMode::Direct));
+}
+
+#[test]
+fn test_unknown_model_uses_default_backends() {
+    let router = ModelRouter::from_config(&create_test_config(Vec::new()));
+
+    let route = router.resolve("loro-voice-assistant");
+    assert_eq!(route.mode, None);
+    assert_eq!(route.small_model.model_name, "default-small");
+    assert_eq!(route.large_model.model_name, "default-large");
+}
+
+#[test]
+fn test_model_ids_lists_aliases() {
+    let ids = ModelRouter::from_config(&create_test_config(Vec::new())).model_ids();
+    assert_eq!(ids, vec![QUICK_MODEL_ALIAS.to_string(), DIRECT_MODEL_ALIAS.to_string()]);
+}
+
+#[test]
+fn test_configured_routes_select_backend_and_defaults() {
+    let mut persona = route("assistant-kids", "kids-large");
+    persona.mode = Some(ResponseMode::Direct);
+    persona.large_system_prompt = Some("你是儿童助手".to_string());
+    persona.max_tokens = Some(64);
+
+    let router = ModelRouter::from_config(&create_test_config(vec![
+        persona,
+        route("qwen-*", "qwen-large"),
+    ]));
+
+    let resolved = router.resolve("assistant-kids");
+    assert_eq!(resolved.large_model.model_name, "kids-large");
+    assert_eq!(resolved.mode, Some(ResponseMode::Direct));
+    assert_eq!(resolved.large_system_prompt.as_deref(), Some("你是儿童助手"));
+    assert_eq!(resolved.max_tokens, Some(64));
+
+    assert_eq!(router.resolve("qwen-7b").large_model.model_name, "qwen-large");
+    assert_eq!(router.resolve("llama-3").large_model.model_name, "default-large");
+
+    // Patterns are not listed, concrete routes are
+    let ids = router.model_ids();
+    assert!(ids.contains(&"assistant-kids".to_string()));
+    assert!(!ids.iter().any(|id| id.contains('*')));
+}
+
+#[test]
+fn test_exact_routes_win_over_patterns() {
+    let router = ModelRouter::from_config(&create_test_config(vec![
+        route("qwen-*", "pattern-large"),
+        route("qwen-max", "exact-large"),
+    ]));
+
+    assert_eq!(router.resolve("qwen-max").large_model.model_name, "exact-large");
+    assert_eq!(router.resolve("qwen-plus").large_model.model_name, "pattern-large");
+}
+
+#[test]
+fn test_configured_route_can_override_alias() {
+    let router = ModelRouter::from_config(&create_test_config(vec![route(
+        QUICK_MODEL_ALIAS,
+        "override-large",
+    )]));
+
+    assert_eq!(
+        router.resolve(QUICK_MODEL_ALIAS).large_model.model_name,
+        "override-large"
+    );
+    let ids = router.model_ids();
+    assert_eq!(ids.iter().filter(|id| *id == QUICK_MODEL_ALIAS).count(), 1);
+}
+
+#[test]
+fn test_matches_pattern() {
+    assert!(matches_pattern("qwen-*", "qwen-7b"));
+    assert!(matches_pattern("qwen-*", "qwen-"));
+    assert!(matches_pattern("*-instruct", "llama-3-instruct"));
+    assert!(matches_pattern("a*b*c", "aXXbYYc"));
+    assert!(matches_pattern("*", "anything"));
+    assert!(matches_pattern("exact", "exact"));
+
+    assert!(!matches_pattern("qwen-*", "llama-7b"));
+    assert!(!matches_pattern("*-instruct", "llama-3-chat"));
+    assert!(!matches_pattern("ab*b", "ab"));
+    assert!(!matches_pattern("exact", "exactly"));
+}
+
+#[test]
+#[serial]
+fn test_routes_loaded_from_env() {
+    env::set_var("SMALL_MODEL_API_KEY", "test-small-key");
+    env::set_var("LARGE_MODEL_API_KEY", "test-large-key");
+    env::set_var("LARGE_MODEL_BASE_URL", "https://large.example.com/v1");
+    env::set_var("ROUTE_TEST_API_KEY", "route-key");
+    env::remove_var("MODEL_ROUTES_FILE");
+    env::set_var(
+        "MODEL_ROUTES",
+        r#"[
+            {"name": "assistant-pro", "mode": "direct", "max_tokens": 512,
+             "large_model": {"model_name": "pro-large", "api_key_env": "ROUTE_TEST_API_KEY"}},
+            {"name": "local-*", "large_model": {"base_url": "http://127.0.0.1:11434", "model_name": "qwen3"}}
+        ]"#,
+    );
+
+    let config = Config::from_env();
+    env::remove_var("MODEL_ROUTES");
+    env::remove_var("LARGE_MODEL_BASE_URL");
+    let config = config.unwrap();
+
+    assert_eq!(config.model_routes.len(), 2);
+    let pro = &config.model_routes[0];
+    assert_eq!(pro.mode, Some(ResponseMode::Direct));
+    assert_eq!(pro.max_tokens, Some(512));
+    assert_eq!(pro.large_model.model_name, "pro-large");
+    // Unset fields inherit from the default large model
+    assert_eq!(pro.large_model.base_url, "https://large.example.com/v1");
+    assert_eq!(pro.large_model.api_key.expose_secret(), "route-key");
+    assert_eq!(pro.small_model.api_key.expose_secret(), "test-small-key");
+
+    let local = &config.model_routes[1];
+    assert!(local.large_model.is_ollama());
+    assert_eq!(local.large_model.api_key.expose_secret(), "test-large-key");
+}
+
+#[test]
+#[serial]
+fn test_invalid_routes_rejected() {
+    env::set_var("SMALL_MODEL_API_KEY", "test-small-key");
+    env::set_var("LARGE_MODEL_API_KEY", "test-large-key");
+    env::remove_var("MODEL_ROUTES_FILE");
+
+    for routes in [
+        "not json",
+        r#"[{"name": "a"}, {"name": "a"}]"#,
+        r#"[{"name": " "}]"#,
+        r#"[{"name": "a", "max_tokens": 0}]"#,
+        r#"[{"name": "a", "large_model": {"base_url": "ftp://x"}}]"#,
+        r#"[{"name": "a", "unknown_field": true}]"#,
+    ] {
+        env::set_var("MODEL_ROUTES", routes);
+        assert!(Config::from_env().is_err(), "routes should be rejected: {routes}");
+    }
+    env::remove_var("MODEL_ROUTES");
+}
+
+#[tokio::test]
+async fn test_ollama_route_sends_generation_options() {
+    let upstream = common::mock_upstream(MockScript::default()).await;
+    let mut persona = route("assistant-kids", "kids-large");
+    persona.mode = Some(ResponseMode::Direct);
+    persona.large_model = common::model(&upstream.ollama_base_url(), "kids-large");
+    persona.max_tokens = Some(64);
+    let config = Config {
+        model_routes: vec![persona],
+        ..common::test_config(&upstream.ollama_base_url())
+    };
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+
+    let body = serde_json::json!({
+        "model": "assistant-kids",
+        "messages": [{"role": "user", "content": "讲个故事"}],
+        "temperature": 0.5,
+        "stop": "。"
+    });
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    response.into_body().collect().await.unwrap();
+
+    let calls = upstream.calls();
+    let options = &calls.iter().find(|call| call.is_stream()).unwrap().body["options"];
+    // The route's max_tokens applies when the request sets none
+    assert_eq!(options["num_predict"], 64);
+    assert_eq!(options["temperature"], 0.5);
+    assert_eq!(options["stop"], serde_json::json!(["。"]));
+}
@@ -1,36 +1,20 @@
+mod common;
+
 use loro::{
-    config::{Config, ModelConfig},
+    config::Config,
     models::{ChatCompletionRequest, Message, RequestCategory},
     service::LoroService,
 };
-use secrecy::Secret;
 
 fn create_test_config() -> Config {
-    Config {
-        host: "127.0.0.1".to_string(),
-        port: 3000,
-        log_level: "info".to_string(),
-        small_model: ModelConfig {
-            api_key: Secret::new("test-key-small".to_string()),
-            base_url: "https://api.openai.com/v1".to_string(),
-            model_name: "gpt-3.5-turbo".to_string(),
-        },
-        large_model: ModelConfig {
-            api_key: Secret::new("test-key-large".to_string()),
-            base_url: "https://api.openai.com/v1".to_string(),
-            model_name: "gpt-4".to_string(),
-        },
-        http_timeout_secs: 30,
-        small_model_timeout_secs: 10,
-        max_retries: 3,
-        stats_max_entries: 1000,
-    }
+    common::test_config("https://api.openai.com/v1")
 }
 
 fn create_test_message(content: &str) -> Message {
     Message {
         role: "user".to_string(),
-        content: content.to_string(),
+        content: content.into(),
+        ..Default::default()
     }
 }
 
@@ -137,6 +121,7 @@ async fn test_chat_completion_request_validation() {
         stream: true,
         stop: None,
         disable_quick_response: false,
+        ..Default::default()
     };
     
     let result = invalid_request.validate();
@@ -152,6 +137,7 @@ async fn test_chat_completion_request_validation() {
         stream: true,
         stop: None,
         disable_quick_response: false,
+        ..Default::default()
     };
     
     let result2 = invalid_request2.validate();
@@ -169,13 +155,15 @@ async fn test_chat_completion_message_validation() {
         model: "gpt-3.5-turbo".to_string(),
         messages: vec![Message {
             role: "invalid_role".to_string(),
-            content: "test content".to_string(),
+            content: "test content".into(),
+            ..Default::default()
         }],
         max_tokens: None,
         temperature: 0.7,
         stream: true,
         stop: None,
         disable_quick_response: false,
+        ..Default::default()
     };
     
     let result = invalid_request.validate();
@@ -187,13 +175,15 @@ async fn test_chat_completion_message_validation() {
         model: "gpt-3.5-turbo".to_string(),
         messages: vec![Message {
             role: "user".to_string(),
-            content: "".to_string(),
+            content: "".into(),
+            ..Default::default()
         }],
         max_tokens: None,
         temperature: 0.7,
         stream: true,
         stop: None,
         disable_quick_response: false,
+        ..Default::default()
     };
     
     let result2 = invalid_request2.validate();
@@ -215,6 +205,7 @@ async fn test_chat_completion_parameter_validation() {
         stream: true,
         stop: None,
         disable_quick_response: false,
+        ..Default::default()
     };
     
     let result = invalid_request.validate();
@@ -230,6 +221,7 @@ async fn test_chat_completion_parameter_validation() {
         stream: true,
         stop: None,
         disable_quick_response: false,
+        ..Default::default()
     };
     
     let result2 = invalid_request2.validate();
@@ -244,7 +236,8 @@ fn test_valid_message_roles() {
     for role in valid_roles {
         let message = Message {
             role: role.to_string(),
-            content: "test content".to_string(),
+            content: "test content".into(),
+            ..Default::default()
         };
         
         let request = ChatCompletionRequest {
@@ -255,6 +248,7 @@ fn test_valid_message_roles() {
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
         
         assert!(request.validate().is_ok(), "Role {} should be valid", role);
@@ -279,6 +273,7 @@ fn test_temperature_edge_cases() {
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
         
         let result = request.validate();
@@ -305,6 +300,7 @@ fn test_max_tokens_edge_cases() {
             stream: true,
             stop: None,
             disable_quick_response: false,
+            ..Default::default()
         };
         
         let result = request.validate();
@@ -0,0 +1,233 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+use loro::{
+    mock_upstream::{MockScript, ReplyScript},
+    service::LoroService,
+    shutdown::DrainState,
+};
+
+async fn create_test_service() -> Arc<LoroService> {
+    let config = common::test_config("https://api.openai.com/v1");
+
+    Arc::new(LoroService::new(config).await.unwrap())
+}
+
+fn create_test_app(service: Arc<LoroService>) -> Router {
+    Router::new()
+        .route("/health", get(loro::health))
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .route("/v1/audio/transcriptions", post(loro::audio_transcriptions))
+        .with_state(service)
+}
+
+fn chat_request() -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            json!({
+                "model": "test-model",
+                "messages": [{"role": "user", "content": "你好"}]
+            })
+            .to_string(),
+        ))
+        .unwrap()
+}
+
+async fn health_status(app: &Router) -> StatusCode {
+    let request = Request::builder()
+        .uri("/health")
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[test]
+fn test_drain_state_tracks_active_streams() {
+    let drain = DrainState::new();
+    assert!(!drain.is_draining());
+    assert_eq!(drain.active_streams(), 0);
+
+    let first = drain.track_stream();
+    let second = drain.track_stream();
+    assert_eq!(drain.active_streams(), 2);
+
+    drop(first);
+    assert_eq!(drain.active_streams(), 1);
+    drop(second);
+    assert_eq!(drain.active_streams(), 0);
+
+    drain.begin_draining();
+    assert!(drain.is_draining());
+}
+
+#[tokio::test]
+async fn test_draining_future_resolves_after_begin() {
+    let drain = Arc::new(DrainState::new());
+
+    let waiter = {
+        let drain = Arc::clone(&drain);
+        tokio::spawn(async move { drain.draining().await })
+    };
+
+    tokio::task::yield_now().await;
+    assert!(!waiter.is_finished());
+
+    drain.begin_draining();
+    tokio::time::timeout(std::time::Duration::from_secs(1), waiter)
+        .await
+        .expect("draining() should resolve once draining begins")
+        .unwrap();
+
+    // Already draining: resolves immediately
+    tokio::time::timeout(std::time::Duration::from_secs(1), drain.draining())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_health_reports_draining() {
+    let service = create_test_service().await;
+    let app = create_test_app(Arc::clone(&service));
+
+    let _stream = service.drain_state().track_stream();
+    service.drain_state().begin_draining();
+
+    let request = Request::builder()
+        .uri("/health")
+        .body(Body::empty())
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(json["status"], "draining");
+    assert_eq!(json["active_streams"], 1);
+}
+
+#[tokio::test]
+async fn test_chat_completion_rejected_while_draining() {
+    let service = create_test_service().await;
+    let app = create_test_app(Arc::clone(&service));
+    service.drain_state().begin_draining();
+
+    let response = app.oneshot(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "draining");
+}
+
+#[tokio::test]
+async fn test_transcription_rejected_while_draining() {
+    let service = create_test_service().await;
+    let app = create_test_app(Arc::clone(&service));
+    service.drain_state().begin_draining();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/audio/transcriptions")
+        .header("content-type", "audio/wav")
+        .body(Body::from("RIFF"))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["error"]["code"], "draining");
+}
+
+#[tokio::test]
+async fn test_health_fails_before_requests_are_refused() {
+    let upstream = common::mock_upstream(Default::default()).await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = create_test_app(Arc::clone(&service));
+
+    let drain = service.drain_state();
+    let pre_drain = {
+        let drain = Arc::clone(&drain);
+        tokio::spawn(async move { drain.drain_after(Duration::from_millis(500)).await })
+    };
+    tokio::task::yield_now().await;
+
+    // During the delay health checks fail, but requests are still served
+    assert!(drain.is_announced());
+    assert!(!drain.is_draining());
+    assert_eq!(health_status(&app).await, StatusCode::SERVICE_UNAVAILABLE);
+    let response = app.clone().oneshot(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.into_body().collect().await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(2), pre_drain)
+        .await
+        .expect("draining should begin after the delay")
+        .unwrap();
+    assert!(drain.is_draining());
+    let response = app.oneshot(chat_request()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_request_counts_as_active_while_the_quick_model_answers() {
+    let upstream = common::mock_upstream(MockScript {
+        quick: ReplyScript {
+            tokens: vec!["好的，".to_string()],
+            latency_ms: 500,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = create_test_app(Arc::clone(&service));
+    let drain = service.drain_state();
+
+    let request = tokio::spawn(app.oneshot(chat_request()));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    // Still waiting for the quick model; shutdown must wait for this request
+    assert_eq!(drain.active_streams(), 1);
+
+    let response = request.await.unwrap().unwrap();
+    response.into_body().collect().await.unwrap();
+    assert_eq!(drain.active_streams(), 0);
+}
+
+#[tokio::test]
+async fn test_zero_pre_drain_delay_drains_immediately() {
+    let drain = DrainState::new();
+    tokio::time::timeout(
+        Duration::from_millis(100),
+        drain.drain_after(Duration::ZERO),
+    )
+    .await
+    .unwrap();
+    assert!(drain.is_draining());
+    assert!(drain.is_announced());
+}
@@ -0,0 +1,427 @@
+mod common;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use base64::Engine;
+use futures::{SinkExt, StreamExt};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tower::ServiceExt;
+
+use loro::{
+    config::{Config, SpeechConfig},
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    models::RequestCategory,
+    service::LoroService,
+    speech::SentenceSplitter,
+};
+
+#[derive(Default)]
+struct MockState {
+    speech_requests: Vec<Value>,
+    fail_speech: bool,
+}
+
+type Shared = Arc<Mutex<MockState>>;
+
+// Audio is the input text prefixed with the voice; the quick acknowledgement is
+// synthesized slowly so out-of-order completion would show up in the output
+async fn mock_speech(
+    State(state): State<Shared>,
+    Json(body): Json<Value>,
+) -> axum::response::Response {
+    let input = body["input"].as_str().unwrap_or_default().to_string();
+    let voice = body["voice"].as_str().unwrap_or_default().to_string();
+    let fail = {
+        let mut state = state.lock().unwrap();
+        state.speech_requests.push(body);
+        state.fail_speech
+    };
+    if fail {
+        return (StatusCode::SERVICE_UNAVAILABLE, "voice not loaded").into_response();
+    }
+    if input == "好的，" {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    format!("{}:{}", voice, input).into_response()
+}
+
+/// The TTS backend runs beside the mock upstream, which only serves chat completions
+async fn spawn_upstreams() -> (MockUpstream, String, Shared) {
+    let upstream = common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: ["今天是晴天。明天", "会下雨！记得", "带伞"]
+                .map(String::from)
+                .to_vec(),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let state: Shared = Arc::new(Mutex::new(MockState::default()));
+    let app = Router::new()
+        .route("/v1/audio/speech", post(mock_speech))
+        .with_state(Arc::clone(&state));
+    let speech_url = format!("{}/v1", common::serve(app).await);
+    (upstream, speech_url, state)
+}
+
+fn create_test_config(upstream: &MockUpstream, speech_url: &str, speech: bool) -> Config {
+    Config {
+        speech: speech.then(|| SpeechConfig {
+            backend: common::model(speech_url, "kokoro"),
+            voice: "zf_xiaobei".to_string(),
+            format: "wav".to_string(),
+            max_concurrency: 3,
+            precache: true,
+        }),
+        ..common::test_config(&upstream.openai_base_url())
+    }
+}
+
+async fn create_service(speech: bool) -> (Arc<LoroService>, Shared) {
+    let (upstream, speech_url, state) = spawn_upstreams().await;
+    let service = LoroService::new(create_test_config(&upstream, &speech_url, speech))
+        .await
+        .unwrap();
+    (Arc::new(service), state)
+}
+
+fn app(service: Arc<LoroService>) -> Router {
+    Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .route("/v1/realtime", get(loro::realtime))
+        .with_state(service)
+}
+
+fn chat_request(body: Value) -> Request<Body> {
+    Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+/// (event name, data) pairs of an SSE body
+fn sse_events(body: &str) -> Vec<(String, String)> {
+    body.split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| {
+            let mut event = "message".to_string();
+            let mut data = String::new();
+            for line in block.lines() {
+                if let Some(name) = line.strip_prefix("event: ") {
+                    event = name.to_string();
+                } else if let Some(payload) = line.strip_prefix("data: ") {
+                    data = payload.to_string();
+                }
+            }
+            (event, data)
+        })
+        .collect()
+}
+
+fn decode(data: &Value) -> String {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data.as_str().unwrap())
+        .unwrap();
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn test_sentence_splitter_streams_sentences() {
+    let mut splitter = SentenceSplitter::new();
+    assert!(splitter.push("今天是").is_empty());
+    assert_eq!(splitter.push("晴天。明天"), vec!["今天是晴天。"]);
+    assert_eq!(splitter.push("会下雨！！记得"), vec!["明天会下雨！！"]);
+    assert_eq!(splitter.finish().as_deref(), Some("记得"));
+    assert_eq!(splitter.finish(), None);
+}
+
+#[test]
+fn test_sentence_splitter_handles_periods_and_quotes() {
+    let mut splitter = SentenceSplitter::new();
+    assert!(splitter.push("It costs 3.5 dollars.").is_empty());
+    assert_eq!(
+        splitter.push(" He said “好。”然后"),
+        vec!["It costs 3.5 dollars.", "He said “好。”"]
+    );
+    assert_eq!(splitter.finish().as_deref(), Some("然后"));
+}
+
+#[test]
+fn test_sentence_splitter_breaks_overlong_clauses() {
+    let mut splitter = SentenceSplitter::new();
+    let clause = "很长的一段话".repeat(10);
+    let sentences = splitter.push(&format!("{}，{}", clause, clause));
+    assert_eq!(sentences, vec![format!("{}，", clause)]);
+    assert_eq!(splitter.finish(), Some(clause));
+}
+
+#[tokio::test]
+async fn test_chat_completion_streams_audio_in_order() {
+    let (service, state) = create_service(true).await;
+
+    let response = app(service)
+        .oneshot(chat_request(json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "明天天气怎么样"}],
+            "modalities": ["text", "audio"]
+        })))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let events = sse_events(std::str::from_utf8(&body).unwrap());
+
+    let audio: Vec<Value> = events
+        .iter()
+        .filter(|(event, _)| event == "audio")
+        .map(|(_, data)| serde_json::from_str(data).unwrap())
+        .collect();
+    let texts: Vec<&str> = audio.iter().map(|a| a["text"].as_str().unwrap()).collect();
+    assert_eq!(
+        texts,
+        vec!["好的，", "今天是晴天。", "明天会下雨！", "记得带伞"]
+    );
+    for (i, segment) in audio.iter().enumerate() {
+        assert_eq!(segment["index"], i);
+        assert_eq!(segment["format"], "wav");
+        assert_eq!(decode(&segment["data"]), format!("zf_xiaobei:{}", texts[i]));
+    }
+    assert_eq!(audio[0]["source"], "quick");
+    assert_eq!(audio[1]["source"], "large");
+
+    // Text chunks are still forwarded, and [DONE] comes after the last audio segment
+    let text_chunks = events
+        .iter()
+        .filter(|(event, _)| event == "message")
+        .count();
+    // quick chunk, three tokens, the finish chunk and [DONE]
+    assert_eq!(text_chunks, 6);
+    assert_eq!(events.last().unwrap().1, "[DONE]");
+    assert_eq!(state.lock().unwrap().speech_requests[0]["model"], "kokoro");
+}
+
+#[tokio::test]
+async fn test_quick_responses_are_pre_rendered() {
+    let (service, state) = create_service(true).await;
+    service.spawn_speech_warmup().unwrap().await.unwrap();
+
+    let phrases: usize = RequestCategory::ALL
+        .iter()
+        .map(|category| category.get_responses().len())
+        .sum();
+    assert_eq!(state.lock().unwrap().speech_requests.len(), phrases);
+
+    let response = app(service)
+        .oneshot(chat_request(json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "明天天气怎么样"}],
+            "modalities": ["text", "audio"]
+        })))
+        .await
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let events = sse_events(std::str::from_utf8(&body).unwrap());
+    let quick: Value =
+        serde_json::from_str(&events.iter().find(|(event, _)| event == "audio").unwrap().1)
+            .unwrap();
+    assert_eq!(quick["source"], "quick");
+    assert_eq!(decode(&quick["data"]), "zf_xiaobei:好的，");
+
+    // Only the three large model sentences reach the TTS backend
+    let state = state.lock().unwrap();
+    assert_eq!(state.speech_requests.len(), phrases + 3);
+    assert!(state.speech_requests[phrases..]
+        .iter()
+        .all(|request| request["input"] != "好的，"));
+}
+
+#[tokio::test]
+async fn test_pre_rendering_can_be_disabled() {
+    let (upstream, speech_url, state) = spawn_upstreams().await;
+    let mut config = create_test_config(&upstream, &speech_url, true);
+    config.speech.as_mut().unwrap().precache = false;
+    let service = LoroService::new(config).await.unwrap();
+
+    assert!(service.spawn_speech_warmup().is_none());
+    assert!(state.lock().unwrap().speech_requests.is_empty());
+}
+
+#[tokio::test]
+async fn test_audio_options_override_voice_and_format() {
+    let (service, state) = create_service(true).await;
+
+    let response = app(service)
+        .oneshot(chat_request(json!({
+            "model": "loro-direct",
+            "messages": [{"role": "user", "content": "明天天气怎么样"}],
+            "modalities": ["text", "audio"],
+            "audio": {"voice": "zm_yunxi", "format": "opus"}
+        })))
+        .await
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let events = sse_events(std::str::from_utf8(&body).unwrap());
+    let first: Value =
+        serde_json::from_str(&events.iter().find(|(event, _)| event == "audio").unwrap().1)
+            .unwrap();
+
+    // Direct mode has no quick acknowledgement to speak
+    assert_eq!(first["source"], "large");
+    assert_eq!(first["format"], "opus");
+    let state = state.lock().unwrap();
+    assert_eq!(state.speech_requests[0]["voice"], "zm_yunxi");
+    assert_eq!(state.speech_requests[0]["response_format"], "opus");
+}
+
+#[tokio::test]
+async fn test_text_only_request_skips_synthesis() {
+    let (service, state) = create_service(true).await;
+
+    let response = app(service)
+        .oneshot(chat_request(json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "你好"}]
+        })))
+        .await
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert!(!std::str::from_utf8(&body).unwrap().contains("event: audio"));
+    assert!(state.lock().unwrap().speech_requests.is_empty());
+}
+
+#[tokio::test]
+async fn test_audio_requested_without_tts_backend() {
+    let (service, _) = create_service(false).await;
+
+    let response = app(service)
+        .oneshot(chat_request(json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "你好"}],
+            "modalities": ["audio"]
+        })))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let body: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"]["code"], "speech_not_configured");
+}
+
+#[tokio::test]
+async fn test_invalid_audio_format_is_rejected() {
+    let (service, _) = create_service(true).await;
+
+    let response = app(service)
+        .oneshot(chat_request(json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "你好"}],
+            "modalities": ["text", "audio"],
+            "audio": {"format": "midi"}
+        })))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_synthesis_failure_reports_segment_error() {
+    let (service, state) = create_service(true).await;
+    state.lock().unwrap().fail_speech = true;
+
+    let response = app(service)
+        .oneshot(chat_request(json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "你好"}],
+            "modalities": ["text", "audio"]
+        })))
+        .await
+        .unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let events = sse_events(std::str::from_utf8(&body).unwrap());
+
+    let audio: Vec<Value> = events
+        .iter()
+        .filter(|(event, _)| event == "audio")
+        .map(|(_, data)| serde_json::from_str(data).unwrap())
+        .collect();
+    assert_eq!(audio.len(), 4);
+    assert!(audio
+        .iter()
+        .all(|a| a["error"].is_string() && a["data"].is_null()));
+    assert_eq!(events.last().unwrap().1, "[DONE]");
+}
+
+#[tokio::test]
+async fn test_realtime_sends_audio_as_binary_frames() {
+    let (service, _) = create_service(true).await;
+    let base_url = common::serve(app(service)).await;
+    let (mut client, _) = connect_async(format!(
+        "{}/v1/realtime",
+        base_url.replace("http://", "ws://")
+    ))
+    .await
+    .unwrap();
+
+    client
+        .send(Message::Text(
+            json!({"type": "session.update", "session": {"modalities": ["text", "audio"]}})
+                .to_string(),
+        ))
+        .await
+        .unwrap();
+    client
+        .send(Message::Text(
+            json!({"type": "turn", "content": "明天天气怎么样"}).to_string(),
+        ))
+        .await
+        .unwrap();
+
+    let mut segments = Vec::new();
+    let mut pending: Option<Value> = None;
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        match message {
+            Message::Text(text) => {
+                let event: Value = serde_json::from_str(&text).unwrap();
+                assert!(
+                    pending.is_none(),
+                    "audio header must be followed by its frame"
+                );
+                match event["type"].as_str().unwrap() {
+                    "response.audio" => pending = Some(event),
+                    "turn.done" => break,
+                    _ => {}
+                }
+            }
+            Message::Binary(audio) => {
+                let header = pending.take().expect("binary frame without header");
+                segments.push((
+                    header["index"].as_u64().unwrap(),
+                    String::from_utf8(audio).unwrap(),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    assert_eq!(segments.len(), 4);
+    assert_eq!(segments[0], (0, "zf_xiaobei:好的，".to_string()));
+    assert_eq!(segments[3], (3, "zf_xiaobei:记得带伞".to_string()));
+}
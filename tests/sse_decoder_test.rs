@@ -0,0 +1,211 @@
+use loro::sse::{SseDecoder, SseEvent};
+use proptest::prelude::*;
+use proptest::sample::Index;
+use std::time::Duration;
+
+fn decode(chunks: &[&[u8]]) -> Vec<SseEvent> {
+    let mut decoder = SseDecoder::new();
+    chunks
+        .iter()
+        .flat_map(|chunk| decoder.feed(chunk).unwrap())
+        .collect()
+}
+
+fn message(data: &str, id: Option<&str>) -> SseEvent {
+    SseEvent {
+        event: "message".to_string(),
+        data: data.to_string(),
+        id: id.map(String::from),
+    }
+}
+
+#[test]
+fn test_multi_line_data_is_joined() {
+    let events = decode(&[b"data: YHOO\ndata: +2\ndata: 10\n\n"]);
+    assert_eq!(events, vec![message("YHOO\n+2\n10", None)]);
+}
+
+#[test]
+fn test_fields_and_comments() {
+    let stream = b": keep-alive\n\
+        event: update\nid: 7\ndata:no space\n\n\
+        data\n\n\
+        id\ndata:  two spaces\n\n";
+    let events = decode(&[stream]);
+    assert_eq!(
+        events,
+        vec![
+            SseEvent {
+                event: "update".to_string(),
+                data: "no space".to_string(),
+                id: Some("7".to_string()),
+            },
+            // A field without a colon has an empty value; the event type resets
+            message("", Some("7")),
+            // Only one leading space is removed, and an empty `id` clears it
+            message(" two spaces", Some("")),
+        ]
+    );
+}
+
+#[test]
+fn test_events_without_data_are_not_dispatched() {
+    let events = decode(&[b"event: ping\n\nid: 3\n\nunknown: field\n\n\n"]);
+    assert!(events.is_empty());
+}
+
+#[test]
+fn test_line_endings() {
+    let events = decode(&[b"data: a\r\n\r\ndata: b\r\rdata: c\n\n"]);
+    assert_eq!(
+        events,
+        vec![message("a", None), message("b", None), message("c", None)]
+    );
+}
+
+#[test]
+fn test_crlf_split_across_chunks_is_one_line_break() {
+    let events = decode(&[b"data: a\r", b"\ndata: b\r", b"", b"\n\r", b"\n"]);
+    assert_eq!(events, vec![message("a\nb", None)]);
+}
+
+#[test]
+fn test_multi_byte_character_split_across_chunks() {
+    let bytes = "data: 今天是晴天\n\n".as_bytes();
+    // Cut inside the first character
+    let events = decode(&[&bytes[..7], &bytes[7..]]);
+    assert_eq!(events, vec![message("今天是晴天", None)]);
+}
+
+#[test]
+fn test_leading_byte_order_mark_is_dropped() {
+    let events = decode(&[b"\xEF\xBB", b"\xBFdata: x\n\n"]);
+    assert_eq!(events, vec![message("x", None)]);
+}
+
+#[test]
+fn test_retry_and_id() {
+    let mut decoder = SseDecoder::new();
+    decoder
+        .feed(b"retry: 1500\nretry: 1s\nid: 9\nid: a\0b\n\n")
+        .unwrap();
+    assert_eq!(decoder.retry(), Some(Duration::from_millis(1500)));
+    // Ids containing NUL are ignored
+    assert_eq!(decoder.last_event_id(), Some("9"));
+}
+
+#[test]
+fn test_incomplete_event_at_end_is_pending() {
+    let mut decoder = SseDecoder::new();
+    assert!(decoder.feed(b"data: a\n").unwrap().is_empty());
+    assert!(decoder.feed(b"data: b").unwrap().is_empty());
+    assert_eq!(decoder.feed(b"\n\n").unwrap(), vec![message("a\nb", None)]);
+}
+
+#[test]
+fn test_buffer_is_bounded() {
+    let mut decoder = SseDecoder::with_limit(16);
+    decoder.feed(b"data: 0123456789").unwrap();
+    assert!(decoder.feed(b"X").is_err());
+
+    // Each line fits, the event does not
+    let mut decoder = SseDecoder::with_limit(16);
+    decoder.feed(b"data: 01234\ndata: 56789\n").unwrap();
+    assert!(decoder.feed(b"data: abcde\n").is_err());
+
+    // A long line that arrives whole in one chunk is rejected too
+    let mut decoder = SseDecoder::with_limit(16);
+    assert!(decoder.feed(b": a comment longer than the limit\n").is_err());
+}
+
+/// One event to encode: optional type, data lines, optional id and retry
+#[derive(Debug, Clone)]
+struct Spec {
+    comment: Option<String>,
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+    retry: Option<u32>,
+}
+
+fn spec() -> impl Strategy<Value = Spec> {
+    let text = "[a-z0-9 :{}\"，。今天是晴天好的😀]{0,12}";
+    (
+        proptest::option::of(text),
+        proptest::option::of("[a-z]{1,8}"),
+        proptest::collection::vec(text, 1..4),
+        proptest::option::of("[0-9]{1,4}"),
+        proptest::option::of(any::<u32>()),
+    )
+        .prop_map(|(comment, event, data, id, retry)| Spec {
+            comment,
+            event,
+            data,
+            id,
+            retry,
+        })
+}
+
+fn encode(specs: &[Spec], eol: &str) -> Vec<u8> {
+    let mut out = String::new();
+    for spec in specs {
+        if let Some(comment) = &spec.comment {
+            out += &format!(":{comment}{eol}");
+        }
+        if let Some(event) = &spec.event {
+            out += &format!("event: {event}{eol}");
+        }
+        if let Some(id) = &spec.id {
+            out += &format!("id: {id}{eol}");
+        }
+        if let Some(retry) = spec.retry {
+            out += &format!("retry: {retry}{eol}");
+        }
+        for line in &spec.data {
+            out += &format!("data: {line}{eol}");
+        }
+        out += eol;
+    }
+    out.into_bytes()
+}
+
+fn expected(specs: &[Spec]) -> Vec<SseEvent> {
+    let mut id = None;
+    specs
+        .iter()
+        .map(|spec| {
+            id = spec.id.clone().or(id.take());
+            SseEvent {
+                event: spec.event.clone().unwrap_or_else(|| "message".to_string()),
+                data: spec.data.join("\n"),
+                id: id.clone(),
+            }
+        })
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn prop_arbitrary_chunk_splits_decode_identically(
+        specs in proptest::collection::vec(spec(), 0..6),
+        eol in prop_oneof![Just("\n"), Just("\r"), Just("\r\n")],
+        cuts in proptest::collection::vec(any::<Index>(), 0..10),
+    ) {
+        let bytes = encode(&specs, eol);
+        let mut cuts: Vec<usize> = cuts.iter().map(|cut| cut.index(bytes.len() + 1)).collect();
+        cuts.sort_unstable();
+
+        let mut decoder = SseDecoder::new();
+        let mut events = Vec::new();
+        let mut start = 0;
+        for cut in cuts.into_iter().chain([bytes.len()]) {
+            events.extend(decoder.feed(&bytes[start..cut]).unwrap());
+            start = cut;
+        }
+
+        prop_assert_eq!(&events, &decode(&[&bytes]));
+        prop_assert_eq!(events, expected(&specs));
+        let retry = specs.iter().rev().find_map(|spec| spec.retry);
+        prop_assert_eq!(decoder.retry(), retry.map(|ms| Duration::from_millis(ms.into())));
+    }
+}
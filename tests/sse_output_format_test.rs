@@ -1,4 +1,5 @@
 use loro::service::LoroService;
+use serde_json::json;
 
 #[test]
 fn test_process_sse_line_static_returns_json_payload_only() {
@@ -25,4 +26,30 @@ fn test_process_ollama_line_static_parses_json_lines() {
     assert_eq!(v["object"], "chat.completion.chunk");
 }
 
+#[test]
+fn test_oversized_chunks_are_errors_on_both_paths() {
+    // 超过 1 MiB 的内容在 SSE 与 Ollama 两条路径上都返回错误
+    let content = "好".repeat(400_000);
+    let line = format!(
+        "data: {}",
+        json!({"choices": [{"delta": {"content": content}}]})
+    );
+    assert!(LoroService::process_sse_line_static(&line, "rid", "model").is_err());
+
+    // 引号在工具参数里被再次转义，渲染结果比原始行大一倍
+    let line = json!({
+        "model": "qwen",
+        "created_at": "2024-01-01",
+        "message": {
+            "role": "assistant",
+            "content": "",
+            "tool_calls": [{"function": {"name": "f", "arguments": {"q": "\"".repeat(300_000)}}}]
+        },
+        "done": false
+    })
+    .to_string();
+    assert!(line.len() < 1024 * 1024);
+    assert!(LoroService::process_ollama_line_static(&line, "rid", "model").is_err());
+}
+
 // 快速响应长度逻辑在已有测试覆盖（responses <= 6 chars）。此处不测私有方法。
@@ -0,0 +1,228 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use loro::{
+    config::{Config, SpeechConfig},
+    errors::{ErrorBody, LoroError},
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+};
+
+async fn mock_speech(Json(body): Json<Value>) -> String {
+    body["input"].as_str().unwrap_or_default().to_string()
+}
+
+/// Large model that reports a failure in-band with an SSE `error` event
+async fn mock_error_event() -> ([(&'static str, &'static str); 1], &'static str) {
+    (
+        [("content-type", "text/event-stream")],
+        concat!(
+            "data: {\"choices\":[{\"delta\":{\"content\":\"今天\"}}]}\n\n",
+            "event: error\n",
+            "data: {\"message\":\"model overloaded\"}\n\n",
+        ),
+    )
+}
+
+/// The large model sends one chunk and then drops the connection
+async fn spawn_mock_upstream() -> MockUpstream {
+    common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["今天是晴天。明天".to_string(), "会下雨".to_string()],
+            token_interval_ms: 50,
+            disconnect_after: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await
+}
+
+fn create_test_config(upstream: &MockUpstream) -> Config {
+    Config {
+        error_apology: Some("抱歉，出现了问题。".to_string()),
+        ..common::test_config(&upstream.openai_base_url())
+    }
+}
+
+/// Streams a chat request through a service built from `config` and returns the SSE data payloads
+async fn stream_payloads(config: Config, body: Value) -> Vec<String> {
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(status, StatusCode::OK, "{:?}", bytes);
+    std::str::from_utf8(&bytes)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(str::to_string)
+        .collect()
+}
+
+fn error_payload(payloads: &[String]) -> Value {
+    payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+        .find(|payload| payload.get("error").is_some())
+        .expect("stream should contain an error event")
+}
+
+#[test]
+fn test_loro_errors_map_to_typed_bodies() {
+    let timeout = anyhow::Error::from(LoroError::Timeout { timeout_secs: 5 });
+    let body = ErrorBody::from_error(&timeout);
+    assert_eq!(body.error_type, "timeout_error");
+    assert_eq!(body.code, "upstream_timeout");
+
+    let upstream = anyhow::Error::from(LoroError::ApiError {
+        provider: "openai".to_string(),
+        status: 401,
+        message: "invalid key sk-secret".to_string(),
+    });
+    let body = ErrorBody::from_error(&upstream);
+    assert_eq!(body.code, "upstream_error");
+    assert!(!body.message.contains("sk-secret"));
+
+    // Untyped errors never leak their text
+    let body = ErrorBody::from_error(&anyhow::anyhow!("lock poisoned at 0xdeadbeef"));
+    assert_eq!(body.code, "internal_error");
+    assert_eq!(body.message, "Internal server error");
+}
+
+#[tokio::test]
+async fn test_interrupted_stream_emits_error_event() {
+    let upstream = spawn_mock_upstream().await;
+    let payloads = stream_payloads(
+        create_test_config(&upstream),
+        json!({"model": "loro-quick", "messages": [{"role": "user", "content": "明天天气怎么样"}]}),
+    )
+    .await;
+
+    let error = error_payload(&payloads);
+    assert_eq!(error["error"]["type"], "api_error");
+    assert_eq!(error["error"]["code"], "stream_interrupted");
+    assert_eq!(error["error"]["apology"], "抱歉，出现了问题。");
+    assert!(!error["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("upstream went away"));
+
+    // The apology is not disguised as assistant content, and the stream still terminates
+    assert!(payloads
+        .iter()
+        .all(|payload| payload.contains("\"error\"") || !payload.contains("抱歉")));
+    assert!(payloads
+        .iter()
+        .all(|payload| !payload.starts_with("[ERROR")));
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+}
+
+#[tokio::test]
+async fn test_apology_can_be_disabled() {
+    let upstream = spawn_mock_upstream().await;
+    let mut config = create_test_config(&upstream);
+    config.error_apology = None;
+    let payloads = stream_payloads(
+        config,
+        json!({"model": "loro-direct", "messages": [{"role": "user", "content": "你好"}]}),
+    )
+    .await;
+
+    let error = error_payload(&payloads);
+    assert_eq!(error["error"]["code"], "stream_interrupted");
+    assert!(error["error"].get("apology").is_none());
+}
+
+#[tokio::test]
+async fn test_apology_is_spoken_after_partial_reply() {
+    let upstream = spawn_mock_upstream().await;
+    let mut config = create_test_config(&upstream);
+    let speech_app = Router::new().route("/v1/audio/speech", post(mock_speech));
+    let speech_url = format!("{}/v1", common::serve(speech_app).await);
+    config.speech = Some(SpeechConfig {
+        backend: common::model(&speech_url, "tts"),
+        voice: "alloy".to_string(),
+        format: "mp3".to_string(),
+        max_concurrency: 2,
+        precache: false,
+    });
+    let payloads = stream_payloads(
+        config,
+        json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "明天天气怎么样"}],
+            "modalities": ["text", "audio"]
+        }),
+    )
+    .await;
+
+    let segments: Vec<Value> = payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str::<Value>(payload).ok())
+        .filter(|payload| payload.get("source").is_some())
+        .collect();
+    let spoken: Vec<(&str, &str)> = segments
+        .iter()
+        .map(|s| (s["source"].as_str().unwrap(), s["text"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        spoken,
+        vec![
+            ("quick", "好的，"),
+            ("large", "今天是晴天。"),
+            ("large", "明天"),
+            ("apology", "抱歉，出现了问题。"),
+        ]
+    );
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+}
+
+#[tokio::test]
+async fn test_upstream_error_event_is_large_model_failure() {
+    let upstream = Router::new().route("/v1/chat/completions", post(mock_error_event));
+    let base_url = format!("{}/v1", common::serve(upstream).await);
+    let config = Config {
+        expose_upstream_errors: true,
+        ..common::test_config(&base_url)
+    };
+    let payloads = stream_payloads(
+        config,
+        json!({"model": "loro-direct", "messages": [{"role": "user", "content": "你好"}]}),
+    )
+    .await;
+
+    let error = error_payload(&payloads);
+    assert_eq!(error["error"]["type"], "api_error");
+    assert_eq!(error["error"]["code"], "large_model_failed");
+    assert_eq!(error["error"]["message"], "Large model request failed");
+    assert_eq!(error["error"]["upstream"]["provider"], "large");
+    assert!(error["error"]["upstream"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("model overloaded"));
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+}
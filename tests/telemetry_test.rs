@@ -0,0 +1,160 @@
+mod common;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::Request,
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+use loro::{
+    config::{OtlpProtocol, TelemetryConfig},
+    mock_upstream::{MockScript, ReplyScript},
+    service::LoroService,
+    telemetry,
+};
+
+/// OTLP/HTTP JSON export requests received by the collector
+type Exports = Arc<Mutex<Vec<Value>>>;
+
+async fn collect_traces(State(exports): State<Exports>, body: Bytes) {
+    exports
+        .lock()
+        .unwrap()
+        .push(serde_json::from_slice(&body).unwrap());
+}
+
+fn attribute<'a>(span: &'a Value, key: &str) -> Option<&'a Value> {
+    span["attributes"]
+        .as_array()?
+        .iter()
+        .find(|attribute| attribute["key"] == key)
+        .map(|attribute| &attribute["value"])
+}
+
+#[test]
+fn test_traces_endpoint() {
+    assert_eq!(
+        telemetry::traces_endpoint("http://collector:4318"),
+        "http://collector:4318/v1/traces"
+    );
+    assert_eq!(
+        telemetry::traces_endpoint("http://collector:4318/v1/traces/"),
+        "http://collector:4318/v1/traces"
+    );
+}
+
+// Multi-threaded so the collector keeps serving while the test thread waits on the flush
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_request_stages_are_exported_over_otlp() {
+    let exports = Exports::default();
+    let collector = common::serve(
+        Router::new()
+            .route("/v1/traces", post(collect_traces))
+            .with_state(Arc::clone(&exports)),
+    )
+    .await;
+    // The small model answers with a full sentence, so the quick response falls back
+    let upstream = common::mock_upstream(MockScript {
+        quick: ReplyScript {
+            tokens: vec!["今天天气很好，适合出门散步。".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let provider = telemetry::tracer_provider(&TelemetryConfig {
+        endpoint: collector,
+        service_name: "loro-test".to_string(),
+        protocol: OtlpProtocol::HttpJson,
+    })
+    .unwrap();
+    let subscriber = tracing_subscriber::registry().with(telemetry::layer(&provider));
+    let guard = tracing::subscriber::set_default(subscriber);
+
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let body =
+        json!({"model": "loro-quick", "messages": [{"role": "user", "content": "今天天气怎么样"}]});
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .header("x-request-id", "otel-req-1")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    response.into_body().collect().await.unwrap();
+    drop(guard);
+
+    telemetry::shutdown(Some(provider)).await;
+
+    let exports = exports.lock().unwrap();
+    let resource_spans =
+        &exports.first().expect("collector received no export")["resourceSpans"][0];
+    let service_name = attribute(&resource_spans["resource"], "service.name").unwrap();
+    assert_eq!(service_name["stringValue"], "loro-test");
+
+    let spans: Vec<&Value> = exports
+        .iter()
+        .flat_map(|export| export["resourceSpans"].as_array().unwrap())
+        .flat_map(|resource| resource["scopeSpans"].as_array().unwrap())
+        .flat_map(|scope| scope["spans"].as_array().unwrap())
+        .collect();
+    let span = |name: &str| {
+        *spans
+            .iter()
+            .find(|span| span["name"] == name)
+            .unwrap_or_else(|| panic!("no '{}' span in {:?}", name, spans))
+    };
+
+    let request = span("request");
+    assert_eq!(
+        attribute(request, "request_id").unwrap()["stringValue"],
+        "otel-req-1"
+    );
+    assert_eq!(attribute(request, "mode").unwrap()["stringValue"], "quick");
+
+    let small = span("small_model");
+    assert_eq!(
+        attribute(small, "model").unwrap()["stringValue"],
+        "small-model"
+    );
+    assert_eq!(attribute(small, "fallback").unwrap()["boolValue"], true);
+    assert_eq!(attribute(small, "retries").unwrap()["intValue"], "0");
+
+    let large = span("large_model_connect");
+    assert_eq!(
+        attribute(large, "model").unwrap()["stringValue"],
+        "large-model"
+    );
+
+    // Every stage belongs to the request's trace
+    for name in [
+        "small_model",
+        "large_model_connect",
+        "time_to_first_token",
+        "stream",
+    ] {
+        let stage = span(name);
+        assert_eq!(stage["traceId"], request["traceId"], "{}", name);
+        assert_eq!(stage["parentSpanId"], request["spanId"], "{}", name);
+    }
+}
@@ -0,0 +1,306 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::json;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use loro::{
+    mock_upstream::{MockScript, MockToolCall, MockUpstream, ReplyScript},
+    models::ChatCompletionRequest,
+    service::LoroService,
+};
+
+const TOOL_CALL_SSE: &str = concat!(
+    "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\",\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"type\":\"function\",\"function\":{\"name\":\"set_light\",\"arguments\":\"\"}}]}}]}\n\n",
+    "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"{\\\"on\\\":true}\"}}]}}]}\n\n",
+    "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"tool_calls\"}]}\n\n",
+    "data: [DONE]\n\n",
+);
+
+/// The large model streams a single `set_light` call, finishing with `finish_reason`
+async fn spawn_mock_upstream(finish_reason: &str) -> MockUpstream {
+    common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: Vec::new(),
+            tool_calls: vec![MockToolCall {
+                name: "set_light".to_string(),
+                arguments: json!({"on": true}),
+            }],
+            finish_reason: finish_reason.to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await
+}
+
+fn tool_request() -> serde_json::Value {
+    json!({
+        "model": "loro-quick",
+        "messages": [{"role": "user", "content": "请把客厅的灯打开"}],
+        "tools": [{
+            "type": "function",
+            "function": {
+                "name": "set_light",
+                "description": "Turn a light on or off",
+                "parameters": {"type": "object", "properties": {"on": {"type": "boolean"}}}
+            }
+        }],
+        "tool_choice": "auto"
+    })
+}
+
+fn sse_payloads(body: &str) -> Vec<String> {
+    body.lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn test_request_with_tools_deserializes_and_validates() {
+    let request: ChatCompletionRequest = serde_json::from_value(tool_request()).unwrap();
+    assert!(request.validate().is_ok());
+    let tools = request.tools.as_ref().unwrap();
+    assert_eq!(tools[0].function.name, "set_light");
+    assert_eq!(request.tool_choice, Some(json!("auto")));
+}
+
+#[test]
+fn test_tool_result_conversation_validates() {
+    let request: ChatCompletionRequest = serde_json::from_value(json!({
+        "model": "loro-quick",
+        "messages": [
+            {"role": "user", "content": "开灯"},
+            {"role": "assistant", "content": null, "tool_calls": [{
+                "id": "call_1", "type": "function",
+                "function": {"name": "set_light", "arguments": "{\"on\":true}"}
+            }]},
+            {"role": "tool", "tool_call_id": "call_1", "content": "{\"ok\":true}"}
+        ]
+    }))
+    .unwrap();
+
+    assert!(request.validate().is_ok());
+    assert!(request.messages[1].content.is_empty());
+    assert_eq!(request.messages[2].tool_call_id.as_deref(), Some("call_1"));
+}
+
+#[test]
+fn test_tool_message_requires_tool_call_id() {
+    let request: ChatCompletionRequest = serde_json::from_value(json!({
+        "model": "loro-quick",
+        "messages": [
+            {"role": "user", "content": "开灯"},
+            {"role": "tool", "content": "{\"ok\":true}"}
+        ]
+    }))
+    .unwrap();
+    assert!(request.validate().unwrap_err().contains("tool_call_id"));
+}
+
+#[test]
+fn test_unsupported_tool_type_rejected() {
+    let mut body = tool_request();
+    body["tools"][0]["type"] = json!("retrieval");
+    let request: ChatCompletionRequest = serde_json::from_value(body).unwrap();
+    assert!(request.validate().is_err());
+}
+
+#[test]
+fn test_sse_line_forwards_tool_call_deltas() {
+    let line = TOOL_CALL_SSE.lines().next().unwrap();
+    let out = LoroService::process_sse_line_static(line, "rid", "model")
+        .unwrap()
+        .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&out).unwrap();
+    let call = &v["choices"][0]["delta"]["tool_calls"][0];
+    assert_eq!(call["index"], 0);
+    assert_eq!(call["id"], "call_1");
+    assert_eq!(call["function"]["name"], "set_light");
+    assert!(v["choices"][0]["delta"].get("content").is_none());
+}
+
+#[test]
+fn test_sse_line_forwards_tool_calls_finish_reason() {
+    let line = TOOL_CALL_SSE.lines().nth(4).unwrap();
+    let out = LoroService::process_sse_line_static(line, "rid", "model")
+        .unwrap()
+        .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(v["choices"][0]["finish_reason"], "tool_calls");
+}
+
+#[test]
+fn test_ollama_line_converts_tool_calls() {
+    let line = r#"{"model":"qwen","created_at":"2024-01-01","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"set_light","arguments":{"on":true}}}]},"done":false}"#;
+    let out = LoroService::process_ollama_line_static(line, "rid", "model")
+        .unwrap()
+        .unwrap();
+    let v: serde_json::Value = serde_json::from_str(&out).unwrap();
+    let call = &v["choices"][0]["delta"]["tool_calls"][0];
+    assert_eq!(call["type"], "function");
+    assert!(call["id"].as_str().unwrap().starts_with("call_"));
+    assert_eq!(call["function"]["name"], "set_light");
+    let args: serde_json::Value =
+        serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+    assert_eq!(args, json!({"on": true}));
+}
+
+#[test]
+fn test_ollama_line_with_oversized_content_is_an_error() {
+    let line = json!({
+        "model": "qwen",
+        "created_at": "2024-01-01",
+        "message": {"role": "assistant", "content": "啊".repeat(400_000)},
+        "done": false
+    })
+    .to_string();
+    assert!(LoroService::process_ollama_line_static(&line, "rid", "model").is_err());
+}
+
+#[tokio::test]
+async fn test_quick_response_streams_before_tool_call() {
+    let upstream = spawn_mock_upstream("tool_calls").await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(tool_request().to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let payloads = sse_payloads(&String::from_utf8_lossy(&body));
+
+    // Quick acknowledgement first, then the tool call fragments, then [DONE]
+    let first: serde_json::Value = serde_json::from_str(&payloads[0]).unwrap();
+    assert_eq!(first["choices"][0]["delta"]["content"], "好的，");
+    let second: serde_json::Value = serde_json::from_str(&payloads[1]).unwrap();
+    assert_eq!(
+        second["choices"][0]["delta"]["tool_calls"][0]["function"]["name"],
+        "set_light"
+    );
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+
+    // Tools reach the large model but not the small model
+    let calls = upstream.calls();
+    let large = &calls.iter().find(|call| call.is_stream()).unwrap().body;
+    assert_eq!(large["tools"][0]["function"]["name"], "set_light");
+    assert_eq!(large["tool_choice"], "auto");
+    let small = &calls.iter().find(|call| !call.is_stream()).unwrap().body;
+    assert!(small.get("tools").is_none());
+    assert_eq!(small["messages"][0]["role"], "system");
+}
+
+#[tokio::test]
+async fn test_ollama_tool_call_stream_finishes_with_tool_calls() {
+    let upstream = spawn_mock_upstream("stop").await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.ollama_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+
+    let mut body = tool_request();
+    body["disable_quick_response"] = json!(true);
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let payloads = sse_payloads(&String::from_utf8_lossy(&body));
+
+    let call: serde_json::Value = serde_json::from_str(&payloads[0]).unwrap();
+    assert_eq!(
+        call["choices"][0]["delta"]["tool_calls"][0]["function"]["name"],
+        "set_light"
+    );
+    // Ollama reports `stop` after a tool call; clients expect `tool_calls`
+    let finish: serde_json::Value = serde_json::from_str(&payloads[1]).unwrap();
+    assert_eq!(finish["choices"][0]["finish_reason"], "tool_calls");
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+}
+
+#[tokio::test]
+async fn test_ollama_tool_calls_are_indexed_across_messages() {
+    let upstream = common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: Vec::new(),
+            tool_calls: vec![
+                MockToolCall {
+                    name: "set_light".to_string(),
+                    arguments: json!({"on": true}),
+                },
+                MockToolCall {
+                    name: "set_light".to_string(),
+                    arguments: json!({"on": false}),
+                },
+            ],
+            finish_reason: "stop".to_string(),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.ollama_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+
+    let mut body = tool_request();
+    body["disable_quick_response"] = json!(true);
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+
+    let response = app.oneshot(request).await.unwrap();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let payloads = sse_payloads(&String::from_utf8_lossy(&body));
+
+    // Ollama sends each call in its own message; indices continue across them
+    let indices: Vec<serde_json::Value> = payloads
+        .iter()
+        .filter_map(|payload| serde_json::from_str::<serde_json::Value>(payload).ok())
+        .filter_map(|chunk| {
+            chunk["choices"][0]["delta"]["tool_calls"]
+                .as_array()
+                .cloned()
+        })
+        .flatten()
+        .map(|call| call["index"].clone())
+        .collect();
+    assert_eq!(indices, vec![json!(0), json!(1)]);
+}
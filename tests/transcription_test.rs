@@ -0,0 +1,346 @@
+mod common;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, State},
+    http::{HeaderMap, Request, StatusCode},
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+use loro::{
+    config::{Config, TranscriptionConfig},
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+    transcription::{encode_multipart, multipart_boundary, parse_multipart, AudioUpload, FormPart},
+};
+
+const BOUNDARY: &str = "test-boundary";
+const AUDIO: &[u8] = b"RIFF\x24\x00\x00\x00WAVEfmt \x00\xff\r\n--not-a-boundary";
+
+#[derive(Default)]
+struct MockState {
+    transcription_forms: Vec<HashMap<String, String>>,
+    audio: Vec<Bytes>,
+    transcript: String,
+    fail: bool,
+}
+
+type Shared = Arc<Mutex<MockState>>;
+
+async fn mock_transcriptions(
+    State(state): State<Shared>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> axum::response::Response {
+    let content_type = headers["content-type"].to_str().unwrap();
+    let boundary = multipart_boundary(content_type).unwrap();
+    let parts = parse_multipart(&body, &boundary).unwrap();
+
+    let mut state = state.lock().unwrap();
+    let mut form = HashMap::new();
+    for part in parts {
+        if part.name == "file" {
+            form.insert("filename".to_string(), part.filename.unwrap_or_default());
+            state.audio.push(part.data);
+        } else {
+            form.insert(part.name, String::from_utf8(part.data.to_vec()).unwrap());
+        }
+    }
+    state.transcription_forms.push(form);
+
+    if state.fail {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "model not loaded").into_response();
+    }
+    Json(json!({"text": format!(" {} ", state.transcript), "language": "zh"})).into_response()
+}
+
+/// The transcription backend runs beside the mock upstream, which serves the chat models
+async fn create_app(transcript: &str, transcription: bool) -> (Router, Shared, MockUpstream) {
+    let upstream = common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            tokens: vec!["晴天。".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let state: Shared = Arc::new(Mutex::new(MockState {
+        transcript: transcript.to_string(),
+        ..Default::default()
+    }));
+    let backend = Router::new()
+        .route("/v1/audio/transcriptions", post(mock_transcriptions))
+        .with_state(Arc::clone(&state));
+    let backend_url = format!("{}/v1", common::serve(backend).await);
+    let config = Config {
+        transcription: transcription.then(|| TranscriptionConfig {
+            backend: common::model(&backend_url, "whisper-small"),
+            language: Some("zh".to_string()),
+        }),
+        max_audio_upload_bytes: 1024 * 1024,
+        ..common::test_config(&upstream.openai_base_url())
+    };
+    let service = LoroService::new(config).await.unwrap();
+    let app = Router::new()
+        .route("/v1/audio/transcriptions", post(loro::audio_transcriptions))
+        .route("/v1/voice/turn", post(loro::voice_turn))
+        .layer(DefaultBodyLimit::max(1024 * 1024))
+        .with_state(Arc::new(service));
+    (app, state, upstream)
+}
+
+fn multipart_request(uri: &str, fields: &[(&str, &str)]) -> Request<Body> {
+    let mut parts = vec![FormPart::file(
+        "file",
+        "utterance.wav",
+        "audio/wav",
+        Bytes::from_static(AUDIO),
+    )];
+    parts.extend(
+        fields
+            .iter()
+            .map(|(name, value)| FormPart::text(name, value)),
+    );
+    Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(encode_multipart(BOUNDARY, &parts)))
+        .unwrap()
+}
+
+async fn body_text(response: axum::response::Response) -> String {
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(bytes.to_vec()).unwrap()
+}
+
+#[test]
+fn test_multipart_round_trip() {
+    let parts = vec![
+        FormPart::file("file", "a.wav", "audio/wav", Bytes::from_static(AUDIO)),
+        FormPart::text("model", "loro-quick"),
+        FormPart::text("empty", ""),
+    ];
+    let body = Bytes::from(encode_multipart(BOUNDARY, &parts));
+    let parsed = parse_multipart(&body, BOUNDARY).unwrap();
+
+    assert_eq!(parsed.len(), 3);
+    assert_eq!(parsed[0].name, "file");
+    assert_eq!(parsed[0].filename.as_deref(), Some("a.wav"));
+    assert_eq!(parsed[0].content_type.as_deref(), Some("audio/wav"));
+    assert_eq!(&parsed[0].data[..], AUDIO);
+    assert_eq!(&parsed[1].data[..], b"loro-quick");
+    assert!(parsed[2].data.is_empty());
+}
+
+#[test]
+fn test_multipart_boundary_parsing() {
+    assert_eq!(
+        multipart_boundary("multipart/form-data; boundary=abc").as_deref(),
+        Some("abc")
+    );
+    assert_eq!(
+        multipart_boundary("multipart/form-data; charset=utf-8; Boundary=\"x y\"").as_deref(),
+        Some("x y")
+    );
+    assert_eq!(multipart_boundary("multipart/form-data"), None);
+}
+
+#[test]
+fn test_malformed_multipart_is_rejected() {
+    let truncated =
+        Bytes::from_static(b"--b\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nabc");
+    assert!(parse_multipart(&truncated, "b").is_err());
+    assert!(parse_multipart(&Bytes::from_static(b"no boundary"), "b").is_err());
+}
+
+#[test]
+fn test_audio_upload_from_raw_body() {
+    let upload = AudioUpload::from_body(Some("audio/ogg"), Bytes::from_static(b"OggS")).unwrap();
+    assert_eq!(upload.filename, "audio.ogg");
+    assert_eq!(upload.content_type, "audio/ogg");
+
+    assert!(AudioUpload::from_body(Some("audio/wav"), Bytes::new()).is_err());
+    assert!(AudioUpload::from_body(Some("application/json"), Bytes::from_static(b"{}")).is_err());
+}
+
+#[test]
+fn test_voice_turn_request_from_fields() {
+    let body = Bytes::from(encode_multipart(
+        BOUNDARY,
+        &[
+            FormPart::file("file", "a.wav", "audio/wav", Bytes::from_static(AUDIO)),
+            FormPart::text("model", "loro-direct"),
+            FormPart::text("max_tokens", "64"),
+            FormPart::text("messages", r#"[{"role":"system","content":"简短回答"}]"#),
+        ],
+    ));
+    let content_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+    let upload = AudioUpload::from_body(Some(&content_type), body).unwrap();
+    let request = upload.chat_request("你好").unwrap();
+
+    assert_eq!(request.model, "loro-direct");
+    assert_eq!(request.max_tokens, Some(64));
+    assert_eq!(request.messages.len(), 2);
+    assert_eq!(request.messages[1].role, "user");
+    assert_eq!(request.messages[1].content.text(), "你好");
+    assert!(request.validate().is_ok());
+
+    let mut bad = upload.clone();
+    bad.fields
+        .insert("max_tokens".to_string(), "lots".to_string());
+    assert!(bad.chat_request("你好").is_err());
+}
+
+#[tokio::test]
+async fn test_transcription_endpoint_forwards_audio() {
+    let (app, state, _upstream) = create_app("今天天气怎么样", true).await;
+
+    let response = app
+        .oneshot(multipart_request(
+            "/v1/audio/transcriptions",
+            &[("prompt", "天气")],
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(body["text"], "今天天气怎么样");
+    assert_eq!(body["language"], "zh");
+
+    let state = state.lock().unwrap();
+    assert_eq!(&state.audio[0][..], AUDIO);
+    let form = &state.transcription_forms[0];
+    assert_eq!(form["model"], "whisper-small");
+    assert_eq!(form["language"], "zh");
+    assert_eq!(form["prompt"], "天气");
+    assert_eq!(form["filename"], "utterance.wav");
+}
+
+#[tokio::test]
+async fn test_transcription_endpoint_text_format_with_raw_body() {
+    let (app, state, _upstream) = create_app("你好", true).await;
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/audio/transcriptions?response_format=text&language=en")
+                .header("content-type", "audio/wav")
+                .body(Body::from(AUDIO))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body_text(response).await, "你好");
+    assert_eq!(
+        state.lock().unwrap().transcription_forms[0]["language"],
+        "en"
+    );
+}
+
+#[tokio::test]
+async fn test_voice_turn_streams_transcript_then_reply() {
+    let (app, _, upstream) = create_app("今天天气怎么样", true).await;
+
+    let response = app
+        .oneshot(multipart_request("/v1/voice/turn", &[]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_text(response).await;
+
+    let mut lines = body.lines().filter(|line| !line.is_empty());
+    assert_eq!(lines.next(), Some("event: transcript"));
+    let transcript: Value =
+        serde_json::from_str(lines.next().unwrap().strip_prefix("data: ").unwrap()).unwrap();
+    assert_eq!(transcript["text"], "今天天气怎么样");
+
+    let payloads: Vec<&str> = lines.filter_map(|l| l.strip_prefix("data: ")).collect();
+    assert_eq!(payloads.len(), 4);
+    let quick: Value = serde_json::from_str(payloads[0]).unwrap();
+    assert_eq!(quick["choices"][0]["delta"]["content"], "好的，");
+    assert!(payloads[1].contains("晴天。"));
+    assert!(payloads[2].contains("\"finish_reason\":\"stop\""));
+    assert_eq!(payloads[3], "[DONE]");
+
+    // The transcript is the user message sent to the large model
+    let calls = upstream.calls();
+    let large = &calls.iter().find(|call| call.is_stream()).unwrap().body;
+    let last = large["messages"].as_array().unwrap().last().unwrap();
+    assert_eq!(last["role"], "user");
+    assert_eq!(last["content"], "今天天气怎么样");
+}
+
+#[tokio::test]
+async fn test_voice_turn_without_speech_is_rejected() {
+    let (app, _, upstream) = create_app("   ", true).await;
+
+    let response = app
+        .oneshot(multipart_request("/v1/voice/turn", &[]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(body["error"]["code"], "no_speech");
+    assert!(upstream.calls().is_empty());
+}
+
+#[tokio::test]
+async fn test_transcription_backend_error_maps_to_bad_gateway() {
+    let (app, state, _upstream) = create_app("你好", true).await;
+    state.lock().unwrap().fail = true;
+
+    let response = app
+        .oneshot(multipart_request("/v1/voice/turn", &[]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(body["error"]["code"], "upstream_error");
+}
+
+#[tokio::test]
+async fn test_transcription_not_configured() {
+    let (app, state, _upstream) = create_app("你好", false).await;
+
+    let response = app
+        .oneshot(multipart_request("/v1/audio/transcriptions", &[]))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = serde_json::from_str(&body_text(response).await).unwrap();
+    assert_eq!(body["error"]["code"], "transcription_not_configured");
+    assert!(state.lock().unwrap().transcription_forms.is_empty());
+}
+
+#[tokio::test]
+async fn test_missing_audio_file_is_rejected() {
+    let (app, _, _upstream) = create_app("你好", true).await;
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/v1/voice/turn")
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(encode_multipart(
+            BOUNDARY,
+            &[FormPart::text("model", "loro-quick")],
+        )))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+use loro::{
+    config::Config,
+    errors::LoroError,
+    mock_upstream::{InjectedError, MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+};
+
+/// Large model that always fails with `status`, or hangs when it is 0
+async fn spawn_upstream(status: u16) -> MockUpstream {
+    let stream = if status == 0 {
+        ReplyScript {
+            latency_ms: 10_000,
+            ..Default::default()
+        }
+    } else {
+        ReplyScript {
+            error: Some(InjectedError {
+                status,
+                message: "quota exceeded for key sk-live-123".to_string(),
+                times: None,
+            }),
+            ..Default::default()
+        }
+    };
+    common::mock_upstream(MockScript {
+        stream,
+        ..Default::default()
+    })
+    .await
+}
+
+fn create_test_config(upstream: &MockUpstream) -> Config {
+    Config {
+        http_timeout_secs: 1,
+        small_model_timeout_secs: 1,
+        ..common::test_config(&upstream.openai_base_url())
+    }
+}
+
+async fn direct_request(config: Config) -> (StatusCode, Value) {
+    let service = Arc::new(LoroService::new(config).await.unwrap());
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"model": "loro-direct", "messages": [{"role": "user", "content": "你好"}]})
+                        .to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+#[test]
+fn test_api_error_status_mapping() {
+    let api_error = |status| LoroError::ApiError {
+        provider: "large".to_string(),
+        status,
+        message: String::new(),
+    };
+    assert_eq!(api_error(429).status_code(), 429);
+    assert_eq!(api_error(503).status_code(), 503);
+    assert_eq!(api_error(504).status_code(), 504);
+    for status in [400, 401, 404, 500, 502] {
+        assert_eq!(api_error(status).status_code(), 502);
+    }
+    assert_eq!(LoroError::Timeout { timeout_secs: 5 }.status_code(), 504);
+    assert_eq!(
+        LoroError::LargeModelFailed("boom".to_string()).status_code(),
+        502
+    );
+    assert_eq!(LoroError::Validation("bad".to_string()).status_code(), 400);
+    assert_eq!(LoroError::Internal("bug".to_string()).status_code(), 500);
+}
+
+#[tokio::test]
+async fn test_upstream_statuses_are_mapped() {
+    let cases = [
+        (429, StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+        (401, StatusCode::BAD_GATEWAY, "upstream_error"),
+        (404, StatusCode::BAD_GATEWAY, "upstream_error"),
+        (503, StatusCode::SERVICE_UNAVAILABLE, "upstream_unavailable"),
+    ];
+    for (upstream, expected, code) in cases {
+        let mock = spawn_upstream(upstream).await;
+        let (status, body) = direct_request(create_test_config(&mock)).await;
+        assert_eq!(status, expected, "upstream {}", upstream);
+        assert_eq!(body["error"]["code"], code, "upstream {}", upstream);
+        assert!(body["error"].get("upstream").is_none());
+        assert!(!body.to_string().contains("sk-live-123"));
+    }
+}
+
+#[tokio::test]
+async fn test_unreachable_upstream_is_unavailable() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+
+    let (status, body) = direct_request(Config {
+        http_timeout_secs: 1,
+        ..common::test_config(&format!("http://{}/v1", addr))
+    })
+    .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "upstream_unavailable");
+}
+
+#[tokio::test]
+async fn test_upstream_timeout_is_gateway_timeout() {
+    let upstream = spawn_upstream(0).await;
+    let (status, body) = direct_request(create_test_config(&upstream)).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body["error"]["type"], "timeout_error");
+    assert_eq!(body["error"]["code"], "upstream_timeout");
+}
+
+#[tokio::test]
+async fn test_upstream_details_behind_debug_flag() {
+    let upstream = spawn_upstream(429).await;
+    let mut config = create_test_config(&upstream);
+    config.expose_upstream_errors = true;
+
+    let (status, body) = direct_request(config).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let upstream = &body["error"]["upstream"];
+    assert_eq!(upstream["provider"], "large");
+    assert_eq!(upstream["status"], 429);
+    assert!(upstream["message"]
+        .as_str()
+        .unwrap()
+        .contains("quota exceeded"));
+}
@@ -0,0 +1,263 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    routing::post,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+use loro::{
+    config::Config,
+    mock_upstream::{MockScript, MockUpstream, ReplyScript},
+    service::LoroService,
+    usage::{estimate_tokens, key_label, UsageCollector, OTHER_KEY},
+};
+use secrecy::Secret;
+
+/// Mock upstream that reports usage (10 prompt tokens, one completion token per character) or not
+async fn spawn_mock_upstream(report_usage: bool) -> MockUpstream {
+    common::mock_upstream(MockScript {
+        quick: ReplyScript {
+            tokens: vec!["好的，".to_string()],
+            report_usage,
+            ..Default::default()
+        },
+        stream: ReplyScript {
+            tokens: vec!["今天是晴天。".to_string()],
+            report_usage,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await
+}
+
+/// Streams a chat request with an optional bearer key and returns the SSE data payloads
+async fn stream_payloads(
+    service: Arc<LoroService>,
+    body: Value,
+    api_key: Option<&str>,
+) -> Vec<String> {
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(service);
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/v1/chat/completions")
+        .header("content-type", "application/json");
+    if let Some(key) = api_key {
+        request = request.header("authorization", format!("Bearer {}", key));
+    }
+    let response = app
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    std::str::from_utf8(&bytes)
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(str::to_string)
+        .collect()
+}
+
+fn usage_entry<'a>(metrics: &'a Value, api_key: &str, model: &str) -> &'a Value {
+    metrics["usage"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["api_key"] == api_key && entry["model"] == model)
+        .unwrap_or_else(|| {
+            panic!(
+                "no usage entry for {} / {}: {}",
+                api_key, model, metrics["usage"]
+            )
+        })
+}
+
+#[test]
+fn test_token_estimate_and_key_label() {
+    assert_eq!(estimate_tokens("今天天气"), 4);
+    assert_eq!(estimate_tokens("hello world"), 3);
+    assert_eq!(estimate_tokens("你好 ok"), 3);
+    assert_eq!(estimate_tokens(""), 0);
+
+    assert_eq!(key_label(None), "anonymous");
+    assert_eq!(key_label(Some("short")), "…");
+    assert_eq!(key_label(Some("sk-test-abcd1234")), "sk-…1234");
+}
+
+#[test]
+fn test_unconfigured_keys_share_one_bucket() {
+    let collector = UsageCollector::new().with_client_keys(["sk-test-abcd1234"]);
+    let known = collector.client_key(Some("sk-test-abcd1234"));
+    assert_eq!(known.label(), "sk-…1234");
+    assert_eq!(collector.client_key(None).label(), "anonymous");
+
+    let first = collector.client_key(Some("sk-random-0001"));
+    let second = collector.client_key(Some("sk-random-0002"));
+    assert_eq!(first.label(), OTHER_KEY);
+    assert_eq!(first, second);
+    assert_ne!(first, known);
+}
+
+#[tokio::test]
+async fn test_unconfigured_keys_are_reported_as_other() {
+    let upstream = spawn_mock_upstream(true).await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+
+    for key in ["sk-random-0001", "sk-random-0002"] {
+        stream_payloads(
+            Arc::clone(&service),
+            json!({"model": "loro-direct", "messages": [{"role": "user", "content": "你好"}]}),
+            Some(key),
+        )
+        .await;
+    }
+
+    let metrics = service.get_metrics().await;
+    assert_eq!(metrics["usage"].as_array().unwrap().len(), 1);
+    let large = usage_entry(&metrics, OTHER_KEY, "large-model");
+    assert_eq!(large["requests"], 2);
+}
+
+#[tokio::test]
+async fn test_reported_usage_is_summed_into_final_chunk() {
+    let upstream = spawn_mock_upstream(true).await;
+    let service = Arc::new(
+        LoroService::new(Config {
+            client_api_keys: vec![Secret::new("sk-test-abcd1234".to_string())],
+            ..common::test_config(&upstream.openai_base_url())
+        })
+        .await
+        .unwrap(),
+    );
+
+    let payloads = stream_payloads(
+        Arc::clone(&service),
+        json!({
+            "model": "loro-quick",
+            "messages": [{"role": "user", "content": "明天天气怎么样"}],
+            "stream_options": {"include_usage": true}
+        }),
+        Some("sk-test-abcd1234"),
+    )
+    .await;
+
+    assert_eq!(payloads.last().unwrap(), "[DONE]");
+    let usage_chunk: Value = serde_json::from_str(&payloads[payloads.len() - 2]).unwrap();
+    assert_eq!(usage_chunk["choices"], json!([]));
+    assert_eq!(
+        usage_chunk["usage"],
+        json!({"prompt_tokens": 20, "completion_tokens": 9, "total_tokens": 29})
+    );
+    // Content chunks never carry usage
+    for payload in &payloads[..payloads.len() - 2] {
+        let chunk: Value = serde_json::from_str(payload).unwrap();
+        assert!(chunk.get("usage").is_none(), "{}", payload);
+    }
+
+    // Usage is always requested from the upstream
+    let calls = upstream.calls();
+    let large_request = &calls.iter().find(|call| call.is_stream()).unwrap().body;
+    assert_eq!(large_request["stream_options"]["include_usage"], true);
+
+    let metrics = service.get_metrics().await;
+    let small = usage_entry(&metrics, "sk-…1234", "small-model");
+    assert_eq!(small["total_tokens"], 13);
+    assert_eq!(small["estimated_requests"], 0);
+    let large = usage_entry(&metrics, "sk-…1234", "large-model");
+    assert_eq!(large["requests"], 1);
+    assert_eq!(large["prompt_tokens"], 10);
+    assert_eq!(large["completion_tokens"], 6);
+}
+
+#[tokio::test]
+async fn test_usage_is_estimated_without_upstream_report() {
+    let upstream = spawn_mock_upstream(false).await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+
+    let payloads = stream_payloads(
+        Arc::clone(&service),
+        json!({"model": "loro-direct", "messages": [{"role": "user", "content": "你好"}]}),
+        None,
+    )
+    .await;
+
+    // No usage chunk unless the client asks for one
+    assert!(payloads
+        .iter()
+        .all(|payload| !payload.contains("\"usage\"")));
+
+    let metrics = service.get_metrics().await;
+    let large = usage_entry(&metrics, "anonymous", "large-model");
+    assert_eq!(large["requests"], 1);
+    assert_eq!(large["estimated_requests"], 1);
+    assert_eq!(large["completion_tokens"], estimate_tokens("今天是晴天。"));
+    assert!(large["prompt_tokens"].as_u64().unwrap() > 0);
+
+    service.reset_metrics().await;
+    assert_eq!(service.get_metrics().await["usage"], json!([]));
+}
+
+#[tokio::test]
+async fn test_usage_is_recorded_when_client_disconnects() {
+    let upstream = common::mock_upstream(MockScript {
+        stream: ReplyScript {
+            token_interval_ms: 50,
+            stall_after: Some(2),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let service = Arc::new(
+        LoroService::new(common::test_config(&upstream.openai_base_url()))
+            .await
+            .unwrap(),
+    );
+    let app = Router::new()
+        .route("/v1/chat/completions", post(loro::chat_completions))
+        .with_state(Arc::clone(&service));
+    let body = json!({"model": "loro-direct", "messages": [{"role": "user", "content": "你好"}]});
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/chat/completions")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    // Read the two tokens the upstream sends before it stalls, then hang up
+    let mut body = response.into_body();
+    let mut received = String::new();
+    while received.matches("data: ").count() < 2 {
+        let frame = body.frame().await.unwrap().unwrap();
+        received.push_str(std::str::from_utf8(frame.data_ref().unwrap()).unwrap());
+    }
+    drop(body);
+
+    let metrics = service.get_metrics().await;
+    let large = usage_entry(&metrics, "anonymous", "large-model");
+    assert_eq!(large["requests"], 1);
+    assert_eq!(large["estimated_requests"], 1);
+    assert_eq!(large["completion_tokens"], estimate_tokens("今天是晴天"));
+}